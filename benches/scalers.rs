@@ -0,0 +1,33 @@
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use blazeboy::{Scaler, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+// Frames per second each scaler manages on one core, against the Game Boy's ~59.73 Hz
+fn main() {
+    // Diagonals and flat areas in 4 shades, something like a real game screen
+    let frame: Vec<u32> = (0..SCREEN_WIDTH * SCREEN_HEIGHT)
+        .map(|i| {
+            let (x, y) = (i % SCREEN_WIDTH, i / SCREEN_WIDTH);
+            [0xFFFFFF, 0xAAAAAA, 0x555555, 0x000000][(x / 8 + y / 3 + x * y / 97) % 4]
+        })
+        .collect();
+    let full_speed = 4194304.0 / 70224.0;
+
+    for scaler in Scaler::ALL {
+        let start = Instant::now();
+        let mut frames = 0;
+        while start.elapsed() < Duration::from_secs(1) {
+            black_box(scaler.apply(black_box(&frame), SCREEN_WIDTH, SCREEN_HEIGHT));
+            frames += 1;
+        }
+        let fps = frames as f64 / start.elapsed().as_secs_f64();
+        println!(
+            "{:<12} {:>4}x {:>9.1} frames/s {:>6.1}x full speed",
+            format!("{:?}", scaler),
+            scaler.factor(),
+            fps,
+            fps / full_speed
+        );
+    }
+}
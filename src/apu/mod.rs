@@ -0,0 +1,366 @@
+pub mod noise;
+pub mod square;
+pub mod units;
+pub mod wave;
+
+use crate::io::{Io, IoRegister};
+use crate::state::{StateError, StateReader, StateWriter};
+use noise::Noise;
+use square::Square;
+use units::frequency;
+use wave::Wave;
+
+// The four sound channels, mixed through NR50/NR51. The registers themselves live in
+// Io like every other one, the APU decodes writes into the channel state and keeps the
+// status bits of NR52 up to date
+pub struct Apu {
+    pub square1: Square,
+    pub square2: Square,
+    pub wave: Wave,
+    pub noise: Noise,
+    power: bool,
+    // Next of the 8 frame sequencer steps
+    frame_step: u8,
+    div_bit: bool,
+    // NR50 and NR51
+    volume: u8,
+    panning: u8,
+    // Every mixed sample at SAMPLE_RATE, if capture is on
+    samples: Vec<[i16; 2]>,
+    capture: bool,
+    // The same for every channel on its own, before muting
+    channel_samples: [Vec<[i16; 2]>; 4],
+    capture_channels: bool,
+    // Channels left out of the mix
+    pub muted: [bool; 4],
+    pub solo: Option<usize>,
+}
+
+impl Apu {
+    // One sample per M-cycle
+    pub const SAMPLE_RATE: u32 = 1048576;
+    // The frame sequencer is clocked by this bit of the system counter falling, 512 Hz
+    const DIV_BIT: u16 = 12;
+
+    // Starts powered on, the way the boot ROM leaves it
+    pub fn new() -> Apu {
+        Apu {
+            square1: Square::new(true),
+            square2: Square::new(false),
+            wave: Wave::new(),
+            noise: Noise::new(),
+            power: true,
+            frame_step: 0,
+            div_bit: false,
+            volume: 0,
+            panning: 0,
+            samples: Vec::new(),
+            capture: false,
+            channel_samples: Default::default(),
+            capture_channels: false,
+            muted: [false; 4],
+            solo: None,
+        }
+    }
+
+    pub fn power(&self) -> bool {
+        self.power
+    }
+
+    // Whether channel 0-3 is playing, the low bits of NR52
+    pub fn channel_on(&self, channel: usize) -> bool {
+        match channel {
+            0 => self.square1.enabled,
+            1 => self.square2.enabled,
+            2 => self.wave.enabled,
+            3 => self.noise.enabled,
+            _ => false,
+        }
+    }
+
+    fn dac_enabled(&self, channel: usize) -> bool {
+        match channel {
+            0 => self.square1.dac_enabled,
+            1 => self.square2.dac_enabled,
+            2 => self.wave.dac_enabled,
+            _ => self.noise.dac_enabled,
+        }
+    }
+
+    // The 0-15 value channel 0-3 puts into its DAC
+    pub fn channel_output(&self, channel: usize) -> u8 {
+        match channel {
+            0 => self.square1.output(),
+            1 => self.square2.output(),
+            2 => self.wave.output(),
+            3 => self.noise.output(),
+            _ => 0,
+        }
+    }
+
+    // A DAC turns 0-15 into -15..15 (odd steps around 0), or 0 while it's off
+    pub fn dac_output(&self, channel: usize) -> i16 {
+        if !self.dac_enabled(channel) {
+            return 0;
+        }
+        self.channel_output(channel) as i16 * 2 - 15
+    }
+
+    // Whether channel 0-3 makes it into the mix, with the mutes and the solo applied
+    pub fn audible(&self, channel: usize) -> bool {
+        match self.solo {
+            Some(solo) => solo == channel,
+            None => !self.muted[channel],
+        }
+    }
+
+    // Left and right output of the channels selected by `channels`, through NR50 and NR51
+    fn mix_channels(&self, channels: impl Fn(usize) -> bool) -> [i16; 2] {
+        let mut output = [0; 2];
+        for (side, sample) in output.iter_mut().enumerate() {
+            // NR50 and NR51 have the left side in the high nibble
+            let shift = if side == 0 { 4 } else { 0 };
+            let volume = (self.volume >> shift & 0x7) as i16 + 1;
+            let sum: i16 = (0..4)
+                .filter(|&channel| channels(channel) && self.panning >> (shift + channel) & 1 != 0)
+                .map(|channel| self.dac_output(channel))
+                .sum();
+            *sample = sum * volume * 68;
+        }
+        output
+    }
+
+    // Current left and right output. Four channels at full volume use the whole i16 range
+    pub fn mix(&self) -> [i16; 2] {
+        self.mix_channels(|channel| self.audible(channel))
+    }
+
+    // Output of a single channel as if it was the only one, ignoring mutes
+    pub fn channel_mix(&self, channel: usize) -> [i16; 2] {
+        self.mix_channels(|other| other == channel)
+    }
+
+    // Collects a mixed sample every M-cycle until turned off
+    pub fn set_capture(&mut self, capture: bool) {
+        self.capture = capture;
+        if !capture {
+            self.samples.clear();
+        }
+    }
+
+    pub fn take_samples(&mut self) -> Vec<[i16; 2]> {
+        std::mem::take(&mut self.samples)
+    }
+
+    // Collects every channel's own output as well, for stems
+    pub fn set_channel_capture(&mut self, capture: bool) {
+        self.capture_channels = capture;
+        if !capture {
+            self.channel_samples = Default::default();
+        }
+    }
+
+    pub fn take_channel_samples(&mut self) -> [Vec<[i16; 2]>; 4] {
+        std::mem::take(&mut self.channel_samples)
+    }
+
+    // Advances the APU by one M-cycle. `counter` is the timer's system counter (DIV)
+    pub fn step(&mut self, io: &mut Io, counter: u16) {
+        let div_bit = counter >> Self::DIV_BIT & 1 != 0;
+        let falling = self.div_bit && !div_bit;
+        self.div_bit = div_bit;
+        if self.power {
+            if falling {
+                self.clock_frame_sequencer(io);
+            }
+            self.square1.step(4);
+            self.square2.step(4);
+            self.wave.step(4, io);
+            self.noise.step(4);
+        }
+        self.update_status(io);
+        if self.capture {
+            let sample = self.mix();
+            self.samples.push(sample);
+        }
+        if self.capture_channels {
+            let samples: [[i16; 2]; 4] = std::array::from_fn(|channel| self.channel_mix(channel));
+            for (channel, sample) in self.channel_samples.iter_mut().zip(samples) {
+                channel.push(sample);
+            }
+        }
+    }
+
+    // Length at 256 Hz, sweep at 128 Hz and envelopes at 64 Hz
+    fn clock_frame_sequencer(&mut self, io: &mut Io) {
+        let step = self.frame_step;
+        self.frame_step = (step + 1) % 8;
+        if step.is_multiple_of(2) {
+            self.square1.clock_length();
+            self.square2.clock_length();
+            self.wave.clock_length();
+            self.noise.clock_length();
+        }
+        if step == 2 || step == 6 {
+            if let Some(frequency) = self.square1.clock_sweep() {
+                io.set(0xFF13, frequency as u8);
+                io.set(0xFF14, io.get(0xFF14) & !0x7 | (frequency >> 8) as u8);
+            }
+        }
+        if step == 7 {
+            self.square1.envelope.clock();
+            self.square2.envelope.clock();
+            self.noise.envelope.clock();
+        }
+    }
+
+    fn update_status(&self, io: &mut Io) {
+        let status = (0..4).fold(0, |status, channel| {
+            status | (self.channel_on(channel) as u8) << channel
+        });
+        io.set(0xFF26, io.get(0xFF26) & 0x80 | status);
+    }
+
+    // Writes to 0xFF10-0xFF3F
+    pub fn write(&mut self, io: &mut Io, address: u16, data: u8, cgb: bool) {
+        match address {
+            0xFF26 => {
+                io.write(address, data, cgb);
+                self.set_power(io, data & 0x80 != 0, cgb);
+            }
+            // Wave RAM keeps working with the power off
+            0xFF30..=0xFF3F => io.write(address, data, cgb),
+            // The DMG still takes the length counters while the power is off
+            _ if !self.power => {
+                if !cgb {
+                    self.write_length(address, data);
+                }
+            }
+            _ => {
+                io.write(address, data, cgb);
+                self.write_register(io, address, data);
+            }
+        }
+        self.update_status(io);
+    }
+
+    fn write_length(&mut self, address: u16, data: u8) {
+        match address {
+            0xFF11 => self.square1.length.load(data as u16 & 0x3F),
+            0xFF16 => self.square2.length.load(data as u16 & 0x3F),
+            0xFF1B => self.wave.write_length(data),
+            0xFF20 => self.noise.write_length(data),
+            _ => (),
+        }
+    }
+
+    fn write_register(&mut self, io: &Io, address: u16, data: u8) {
+        // The last frame sequencer step clocked the length counters
+        let length_step = self.frame_step % 2 == 1;
+        match address {
+            0xFF10 => self.square1.write_sweep(data),
+            0xFF11 => self.square1.write_duty_length(data),
+            0xFF12 => self.square1.write_envelope(data),
+            0xFF13 => self.square1.frequency = frequency(data, io.get(0xFF14)),
+            0xFF14 => {
+                self.square1.frequency = frequency(io.get(0xFF13), data);
+                self.square1.write_control(data, length_step);
+            }
+            0xFF16 => self.square2.write_duty_length(data),
+            0xFF17 => self.square2.write_envelope(data),
+            0xFF18 => self.square2.frequency = frequency(data, io.get(0xFF19)),
+            0xFF19 => {
+                self.square2.frequency = frequency(io.get(0xFF18), data);
+                self.square2.write_control(data, length_step);
+            }
+            0xFF1A => self.wave.write_dac(data),
+            0xFF1B => self.wave.write_length(data),
+            0xFF1C => self.wave.write_volume(data),
+            0xFF1D => self.wave.frequency = frequency(data, io.get(0xFF1E)),
+            0xFF1E => {
+                self.wave.frequency = frequency(io.get(0xFF1D), data);
+                self.wave.write_control(data, length_step);
+            }
+            0xFF20 => self.noise.write_length(data),
+            0xFF21 => self.noise.write_envelope(data),
+            0xFF22 => self.noise.write_polynomial(data),
+            0xFF23 => self.noise.write_control(data, length_step),
+            0xFF24 => self.volume = data,
+            0xFF25 => self.panning = data,
+            _ => (),
+        }
+    }
+
+    // Turning the APU off clears NR10-NR51 and stops every channel. The DMG keeps the
+    // length counters
+    fn set_power(&mut self, io: &mut Io, power: bool, cgb: bool) {
+        if power == self.power {
+            return;
+        }
+        self.power = power;
+        if power {
+            self.frame_step = 0;
+            return;
+        }
+        let lengths = [
+            self.square1.length.counter,
+            self.square2.length.counter,
+            self.wave.length.counter,
+            self.noise.length.counter,
+        ];
+        (self.square1, self.square2) = (Square::new(true), Square::new(false));
+        (self.wave, self.noise) = (Wave::new(), Noise::new());
+        if !cgb {
+            self.square1.length.counter = lengths[0];
+            self.square2.length.counter = lengths[1];
+            self.wave.length.counter = lengths[2];
+            self.noise.length.counter = lengths[3];
+        }
+        (self.volume, self.panning) = (0, 0);
+        for address in 0xFF10..=0xFF25 {
+            io.set(address, 0);
+        }
+    }
+
+    // What the CPU sees when reading 0xFF10-0xFF3F
+    pub fn read(&self, io: &Io, address: u16, cgb: bool) -> u8 {
+        // Wave RAM reads return the byte the channel is playing while it's on
+        let address = match address {
+            0xFF30..=0xFF3F if self.wave.enabled => 0xFF30 + self.wave.position() as u16,
+            _ => address,
+        };
+        io.get(address) | IoRegister::get(address, cgb).read_mask()
+    }
+
+    // The mixer settings and captured samples belong to the frontend and aren't saved
+    pub fn save_state(&self, state: &mut StateWriter) {
+        self.square1.save_state(state);
+        self.square2.save_state(state);
+        self.wave.save_state(state);
+        self.noise.save_state(state);
+        state.bool(self.power);
+        state.u8(self.frame_step);
+        state.bool(self.div_bit);
+        state.u8(self.volume);
+        state.u8(self.panning);
+    }
+
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        self.square1.load_state(state)?;
+        self.square2.load_state(state)?;
+        self.wave.load_state(state)?;
+        self.noise.load_state(state)?;
+        self.power = state.bool()?;
+        self.frame_step = state.u8()?;
+        self.div_bit = state.bool()?;
+        self.volume = state.u8()?;
+        self.panning = state.u8()?;
+        Ok(())
+    }
+}
+
+impl Default for Apu {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,131 @@
+use super::units::{Envelope, LengthCounter};
+use crate::state::{StateError, StateReader, StateWriter};
+
+const DIVISORS: [i32; 8] = [8, 16, 32, 48, 64, 80, 96, 112];
+
+// Channel 4: pseudo random noise from a 15 bit LFSR, or a 7 bit one with NR43 bit 3 set
+pub struct Noise {
+    pub enabled: bool,
+    pub dac_enabled: bool,
+    pub length: LengthCounter,
+    pub envelope: Envelope,
+    pub lfsr: u16,
+    shift: u8,
+    short_mode: bool,
+    divisor: u8,
+    timer: i32,
+}
+
+impl Noise {
+    pub fn new() -> Noise {
+        Noise {
+            enabled: false,
+            dac_enabled: false,
+            length: LengthCounter::new(64),
+            envelope: Envelope::new(),
+            lfsr: 0x7FFF,
+            shift: 0,
+            short_mode: false,
+            divisor: 0,
+            timer: 0,
+        }
+    }
+
+    fn period(&self) -> i32 {
+        DIVISORS[self.divisor as usize] << self.shift
+    }
+
+    pub fn step(&mut self, cycles: i32) {
+        self.timer -= cycles;
+        while self.timer <= 0 {
+            self.timer += self.period();
+            // Shifts 14 and 15 stop the LFSR
+            if self.shift >= 14 {
+                continue;
+            }
+            let bit = (self.lfsr ^ (self.lfsr >> 1)) & 1;
+            self.lfsr = (self.lfsr >> 1) | (bit << 14);
+            if self.short_mode {
+                self.lfsr = (self.lfsr & !0x40) | (bit << 6);
+            }
+        }
+    }
+
+    pub fn output(&self) -> u8 {
+        if !self.enabled || self.lfsr & 1 != 0 {
+            return 0;
+        }
+        self.envelope.volume
+    }
+
+    pub fn write_length(&mut self, value: u8) {
+        self.length.load(value as u16 & 0x3F);
+    }
+
+    pub fn write_envelope(&mut self, value: u8) {
+        self.envelope.write(value);
+        self.dac_enabled = value & 0xF8 != 0;
+        if !self.dac_enabled {
+            self.enabled = false;
+        }
+    }
+
+    pub fn write_polynomial(&mut self, value: u8) {
+        self.shift = value >> 4;
+        self.short_mode = value & 0x08 != 0;
+        self.divisor = value & 0x7;
+    }
+
+    pub fn write_control(&mut self, value: u8, length_step: bool) {
+        let trigger = value & 0x80 != 0;
+        if !self
+            .length
+            .write_control(value & 0x40 != 0, trigger, length_step)
+        {
+            self.enabled = false;
+        }
+        if trigger {
+            self.enabled = self.dac_enabled;
+            self.lfsr = 0x7FFF;
+            self.timer = self.period();
+            self.envelope.trigger();
+        }
+    }
+
+    pub fn clock_length(&mut self) {
+        if !self.length.clock() {
+            self.enabled = false;
+        }
+    }
+
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.bool(self.enabled);
+        state.bool(self.dac_enabled);
+        self.length.save_state(state);
+        self.envelope.save_state(state);
+        state.u16(self.lfsr);
+        state.u8(self.shift);
+        state.bool(self.short_mode);
+        state.u8(self.divisor);
+        state.u32(self.timer as u32);
+    }
+
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        self.enabled = state.bool()?;
+        self.dac_enabled = state.bool()?;
+        self.length.load_state(state)?;
+        self.envelope.load_state(state)?;
+        self.lfsr = state.u16()?;
+        self.shift = state.u8()?;
+        self.short_mode = state.bool()?;
+        self.divisor = state.u8()?;
+        self.timer = state.u32()? as i32;
+        Ok(())
+    }
+}
+
+impl Default for Noise {
+    fn default() -> Self {
+        Self::new()
+    }
+}
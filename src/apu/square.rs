@@ -0,0 +1,228 @@
+use super::units::{Envelope, LengthCounter};
+use crate::state::{StateError, StateReader, StateWriter};
+
+const DUTY_PATTERNS: [u8; 4] = [0b0000_0001, 0b1000_0001, 0b1000_0111, 0b0111_1110];
+
+// NR10, only channel 1 has it
+struct Sweep {
+    period: u8,
+    negate: bool,
+    shift: u8,
+    timer: u8,
+    enabled: bool,
+    shadow: u16,
+    // A calculation in negate mode happened since the last trigger
+    negated: bool,
+}
+
+impl Sweep {
+    fn new() -> Sweep {
+        Sweep {
+            period: 0,
+            negate: false,
+            shift: 0,
+            timer: 0,
+            enabled: false,
+            shadow: 0,
+            negated: false,
+        }
+    }
+
+    fn calculate(&mut self) -> u16 {
+        let delta = self.shadow >> self.shift;
+        if self.negate {
+            self.negated = true;
+            self.shadow - delta
+        } else {
+            self.shadow + delta
+        }
+    }
+
+    fn save_state(&self, state: &mut StateWriter) {
+        state.bytes(&[self.period, self.shift, self.timer]);
+        state.bool(self.negate);
+        state.bool(self.enabled);
+        state.u16(self.shadow);
+        state.bool(self.negated);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        let mut bytes = [0; 3];
+        state.bytes(&mut bytes)?;
+        [self.period, self.shift, self.timer] = bytes;
+        self.negate = state.bool()?;
+        self.enabled = state.bool()?;
+        self.shadow = state.u16()?;
+        self.negated = state.bool()?;
+        Ok(())
+    }
+}
+
+// Channels 1 and 2: a square wave with 4 duty cycles, an envelope and a length counter
+pub struct Square {
+    pub enabled: bool,
+    pub dac_enabled: bool,
+    pub length: LengthCounter,
+    pub envelope: Envelope,
+    pub frequency: u16,
+    sweep: Option<Sweep>,
+    duty: u8,
+    position: u8,
+    // T-cycles until the next duty step
+    timer: i32,
+}
+
+impl Square {
+    pub fn new(sweep: bool) -> Square {
+        Square {
+            enabled: false,
+            dac_enabled: false,
+            length: LengthCounter::new(64),
+            envelope: Envelope::new(),
+            frequency: 0,
+            sweep: sweep.then(Sweep::new),
+            duty: 0,
+            position: 0,
+            timer: 0,
+        }
+    }
+
+    fn period(&self) -> i32 {
+        (2048 - self.frequency as i32) * 4
+    }
+
+    pub fn step(&mut self, cycles: i32) {
+        self.timer -= cycles;
+        while self.timer <= 0 {
+            self.timer += self.period();
+            self.position = (self.position + 1) % 8;
+        }
+    }
+
+    // 0-15, what goes into the DAC
+    pub fn output(&self) -> u8 {
+        if !self.enabled {
+            return 0;
+        }
+        let high = DUTY_PATTERNS[self.duty as usize] >> (7 - self.position) & 1;
+        high * self.envelope.volume
+    }
+
+    pub fn write_sweep(&mut self, value: u8) {
+        if let Some(sweep) = &mut self.sweep {
+            sweep.period = (value >> 4) & 0x7;
+            sweep.shift = value & 0x7;
+            let negate = value & 0x08 != 0;
+            // Leaving negate mode after using it kills the channel
+            if sweep.negate && !negate && sweep.negated {
+                self.enabled = false;
+            }
+            sweep.negate = negate;
+        }
+    }
+
+    pub fn write_duty_length(&mut self, value: u8) {
+        self.duty = value >> 6;
+        self.length.load(value as u16 & 0x3F);
+    }
+
+    pub fn write_envelope(&mut self, value: u8) {
+        self.envelope.write(value);
+        self.dac_enabled = value & 0xF8 != 0;
+        if !self.dac_enabled {
+            self.enabled = false;
+        }
+    }
+
+    pub fn write_control(&mut self, value: u8, length_step: bool) {
+        let trigger = value & 0x80 != 0;
+        if !self
+            .length
+            .write_control(value & 0x40 != 0, trigger, length_step)
+        {
+            self.enabled = false;
+        }
+        if trigger {
+            self.trigger();
+        }
+    }
+
+    fn trigger(&mut self) {
+        self.enabled = self.dac_enabled;
+        self.timer = self.period();
+        self.envelope.trigger();
+        let frequency = self.frequency;
+        if let Some(sweep) = &mut self.sweep {
+            sweep.shadow = frequency;
+            sweep.timer = if sweep.period == 0 { 8 } else { sweep.period };
+            sweep.enabled = sweep.period != 0 || sweep.shift != 0;
+            sweep.negated = false;
+            if sweep.shift != 0 && sweep.calculate() > 2047 {
+                self.enabled = false;
+            }
+        }
+    }
+
+    // 128 Hz. Returns the new frequency when the sweep changed it, so it ends up in NR13/NR14
+    pub fn clock_sweep(&mut self) -> Option<u16> {
+        let sweep = self.sweep.as_mut()?;
+        sweep.timer = sweep.timer.saturating_sub(1);
+        if sweep.timer > 0 {
+            return None;
+        }
+        sweep.timer = if sweep.period == 0 { 8 } else { sweep.period };
+        if !sweep.enabled || sweep.period == 0 {
+            return None;
+        }
+        let frequency = sweep.calculate();
+        if frequency > 2047 {
+            self.enabled = false;
+            return None;
+        }
+        if sweep.shift == 0 {
+            return None;
+        }
+        sweep.shadow = frequency;
+        // The new frequency goes through the overflow check a second time
+        if sweep.calculate() > 2047 {
+            self.enabled = false;
+        }
+        self.frequency = frequency;
+        Some(frequency)
+    }
+
+    pub fn clock_length(&mut self) {
+        if !self.length.clock() {
+            self.enabled = false;
+        }
+    }
+
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.bool(self.enabled);
+        state.bool(self.dac_enabled);
+        self.length.save_state(state);
+        self.envelope.save_state(state);
+        state.u16(self.frequency);
+        if let Some(sweep) = &self.sweep {
+            sweep.save_state(state);
+        }
+        state.u8(self.duty);
+        state.u8(self.position);
+        state.u32(self.timer as u32);
+    }
+
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        self.enabled = state.bool()?;
+        self.dac_enabled = state.bool()?;
+        self.length.load_state(state)?;
+        self.envelope.load_state(state)?;
+        self.frequency = state.u16()?;
+        if let Some(sweep) = &mut self.sweep {
+            sweep.load_state(state)?;
+        }
+        self.duty = state.u8()?;
+        self.position = state.u8()?;
+        self.timer = state.u32()? as i32;
+        Ok(())
+    }
+}
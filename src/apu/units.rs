@@ -0,0 +1,137 @@
+use crate::state::{StateError, StateReader, StateWriter};
+
+// The pieces the channels share, clocked by the frame sequencer
+
+// Turns the channel off when it runs out, if NRx4 bit 6 is set
+pub struct LengthCounter {
+    pub counter: u16,
+    pub enabled: bool,
+    max: u16,
+}
+
+impl LengthCounter {
+    pub fn new(max: u16) -> LengthCounter {
+        LengthCounter {
+            counter: 0,
+            enabled: false,
+            max,
+        }
+    }
+
+    // NRx1 holds max - length
+    pub fn load(&mut self, value: u16) {
+        self.counter = self.max - value;
+    }
+
+    // Returns false once the channel has to be turned off
+    pub fn clock(&mut self) -> bool {
+        if self.enabled && self.counter > 0 {
+            self.counter -= 1;
+            return self.counter != 0;
+        }
+        true
+    }
+
+    // NRx4 written. `length_step` is true when the frame sequencer's last step clocked the
+    // length counters, in which case enabling the counter clocks it once more right away.
+    // Returns false if the channel has to be turned off
+    pub fn write_control(&mut self, enable: bool, trigger: bool, length_step: bool) -> bool {
+        let was_enabled = self.enabled;
+        self.enabled = enable;
+        let mut on = true;
+        if enable && !was_enabled && length_step && self.counter > 0 {
+            self.counter -= 1;
+            on = self.counter != 0;
+        }
+        if trigger && self.counter == 0 {
+            self.counter = self.max;
+            if enable && length_step {
+                self.counter -= 1;
+            }
+            on = true;
+        }
+        on
+    }
+
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.u16(self.counter);
+        state.bool(self.enabled);
+    }
+
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        self.counter = state.u16()?;
+        self.enabled = state.bool()?;
+        Ok(())
+    }
+}
+
+// Volume envelope, NRx2
+pub struct Envelope {
+    pub volume: u8,
+    initial_volume: u8,
+    increase: bool,
+    period: u8,
+    timer: u8,
+}
+
+impl Envelope {
+    pub fn new() -> Envelope {
+        Envelope {
+            volume: 0,
+            initial_volume: 0,
+            increase: false,
+            period: 0,
+            timer: 0,
+        }
+    }
+
+    pub fn write(&mut self, value: u8) {
+        self.initial_volume = value >> 4;
+        self.increase = value & 0x08 != 0;
+        self.period = value & 0x7;
+    }
+
+    pub fn trigger(&mut self) {
+        self.volume = self.initial_volume;
+        self.timer = self.period;
+    }
+
+    pub fn clock(&mut self) {
+        if self.period == 0 {
+            return;
+        }
+        self.timer = self.timer.saturating_sub(1);
+        if self.timer == 0 {
+            self.timer = self.period;
+            if self.increase && self.volume < 15 {
+                self.volume += 1;
+            } else if !self.increase && self.volume > 0 {
+                self.volume -= 1;
+            }
+        }
+    }
+
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.bytes(&[self.volume, self.initial_volume, self.period, self.timer]);
+        state.bool(self.increase);
+    }
+
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        let mut bytes = [0; 4];
+        state.bytes(&mut bytes)?;
+        [self.volume, self.initial_volume, self.period, self.timer] = bytes;
+        self.increase = state.bool()?;
+        Ok(())
+    }
+}
+
+impl Default for Envelope {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// The 11 bit frequency spread over NRx3 and the low bits of NRx4
+pub fn frequency(nrx3: u8, nrx4: u8) -> u16 {
+    (nrx4 as u16 & 0x7) << 8 | nrx3 as u16
+}
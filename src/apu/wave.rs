@@ -0,0 +1,126 @@
+use super::units::LengthCounter;
+use crate::io::Io;
+use crate::state::{StateError, StateReader, StateWriter};
+
+// Channel 3: plays the 32 4-bit samples in wave RAM (0xFF30-0xFF3F), high nibble first
+pub struct Wave {
+    pub enabled: bool,
+    pub dac_enabled: bool,
+    pub length: LengthCounter,
+    pub frequency: u16,
+    // NR32 bits 5-6: mute, 100%, 50%, 25%
+    volume: u8,
+    position: u8,
+    // The sample the channel is playing, only refreshed when the position moves
+    sample: u8,
+    timer: i32,
+}
+
+impl Wave {
+    pub fn new() -> Wave {
+        Wave {
+            enabled: false,
+            dac_enabled: false,
+            length: LengthCounter::new(256),
+            frequency: 0,
+            volume: 0,
+            position: 0,
+            sample: 0,
+            timer: 0,
+        }
+    }
+
+    fn period(&self) -> i32 {
+        (2048 - self.frequency as i32) * 2
+    }
+
+    pub fn step(&mut self, cycles: i32, io: &Io) {
+        self.timer -= cycles;
+        while self.timer <= 0 {
+            self.timer += self.period();
+            self.position = (self.position + 1) % 32;
+            let byte = io.get(0xFF30 + self.position as u16 / 2);
+            self.sample = if self.position.is_multiple_of(2) {
+                byte >> 4
+            } else {
+                byte & 0xF
+            };
+        }
+    }
+
+    // Byte of wave RAM the channel is reading right now
+    pub fn position(&self) -> usize {
+        self.position as usize / 2
+    }
+
+    pub fn output(&self) -> u8 {
+        if !self.enabled || self.volume == 0 {
+            return 0;
+        }
+        self.sample >> (self.volume - 1)
+    }
+
+    pub fn write_dac(&mut self, value: u8) {
+        self.dac_enabled = value & 0x80 != 0;
+        if !self.dac_enabled {
+            self.enabled = false;
+        }
+    }
+
+    pub fn write_length(&mut self, value: u8) {
+        self.length.load(value as u16);
+    }
+
+    pub fn write_volume(&mut self, value: u8) {
+        self.volume = (value >> 5) & 0x3;
+    }
+
+    pub fn write_control(&mut self, value: u8, length_step: bool) {
+        let trigger = value & 0x80 != 0;
+        if !self
+            .length
+            .write_control(value & 0x40 != 0, trigger, length_step)
+        {
+            self.enabled = false;
+        }
+        if trigger {
+            self.enabled = self.dac_enabled;
+            self.position = 0;
+            // The first sample comes a little later than a full period
+            self.timer = self.period() + 6;
+        }
+    }
+
+    pub fn clock_length(&mut self) {
+        if !self.length.clock() {
+            self.enabled = false;
+        }
+    }
+
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.bool(self.enabled);
+        state.bool(self.dac_enabled);
+        self.length.save_state(state);
+        state.u16(self.frequency);
+        state.bytes(&[self.volume, self.position, self.sample]);
+        state.u32(self.timer as u32);
+    }
+
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        self.enabled = state.bool()?;
+        self.dac_enabled = state.bool()?;
+        self.length.load_state(state)?;
+        self.frequency = state.u16()?;
+        let mut bytes = [0; 3];
+        state.bytes(&mut bytes)?;
+        [self.volume, self.position, self.sample] = bytes;
+        self.timer = state.u32()? as i32;
+        Ok(())
+    }
+}
+
+impl Default for Wave {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,144 @@
+use std::f64::consts::PI;
+
+// Sub-sample positions the step kernel is computed for
+const PHASES: usize = 64;
+// Kernel width in output samples
+const TAPS: usize = 16;
+// Cutoff as a fraction of the output rate, a bit under Nyquist
+const CUTOFF: f64 = 0.45;
+
+// Band-limited step synthesis in the style of blip_buf: the input is a signal that only
+// changes in steps at a high clock rate, every step is added to the output as a windowed
+// sinc impulse and the output is the running sum of those impulses
+pub struct BlipBuffer {
+    kernel: Vec<[f32; TAPS]>,
+    // Impulses added so far, integrated when read out
+    buffer: Vec<f32>,
+    // Output position of the next input clock
+    offset: f64,
+    // Output samples per input clock
+    factor: f64,
+    integrator: f32,
+    last: i16,
+}
+
+impl BlipBuffer {
+    pub fn new(clock_rate: u32, sample_rate: u32) -> BlipBuffer {
+        BlipBuffer {
+            kernel: (0..PHASES)
+                .map(|phase| kernel(phase as f64 / PHASES as f64))
+                .collect(),
+            buffer: vec![0.0; TAPS * 2],
+            offset: 0.0,
+            factor: sample_rate as f64 / clock_rate as f64,
+            integrator: 0.0,
+            last: 0,
+        }
+    }
+
+    // Changes how many output samples one input clock is worth, for rate control
+    pub fn set_factor(&mut self, factor: f64) {
+        self.factor = factor;
+    }
+
+    pub fn factor(&self) -> f64 {
+        self.factor
+    }
+
+    // Adds one input sample per clock
+    pub fn push(&mut self, samples: impl IntoIterator<Item = i16>) {
+        for sample in samples {
+            if sample != self.last {
+                self.add_delta((sample as i32 - self.last as i32) as f32);
+                self.last = sample;
+            }
+            self.offset += self.factor;
+        }
+    }
+
+    fn add_delta(&mut self, delta: f32) {
+        let position = self.offset.floor();
+        let phase = ((self.offset - position) * PHASES as f64) as usize;
+        let start = position as usize;
+        if self.buffer.len() < start + TAPS {
+            self.buffer.resize(start + TAPS * 2, 0.0);
+        }
+        for (sample, weight) in self.buffer[start..start + TAPS]
+            .iter_mut()
+            .zip(&self.kernel[phase.min(PHASES - 1)])
+        {
+            *sample += delta * weight;
+        }
+    }
+
+    // Output samples ready to be read
+    pub fn available(&self) -> usize {
+        self.offset as usize
+    }
+
+    // Reads every finished sample. They come out TAPS / 2 samples late
+    pub fn read(&mut self) -> Vec<f32> {
+        let count = self.available();
+        if self.buffer.len() < count + TAPS {
+            self.buffer.resize(count + TAPS, 0.0);
+        }
+        let mut output = Vec::with_capacity(count);
+        for &impulse in &self.buffer[..count] {
+            self.integrator += impulse;
+            output.push(self.integrator);
+        }
+        self.buffer.drain(..count);
+        self.offset -= count as f64;
+        output
+    }
+}
+
+// Impulse for a step at `fraction` of a sample, Blackman windowed and summing to 1
+fn kernel(fraction: f64) -> [f32; TAPS] {
+    let mut kernel = [0.0; TAPS];
+    let center = (TAPS / 2) as f64 + fraction;
+    let weights: Vec<f64> = (0..TAPS)
+        .map(|i| {
+            let t = i as f64 + 0.5 - center;
+            let sinc = if t.abs() < 1e-9 {
+                1.0
+            } else {
+                (PI * 2.0 * CUTOFF * t).sin() / (PI * 2.0 * CUTOFF * t)
+            };
+            let x = (i as f64 + 0.5 - fraction) / TAPS as f64;
+            let window = 0.42 - 0.5 * (2.0 * PI * x).cos() + 0.08 * (4.0 * PI * x).cos();
+            sinc * window.max(0.0)
+        })
+        .collect();
+    let sum: f64 = weights.iter().sum();
+    for (value, weight) in kernel.iter_mut().zip(weights) {
+        *value = (weight / sum) as f32;
+    }
+    kernel
+}
+
+// One pole high-pass that removes the DC offset of the DACs, like the capacitor on
+// the real audio output
+pub struct HighPass {
+    charge: f32,
+    previous_input: f32,
+    previous_output: f32,
+}
+
+impl HighPass {
+    // Cutoff around 20 Hz at `sample_rate`
+    pub fn new(sample_rate: u32) -> HighPass {
+        HighPass {
+            charge: 1.0 - (2.0 * PI as f32 * 20.0 / sample_rate as f32),
+            previous_input: 0.0,
+            previous_output: 0.0,
+        }
+    }
+
+    pub fn apply(&mut self, input: f32) -> f32 {
+        let output = input - self.previous_input + self.charge * self.previous_output;
+        self.previous_input = input;
+        self.previous_output = output;
+        output
+    }
+}
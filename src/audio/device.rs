@@ -0,0 +1,95 @@
+use super::AudioError;
+
+// Where the resampled audio goes. Samples are interleaved stereo
+pub trait AudioDevice {
+    fn sample_rate(&self) -> u32;
+
+    fn queue(&mut self, samples: &[i16]) -> Result<(), AudioError>;
+
+    // Stereo frames waiting to be played
+    fn queued(&self) -> usize;
+}
+
+// Plays nothing. Counts what it gets so headless runs and tests can check the output,
+// and pretends to play in real time so rate control behaves as with a real device
+pub struct NullAudio {
+    sample_rate: u32,
+    pub frames_written: u64,
+    // Stereo frames the fake device plays every time something is queued
+    pub playback: usize,
+    queued: usize,
+}
+
+impl NullAudio {
+    pub fn new(sample_rate: u32) -> NullAudio {
+        NullAudio {
+            sample_rate,
+            frames_written: 0,
+            playback: 0,
+            queued: 0,
+        }
+    }
+}
+
+impl AudioDevice for NullAudio {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn queue(&mut self, samples: &[i16]) -> Result<(), AudioError> {
+        let frames = samples.len() / 2;
+        self.frames_written += frames as u64;
+        self.queued = (self.queued + frames).saturating_sub(self.playback);
+        Ok(())
+    }
+
+    fn queued(&self) -> usize {
+        self.queued
+    }
+}
+
+#[cfg(feature = "sdl")]
+pub use sdl::SdlAudio;
+
+#[cfg(feature = "sdl")]
+mod sdl {
+    use super::{AudioDevice, AudioError};
+    use sdl2::audio::{AudioQueue, AudioSpecDesired};
+
+    // An SDL2 audio queue, already playing
+    pub struct SdlAudio {
+        queue: AudioQueue<i16>,
+    }
+
+    impl SdlAudio {
+        pub fn open(sdl: &sdl2::Sdl, sample_rate: u32) -> Result<SdlAudio, AudioError> {
+            let audio = sdl.audio().map_err(|_| AudioError::Open)?;
+            let spec = AudioSpecDesired {
+                freq: Some(sample_rate as i32),
+                channels: Some(2),
+                samples: Some(1024),
+            };
+            let queue = audio
+                .open_queue::<i16, _>(None, &spec)
+                .map_err(|_| AudioError::Open)?;
+            queue.resume();
+            Ok(SdlAudio { queue })
+        }
+    }
+
+    impl AudioDevice for SdlAudio {
+        fn sample_rate(&self) -> u32 {
+            self.queue.spec().freq as u32
+        }
+
+        fn queue(&mut self, samples: &[i16]) -> Result<(), AudioError> {
+            self.queue
+                .queue_audio(samples)
+                .map_err(|_| AudioError::Queue)
+        }
+
+        fn queued(&self) -> usize {
+            self.queue.size() as usize / 4
+        }
+    }
+}
@@ -0,0 +1,116 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+use super::{AudioError, Resampler};
+use crate::wav::WavWriter;
+
+// 64 bit FNV-1a over little endian PCM, to compare audio between runs
+pub fn pcm_hash(hash: u64, samples: &[i16]) -> u64 {
+    let mut hash = hash;
+    for sample in samples {
+        for byte in sample.to_le_bytes() {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001B3);
+        }
+    }
+    hash
+}
+
+pub const PCM_HASH_START: u64 = 0xCBF29CE484222325;
+
+struct Track {
+    wav: WavWriter<BufWriter<File>>,
+    resampler: Resampler,
+}
+
+impl Track {
+    fn create(filename: &str, sample_rate: u32) -> Result<Track, AudioError> {
+        Ok(Track {
+            wav: WavWriter::create(filename, 2, sample_rate).map_err(|_| AudioError::Save)?,
+            resampler: Resampler::new(sample_rate),
+        })
+    }
+
+    fn write(&mut self, samples: &[[i16; 2]]) -> Result<Vec<i16>, AudioError> {
+        let output = self.resampler.process(samples);
+        self.wav
+            .write_samples(&output)
+            .map_err(|_| AudioError::Save)?;
+        Ok(output)
+    }
+}
+
+// Writes what the APU plays to a stereo WAV file, and optionally every channel on its
+// own next to it (song.wav, song.ch1.wav ... song.ch4.wav). There's no rate control
+// here, so the same input always makes the same file
+pub struct WavRecorder {
+    mix: Track,
+    stems: Vec<Track>,
+    hash: u64,
+}
+
+impl WavRecorder {
+    pub fn create(
+        filename: &str,
+        sample_rate: u32,
+        stems: bool,
+    ) -> Result<WavRecorder, AudioError> {
+        let mix = Track::create(filename, sample_rate)?;
+        let stems = if stems {
+            (1..=4)
+                .map(|channel| Track::create(&stem_filename(filename, channel), sample_rate))
+                .collect::<Result<Vec<Track>, AudioError>>()?
+        } else {
+            Vec::new()
+        };
+        Ok(WavRecorder {
+            mix,
+            stems,
+            hash: PCM_HASH_START,
+        })
+    }
+
+    pub fn has_stems(&self) -> bool {
+        !self.stems.is_empty()
+    }
+
+    // `samples` from Apu::take_samples, `channels` from Apu::take_channel_samples
+    pub fn write(
+        &mut self,
+        samples: &[[i16; 2]],
+        channels: &[Vec<[i16; 2]>; 4],
+    ) -> Result<(), AudioError> {
+        let output = self.mix.write(samples)?;
+        self.hash = pcm_hash(self.hash, &output);
+        for (stem, samples) in self.stems.iter_mut().zip(channels) {
+            stem.write(samples)?;
+        }
+        Ok(())
+    }
+
+    // Hash of the mix written so far
+    pub fn hash(&self) -> u64 {
+        self.hash
+    }
+
+    pub fn finish(self) -> Result<u64, AudioError> {
+        self.mix.wav.finish().map_err(|_| AudioError::Save)?;
+        for stem in self.stems {
+            stem.wav.finish().map_err(|_| AudioError::Save)?;
+        }
+        Ok(self.hash)
+    }
+}
+
+// song.wav -> song.ch1.wav
+pub fn stem_filename(filename: &str, channel: usize) -> String {
+    let path = Path::new(filename);
+    let stem = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("audio");
+    path.with_file_name(format!("{}.ch{}.wav", stem, channel))
+        .to_string_lossy()
+        .into_owned()
+}
@@ -0,0 +1,125 @@
+pub mod blip;
+pub mod device;
+pub mod export;
+
+use crate::apu::Apu;
+use blip::{BlipBuffer, HighPass};
+use device::AudioDevice;
+
+#[derive(Debug)]
+pub enum AudioError {
+    Open,
+    Queue,
+    Save,
+}
+
+// Keeps the device queue around `target` frames by playing the emulator slightly
+// faster or slower than real time, at most `max_delta` off. Small enough to be inaudible,
+// big enough to absorb the drift between the emulated and the real clocks
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct RateControl {
+    pub target: usize,
+    pub max_delta: f64,
+}
+
+impl RateControl {
+    pub fn new(sample_rate: u32) -> RateControl {
+        // 50 ms of latency
+        RateControl {
+            target: sample_rate as usize / 20,
+            max_delta: 0.005,
+        }
+    }
+
+    // Multiplier for the output rate: above 1 makes more samples to fill an emptying queue
+    pub fn adjust(&self, queued: usize) -> f64 {
+        let fill = (queued as f64 / (2 * self.target.max(1)) as f64).min(1.0);
+        1.0 + self.max_delta * (1.0 - 2.0 * fill)
+    }
+}
+
+// Stereo samples at the APU rate down to `sample_rate`, through the DC blocker
+pub struct Resampler {
+    blips: [BlipBuffer; 2],
+    high_pass: [HighPass; 2],
+    sample_rate: u32,
+}
+
+impl Resampler {
+    pub fn new(sample_rate: u32) -> Resampler {
+        Resampler {
+            blips: [
+                BlipBuffer::new(Apu::SAMPLE_RATE, sample_rate),
+                BlipBuffer::new(Apu::SAMPLE_RATE, sample_rate),
+            ],
+            high_pass: [HighPass::new(sample_rate), HighPass::new(sample_rate)],
+            sample_rate,
+        }
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    // Scales the output rate, 1.0 is exactly sample_rate
+    pub fn set_adjust(&mut self, adjust: f64) {
+        let factor = self.sample_rate as f64 / Apu::SAMPLE_RATE as f64 * adjust;
+        for blip in self.blips.iter_mut() {
+            blip.set_factor(factor);
+        }
+    }
+
+    // Interleaved output for everything finished so far
+    pub fn process(&mut self, samples: &[[i16; 2]]) -> Vec<i16> {
+        for (side, blip) in self.blips.iter_mut().enumerate() {
+            blip.push(samples.iter().map(|sample| sample[side]));
+        }
+        let [left, right] = [0, 1].map(|side| {
+            let high_pass = &mut self.high_pass[side];
+            self.blips[side]
+                .read()
+                .into_iter()
+                .map(|sample| high_pass.apply(sample).round().clamp(-32768.0, 32767.0) as i16)
+                .collect::<Vec<i16>>()
+        });
+        let mut output = Vec::with_capacity(left.len() * 2);
+        for (left, right) in left.into_iter().zip(right) {
+            output.extend_from_slice(&[left, right]);
+        }
+        output
+    }
+}
+
+// The APU's samples resampled to the device rate and queued on the device
+pub struct AudioOutput {
+    pub device: Box<dyn AudioDevice>,
+    pub rate_control: Option<RateControl>,
+    resampler: Resampler,
+}
+
+impl AudioOutput {
+    pub fn new(device: Box<dyn AudioDevice>) -> AudioOutput {
+        let rate = device.sample_rate();
+        AudioOutput {
+            rate_control: Some(RateControl::new(rate)),
+            resampler: Resampler::new(rate),
+            device,
+        }
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.device.sample_rate()
+    }
+
+    // Queues the APU samples (see Apu::take_samples) and returns them as they went to
+    // the device, interleaved stereo
+    pub fn update(&mut self, samples: &[[i16; 2]]) -> Result<Vec<i16>, AudioError> {
+        if let Some(rate_control) = self.rate_control {
+            let adjust = rate_control.adjust(self.device.queued());
+            self.resampler.set_adjust(adjust);
+        }
+        let output = self.resampler.process(samples);
+        self.device.queue(&output)?;
+        Ok(output)
+    }
+}
@@ -0,0 +1,327 @@
+use std::collections::HashMap;
+use std::process::exit;
+use std::time::{Duration, Instant};
+
+use blazeboy::{
+    Catridge, Device, GameBoy, Hotkey, Input, InputMap, InputState, Model, Movie, MoviePlayer,
+    MovieRecorder, SdlAudio,
+};
+use sdl2::controller::GameController;
+use sdl2::event::Event;
+use sdl2::pixels::PixelFormatEnum;
+
+// Plays a ROM in an SDL window with sound, on the keyboard or any game controller:
+//   blazesdl <rom> [--input <map.json>] [--scale N] [--sgb] [--state <file>]
+//            [--record <movie>] [--play <movie>]
+//
+// The input map sets the keys, controller buttons and stick directions for every
+// button, turbo button and hotkey, see InputMap. Controllers can come and go while
+// playing. Without --input the defaults are Arrows: D-pad, X: A, Z: B, S/A: turbo A/B,
+// Enter: Start, Backspace: Select, Tab: fast forward, P: pause, F12: screenshot,
+// F5/F7: save/load the state in <rom>.state, Esc: quit
+//
+// --state starts from a save state instead of power on. --record writes the buttons of
+// every frame from the start, and the state if there was one, to a movie when the
+// window closes. --play runs one on the model it was made on, the buttons only take
+// over once it ends or desyncs
+const USAGE: &str = "usage: blazesdl <rom> [--input <map.json>] [--scale N] [--sgb] \
+                     [--state <file>] [--record <movie>] [--play <movie>]";
+
+// Frames run per frame drawn while fast forwarding
+const FAST_FRAMES: u32 = 4;
+
+struct Options {
+    rom: String,
+    input: Option<String>,
+    scale: u32,
+    sgb: bool,
+    state: Option<String>,
+    record: Option<String>,
+    play: Option<String>,
+}
+
+enum Session {
+    Live,
+    Record(MovieRecorder),
+    Play(MoviePlayer),
+}
+
+fn parse_args() -> Result<Options, String> {
+    let mut args = std::env::args().skip(1);
+    let mut options = Options {
+        rom: String::new(),
+        input: None,
+        scale: 3,
+        sgb: false,
+        state: None,
+        record: None,
+        play: None,
+    };
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or(format!("{} needs a value", name));
+        match arg.as_str() {
+            "--input" => options.input = Some(value("--input")?),
+            "--scale" => {
+                let scale = value("--scale")?.parse().map_err(|_| "invalid --scale")?;
+                options.scale = scale;
+            }
+            "--sgb" => options.sgb = true,
+            "--state" => options.state = Some(value("--state")?),
+            "--record" => options.record = Some(value("--record")?),
+            "--play" => options.play = Some(value("--play")?),
+            _ if options.rom.is_empty() && !arg.starts_with("--") => options.rom = arg,
+            _ => return Err(USAGE.to_string()),
+        }
+    }
+    // A movie that's played has its own start
+    let conflict = options.play.is_some() && (options.record.is_some() || options.state.is_some());
+    if options.rom.is_empty() || options.scale == 0 || conflict {
+        return Err(USAGE.to_string());
+    }
+    Ok(options)
+}
+
+fn main() {
+    let options = parse_args().unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        exit(1);
+    });
+    let map = match &options.input {
+        Some(filename) => InputMap::new(filename).unwrap_or_else(|error| {
+            eprintln!("couldn't load {}: {:?}", filename, error);
+            exit(1);
+        }),
+        None => InputMap::default(),
+    };
+    let catridge = Catridge::new(&options.rom).unwrap_or_else(|error| {
+        eprintln!("couldn't load {}: {:?}", options.rom, error);
+        exit(1);
+    });
+    let (mut gameboy, mut session) = start(catridge, &options).unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        exit(1);
+    });
+    gameboy.video.sgb_border = gameboy.memory.ppu.sgb.is_some();
+    let result = run(&mut gameboy, &options, InputState::new(map), &mut session);
+    if let (Session::Record(recorder), Some(filename)) = (&session, &options.record) {
+        match recorder.movie.save(filename) {
+            Ok(()) => println!(
+                "wrote {} frames to {}, {} lag frames",
+                recorder.movie.frames.len(),
+                filename,
+                recorder.lag_frames
+            ),
+            Err(error) => eprintln!("couldn't write {}: {:?}", filename, error),
+        }
+    }
+    if let Err(error) = result {
+        eprintln!("{}", error);
+        exit(1);
+    }
+}
+
+fn start(catridge: Catridge, options: &Options) -> Result<(GameBoy, Session), String> {
+    if let Some(filename) = &options.play {
+        let movie = Movie::load(filename)
+            .map_err(|error| format!("couldn't load {}: {:?}", filename, error))?;
+        let gameboy = movie
+            .boot(catridge)
+            .map_err(|error| format!("can't play {}: {:?}", filename, error))?;
+        return Ok((gameboy, Session::Play(MoviePlayer::new(movie))));
+    }
+    let live = |catridge| -> Result<GameBoy, String> {
+        let mut gameboy = if options.sgb {
+            GameBoy::from_catridge_sgb(catridge)
+        } else {
+            GameBoy::from_catridge(catridge)
+        };
+        if let Some(filename) = &options.state {
+            gameboy
+                .load_state(filename)
+                .map_err(|error| format!("couldn't load {}: {:?}", filename, error))?;
+        }
+        Ok(gameboy)
+    };
+    if options.record.is_some() {
+        let movie = if options.state.is_some() {
+            Movie::from_state(&live(catridge.clone())?)
+        } else {
+            let model = match (options.sgb, catridge.cgb) {
+                (true, _) => Model::Sgb,
+                (false, true) => Model::Cgb,
+                (false, false) => Model::Dmg,
+            };
+            Movie::new(&catridge, model, Vec::new())
+        };
+        let gameboy = movie
+            .boot(catridge)
+            .map_err(|error| format!("{:?}", error))?;
+        return Ok((gameboy, Session::Record(MovieRecorder::new(movie))));
+    }
+    Ok((live(catridge)?, Session::Live))
+}
+
+fn run(
+    gameboy: &mut GameBoy,
+    options: &Options,
+    mut input: InputState,
+    session: &mut Session,
+) -> Result<(), String> {
+    let sdl = sdl2::init()?;
+    let controllers = sdl.game_controller()?;
+    let (width, height) = gameboy.video.size();
+    let (width, height) = (width as u32, height as u32);
+    let window = sdl
+        .video()?
+        .window("blazeboy", width * options.scale, height * options.scale)
+        .position_centered()
+        .build()
+        .map_err(|error| error.to_string())?;
+    let mut canvas = window
+        .into_canvas()
+        .build()
+        .map_err(|error| error.to_string())?;
+    let texture_creator = canvas.texture_creator();
+    let mut texture = texture_creator
+        .create_texture_streaming(PixelFormatEnum::RGBA32, width, height)
+        .map_err(|error| error.to_string())?;
+    match SdlAudio::open(&sdl, 48000) {
+        Ok(device) => gameboy.start_audio(Box::new(device)),
+        Err(error) => eprintln!("no sound: {:?}", error),
+    }
+    let mut events = sdl.event_pump()?;
+
+    // Open controllers by instance id, the id their events and removal come with.
+    // The ones connected at start come in as added events too
+    let mut open: HashMap<u32, GameController> = HashMap::new();
+    let frame_time = Duration::from_nanos(1_000_000_000 * GameBoy::FRAME_CYCLES / 4194304);
+    let mut paused = false;
+    let mut screenshots = 0;
+    let mut next_frame = Instant::now();
+
+    loop {
+        for event in events.poll_iter() {
+            match event {
+                Event::Quit { .. } => return Ok(()),
+                Event::KeyDown {
+                    keycode: Some(key),
+                    repeat: false,
+                    ..
+                } => input.press(Device::Keyboard, Input::key(&key.name())),
+                Event::KeyUp {
+                    keycode: Some(key), ..
+                } => input.release(Device::Keyboard, &Input::key(&key.name())),
+                Event::ControllerDeviceAdded { which, .. } => match controllers.open(which) {
+                    Ok(controller) => {
+                        println!("connected {}", controller.name());
+                        open.insert(controller.instance_id(), controller);
+                    }
+                    Err(error) => eprintln!("couldn't open controller {}: {}", which, error),
+                },
+                Event::ControllerDeviceRemoved { which, .. } => {
+                    if let Some(controller) = open.remove(&which) {
+                        println!("disconnected {}", controller.name());
+                    }
+                    input.remove_device(Device::Controller(which));
+                }
+                Event::ControllerButtonDown { which, button, .. } => {
+                    input.press(Device::Controller(which), Input::button(&button.string()))
+                }
+                Event::ControllerButtonUp { which, button, .. } => {
+                    input.release(Device::Controller(which), &Input::button(&button.string()))
+                }
+                Event::ControllerAxisMotion {
+                    which, axis, value, ..
+                } => input.axis(Device::Controller(which), &axis.string(), value),
+                _ => (),
+            }
+        }
+
+        for hotkey in input.take_hotkeys() {
+            match hotkey {
+                Hotkey::Quit => return Ok(()),
+                Hotkey::Pause => paused = !paused,
+                Hotkey::Screenshot => {
+                    let filename = format!("{}.{}.png", options.rom, screenshots);
+                    screenshots += 1;
+                    if let Err(error) = gameboy.screenshot(&filename) {
+                        eprintln!("couldn't write {}: {:?}", filename, error);
+                    }
+                }
+                Hotkey::SaveState => {
+                    let filename = format!("{}.state", options.rom);
+                    if let Err(error) = gameboy.save_state(&filename) {
+                        eprintln!("couldn't write {}: {:?}", filename, error);
+                    }
+                }
+                // A movie only has the buttons from its start, a state in the middle
+                // would put it out of sync
+                Hotkey::LoadState if !matches!(session, Session::Live) => {
+                    eprintln!("can't load a state while recording or playing a movie")
+                }
+                Hotkey::LoadState => {
+                    let filename = format!("{}.state", options.rom);
+                    if let Err(error) = gameboy.load_state(&filename) {
+                        eprintln!("couldn't load {}: {:?}", filename, error);
+                    }
+                }
+                Hotkey::FastForward => (),
+            }
+        }
+
+        let fast = input.hotkey_held(Hotkey::FastForward);
+        if !paused {
+            // The sound of the extra frames would only pile up in the queue
+            let audio = if fast { gameboy.audio.take() } else { None };
+            for _ in 0..if fast { FAST_FRAMES } else { 1 } {
+                run_frame(gameboy, &input, session);
+                input.next_frame();
+            }
+            if fast {
+                gameboy.memory.apu.take_samples();
+                gameboy.audio = audio;
+            }
+        }
+
+        let frame = gameboy.rgba();
+        texture
+            .update(None, &frame, width as usize * 4)
+            .map_err(|error| error.to_string())?;
+        canvas.copy(&texture, None, None)?;
+        canvas.present();
+
+        // Rate control takes care of the drift between this and the audio clock
+        next_frame += frame_time;
+        let now = Instant::now();
+        if next_frame > now {
+            std::thread::sleep(next_frame - now);
+        } else {
+            next_frame = now;
+        }
+    }
+}
+
+fn run_frame(gameboy: &mut GameBoy, input: &InputState, session: &mut Session) {
+    match session {
+        Session::Live => {
+            gameboy.set_buttons(input.buttons());
+            gameboy.run_frame();
+        }
+        Session::Record(recorder) => {
+            recorder.run_frame(gameboy, input.buttons());
+        }
+        Session::Play(player) => {
+            if let Err(error) = player.run_frame(gameboy) {
+                eprintln!("{:?} by frame {}", error, player.frame());
+                *session = Session::Live;
+            } else if player.finished() {
+                println!(
+                    "movie ended after {} frames, {} lag frames",
+                    player.frame(),
+                    player.lag_frames
+                );
+                *session = Session::Live;
+            }
+        }
+    }
+}
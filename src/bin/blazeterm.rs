@@ -0,0 +1,255 @@
+use std::io::{stdout, Write};
+use std::process::exit;
+use std::time::{Duration, Instant};
+
+use blazeboy::{Buttons, Catridge, GameBoy, TerminalColors, TerminalRenderer};
+use crossterm::event::{
+    self, Event, KeyCode, KeyEvent, KeyEventKind, KeyboardEnhancementFlags,
+    PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+};
+use crossterm::{cursor, execute, terminal};
+
+// Plays a ROM inside the terminal, two pixels per character cell:
+//   blazeterm <rom> [--256] [--truecolor] [--sgb]
+//
+// Arrows: D-pad, X: A, Z: B, Enter: Start, Backspace: Select
+// P: pause, N: next frame while paused, Tab: fast forward, S: screenshot,
+// C: switch colour mode, R: redraw, Q/Esc: quit
+const USAGE: &str = "usage: blazeterm <rom> [--256] [--truecolor] [--sgb]";
+
+// Terminals without key release events repeat held keys instead, so a press counts
+// for this many frames and every repeat renews it
+const HOLD_FRAMES: u32 = 8;
+
+const BUTTON_NAMES: [&str; 8] = ["A", "B", "Select", "Start", "Right", "Left", "Up", "Down"];
+
+struct Input {
+    // Frames left per button, in P1 order: A, B, Select, Start, Right, Left, Up, Down
+    held: [u32; 8],
+    releases: bool,
+}
+
+impl Input {
+    fn button(code: KeyCode) -> Option<usize> {
+        match code {
+            KeyCode::Char('x') | KeyCode::Char('X') => Some(0),
+            KeyCode::Char('z') | KeyCode::Char('Z') => Some(1),
+            KeyCode::Backspace => Some(2),
+            KeyCode::Enter => Some(3),
+            KeyCode::Right => Some(4),
+            KeyCode::Left => Some(5),
+            KeyCode::Up => Some(6),
+            KeyCode::Down => Some(7),
+            _ => None,
+        }
+    }
+
+    fn key(&mut self, event: &KeyEvent) {
+        if let Some(button) = Self::button(event.code) {
+            self.held[button] = match event.kind {
+                KeyEventKind::Release => 0,
+                // With release events a press lasts until it's let go
+                _ if self.releases => u32::MAX,
+                _ => HOLD_FRAMES,
+            };
+        }
+    }
+
+    fn next_frame(&mut self) {
+        for frames in self.held.iter_mut() {
+            *frames = frames.saturating_sub(1);
+        }
+    }
+
+    // Bit set for every button held, A in bit 0
+    fn buttons(&self) -> u8 {
+        self.held
+            .iter()
+            .enumerate()
+            .fold(0, |buttons, (i, &frames)| {
+                buttons | ((frames > 0) as u8) << i
+            })
+    }
+}
+
+fn status_line(buttons: u8, fps: f64, paused: bool, fast: bool) -> String {
+    let held: Vec<&str> = (0..8)
+        .filter(|i| buttons & 1 << i != 0)
+        .map(|i| BUTTON_NAMES[i])
+        .collect();
+    let state = if paused {
+        " [paused]"
+    } else if fast {
+        " [fast]"
+    } else {
+        ""
+    };
+    format!("{:5.1} fps{} {}", fps, state, held.join(" "))
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let Some(rom) = args.get(1).filter(|rom| !rom.starts_with("--")) else {
+        eprintln!("{}", USAGE);
+        exit(1);
+    };
+    let mut colors = TerminalColors::detect();
+    let mut sgb = false;
+    for flag in &args[2..] {
+        match flag.as_str() {
+            "--256" => colors = TerminalColors::Ansi256,
+            "--truecolor" => colors = TerminalColors::TrueColor,
+            "--sgb" => sgb = true,
+            _ => {
+                eprintln!("{}", USAGE);
+                exit(1);
+            }
+        }
+    }
+    let catridge = match Catridge::new(rom) {
+        Ok(catridge) => catridge,
+        Err(error) => {
+            eprintln!("couldn't load {}: {:?}", rom, error);
+            exit(1);
+        }
+    };
+    let mut gameboy = if sgb {
+        let mut gameboy = GameBoy::from_catridge_sgb(catridge);
+        gameboy.video.sgb_border = true;
+        gameboy
+    } else {
+        GameBoy::from_catridge(catridge)
+    };
+
+    let mut out = stdout();
+    let releases = terminal::supports_keyboard_enhancement().unwrap_or(false);
+    let setup = terminal::enable_raw_mode().and_then(|_| {
+        execute!(
+            out,
+            terminal::EnterAlternateScreen,
+            cursor::Hide,
+            terminal::Clear(terminal::ClearType::All)
+        )
+    });
+    if let Err(error) = setup {
+        eprintln!("couldn't set up the terminal: {}", error);
+        exit(1);
+    }
+    if releases {
+        let flags = KeyboardEnhancementFlags::REPORT_EVENT_TYPES;
+        let _ = execute!(out, PushKeyboardEnhancementFlags(flags));
+    }
+
+    let result = run(&mut gameboy, rom, colors, releases);
+
+    if releases {
+        let _ = execute!(out, PopKeyboardEnhancementFlags);
+    }
+    let _ = execute!(out, cursor::Show, terminal::LeaveAlternateScreen);
+    let _ = terminal::disable_raw_mode();
+    if let Err(error) = result {
+        eprintln!("{}", error);
+        exit(1);
+    }
+}
+
+fn run(
+    gameboy: &mut GameBoy,
+    rom: &str,
+    colors: TerminalColors,
+    releases: bool,
+) -> std::io::Result<()> {
+    let frame_time = Duration::from_nanos(1_000_000_000 * GameBoy::FRAME_CYCLES / 4194304);
+    let mut renderer = TerminalRenderer::new(colors);
+    let mut input = Input {
+        held: [0; 8],
+        releases,
+    };
+    let (mut paused, mut fast, mut step) = (false, false, false);
+    let mut screenshots = 0;
+    let mut next_frame = Instant::now();
+    let (mut fps, mut fps_frames, mut fps_start) = (0.0, 0, Instant::now());
+    let mut last_draw = Instant::now();
+    let mut out = stdout();
+
+    loop {
+        while event::poll(Duration::ZERO)? {
+            match event::read()? {
+                Event::Key(key) => {
+                    input.key(&key);
+                    if key.kind == KeyEventKind::Release {
+                        continue;
+                    }
+                    match key.code {
+                        KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                        KeyCode::Char('p') => paused = !paused,
+                        KeyCode::Char('n') => step = true,
+                        KeyCode::Tab => fast = !fast,
+                        KeyCode::Char('s') => {
+                            let filename = format!("{}.{}.png", rom, screenshots);
+                            screenshots += 1;
+                            let _ = gameboy.screenshot(&filename);
+                        }
+                        KeyCode::Char('c') => {
+                            renderer.colors = match renderer.colors {
+                                TerminalColors::TrueColor => TerminalColors::Ansi256,
+                                TerminalColors::Ansi256 => TerminalColors::TrueColor,
+                            };
+                            renderer.invalidate();
+                        }
+                        KeyCode::Char('r') => {
+                            execute!(out, terminal::Clear(terminal::ClearType::All))?;
+                            renderer.invalidate();
+                        }
+                        _ => (),
+                    }
+                }
+                Event::Resize(_, _) => {
+                    execute!(out, terminal::Clear(terminal::ClearType::All))?;
+                    renderer.invalidate();
+                }
+                _ => (),
+            }
+        }
+
+        if !paused || step {
+            gameboy.set_buttons(Buttons(input.buttons()));
+            gameboy.run_frame();
+            input.next_frame();
+            step = false;
+            fps_frames += 1;
+        }
+
+        // Fast forward only draws a few frames a second
+        if !fast || last_draw.elapsed() >= Duration::from_millis(250) {
+            last_draw = Instant::now();
+            let frame = gameboy.video.render(&gameboy.memory.ppu);
+            let (width, _) = gameboy.video.size();
+            let mut output = renderer.draw(&frame, width);
+            // Status line right under the picture
+            let line = (frame.len() / width).div_ceil(2) + 1;
+            let status = status_line(input.buttons(), fps, paused, fast);
+            output.push_str(&format!("\x1b[{};1H\x1b[2K{}", line, status));
+            out.write_all(output.as_bytes())?;
+            out.flush()?;
+        }
+        let elapsed = fps_start.elapsed();
+        if elapsed >= Duration::from_secs(1) {
+            fps = fps_frames as f64 / elapsed.as_secs_f64();
+            (fps_frames, fps_start) = (0, Instant::now());
+        }
+
+        if fast {
+            next_frame = Instant::now();
+        } else {
+            next_frame += frame_time;
+            let now = Instant::now();
+            if next_frame > now {
+                std::thread::sleep(next_frame - now);
+            } else if now - next_frame > frame_time * 4 {
+                // Too far behind to catch up, start counting again from now
+                next_frame = now;
+            }
+        }
+    }
+}
@@ -0,0 +1,151 @@
+use std::process::exit;
+
+use blazeboy::{Gbs, GbsPlayer};
+
+// Plays a GBS file, or renders part of a track to WAV:
+//   gbsplay <file.gbs> [track] [--seconds N] [--wav <out.wav>] [--stems] [--rate HZ]
+//           [--vgm <out.vgm>]
+//
+// Tracks count from 1 like in most players, the default is the file's first song.
+// Without --wav the track plays through SDL, for --seconds or until interrupted.
+// --vgm logs the APU register writes on top of either
+const USAGE: &str = "usage: gbsplay <file.gbs> [track] [--seconds N] [--wav <out.wav>] [--stems] \
+                     [--rate HZ] [--vgm <out.vgm>]";
+
+struct Options {
+    filename: String,
+    track: Option<u8>,
+    seconds: Option<u32>,
+    wav: Option<String>,
+    vgm: Option<String>,
+    stems: bool,
+    rate: u32,
+}
+
+fn parse_args() -> Result<Options, String> {
+    let mut args = std::env::args().skip(1);
+    let mut options = Options {
+        filename: String::new(),
+        track: None,
+        seconds: None,
+        wav: None,
+        vgm: None,
+        stems: false,
+        rate: 48000,
+    };
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or(format!("{} needs a value", name));
+        match arg.as_str() {
+            "--seconds" => {
+                let seconds = value("--seconds")?;
+                options.seconds = Some(seconds.parse().map_err(|_| "invalid --seconds")?);
+            }
+            "--wav" => options.wav = Some(value("--wav")?),
+            "--vgm" => options.vgm = Some(value("--vgm")?),
+            "--stems" => options.stems = true,
+            "--rate" => options.rate = value("--rate")?.parse().map_err(|_| "invalid --rate")?,
+            _ if options.filename.is_empty() => options.filename = arg,
+            _ if options.track.is_none() => match arg.parse::<u8>() {
+                Ok(track) if track > 0 => options.track = Some(track),
+                _ => return Err(format!("invalid track {}", arg)),
+            },
+            _ => return Err(USAGE.to_string()),
+        }
+    }
+    if options.filename.is_empty() {
+        return Err(USAGE.to_string());
+    }
+    Ok(options)
+}
+
+fn main() {
+    let options = parse_args().unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        exit(1);
+    });
+    let gbs = Gbs::new(&options.filename).unwrap_or_else(|error| {
+        eprintln!("couldn't load {}: {:?}", options.filename, error);
+        exit(1);
+    });
+    let mut player = GbsPlayer::new(gbs);
+    if let Some(track) = options.track {
+        player.start(track - 1);
+    }
+
+    let gbs = &player.gbs;
+    println!("{}", gbs.title);
+    println!("{}", gbs.author);
+    println!("{}", gbs.copyright);
+    let timing = if gbs.uses_timer() { "timer" } else { "V-blank" };
+    println!(
+        "track {} of {}, play at {:.2} Hz ({})",
+        player.track() + 1,
+        gbs.songs,
+        gbs.play_rate(),
+        timing
+    );
+
+    if let Some(filename) = &options.vgm {
+        if let Err(error) = player.gameboy.start_vgm(filename) {
+            eprintln!("couldn't write {}: {:?}", filename, error);
+            exit(1);
+        }
+    }
+    let result = match &options.wav {
+        Some(filename) => {
+            let seconds = options.seconds.unwrap_or(60);
+            player
+                .render_wav(filename, options.rate, options.stems, seconds)
+                .map(|hash| {
+                    println!(
+                        "wrote {} seconds to {}, hash {:016x}",
+                        seconds, filename, hash
+                    )
+                })
+                .map_err(|error| format!("couldn't write {}: {:?}", filename, error))
+        }
+        None => play(&mut player, options.rate, options.seconds),
+    };
+    let result = result.and_then(|_| {
+        player
+            .gameboy
+            .stop_vgm()
+            .map_err(|error| format!("couldn't finish the VGM log: {:?}", error))
+    });
+    if let Err(error) = result {
+        eprintln!("{}", error);
+        exit(1);
+    }
+}
+
+#[cfg(feature = "sdl")]
+fn play(player: &mut GbsPlayer, rate: u32, seconds: Option<u32>) -> Result<(), String> {
+    use blazeboy::{GameBoy, SdlAudio};
+    use std::time::{Duration, Instant};
+
+    let sdl = sdl2::init()?;
+    let device = SdlAudio::open(&sdl, rate).map_err(|error| format!("{:?}", error))?;
+    player.gameboy.start_audio(Box::new(device));
+    let frame_time = Duration::from_nanos(1_000_000_000 * GameBoy::FRAME_CYCLES / 4194304);
+    let frames = seconds.map(|seconds| seconds as u64 * 4194304 / GameBoy::FRAME_CYCLES);
+    let mut next_frame = Instant::now();
+    let mut frame = 0;
+    while frames.is_none_or(|frames| frame < frames) {
+        player.run_frame();
+        frame += 1;
+        // Rate control takes care of the drift between this and the audio clock
+        next_frame += frame_time;
+        let now = Instant::now();
+        if next_frame > now {
+            std::thread::sleep(next_frame - now);
+        } else {
+            next_frame = now;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "sdl"))]
+fn play(_player: &mut GbsPlayer, _rate: u32, _seconds: Option<u32>) -> Result<(), String> {
+    Err("built without SDL audio (the sdl feature), use --wav".to_string())
+}
@@ -0,0 +1,71 @@
+use std::process::exit;
+
+use blazeboy::{Catridge, GameBoy};
+
+// Runs a ROM headless for a number of frames, then dumps the VRAM debug views:
+//   vramdump <rom> [frames] [output dir] [--state <file>]
+//
+// With --state the console starts from the save state instead of power on, and runs
+// no frames unless asked to
+const USAGE: &str = "usage: vramdump <rom> [frames] [output dir] [--state <file>]";
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let mut positional = Vec::new();
+    let mut state = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--state" => match args.next() {
+                Some(filename) => state = Some(filename),
+                None => {
+                    eprintln!("--state needs a value");
+                    exit(1);
+                }
+            },
+            _ if positional.len() < 3 && !arg.starts_with("--") => positional.push(arg),
+            _ => {
+                eprintln!("{}", USAGE);
+                exit(1);
+            }
+        }
+    }
+    if positional.is_empty() {
+        eprintln!("{}", USAGE);
+        exit(1);
+    }
+    let frames: u64 = match positional.get(1).map(|frames| frames.parse()) {
+        Some(Ok(frames)) => frames,
+        Some(Err(_)) => {
+            eprintln!("invalid frame count {}", positional[1]);
+            exit(1);
+        }
+        None if state.is_some() => 0,
+        None => 60,
+    };
+    let dir = positional.get(2).map(String::as_str).unwrap_or(".");
+
+    let catridge = match Catridge::new(&positional[0]) {
+        Ok(catridge) => catridge,
+        Err(error) => {
+            eprintln!("couldn't load {}: {:?}", positional[0], error);
+            exit(1);
+        }
+    };
+    let mut gameboy = GameBoy::from_catridge(catridge);
+    if let Some(filename) = &state {
+        if let Err(error) = gameboy.load_state(filename) {
+            eprintln!("couldn't load {}: {:?}", filename, error);
+            exit(1);
+        }
+    }
+    for _ in 0..frames {
+        gameboy.run_frame();
+    }
+    match gameboy.dump_vram(dir) {
+        Ok(table) => print!("{}", table),
+        Err(error) => {
+            eprintln!("couldn't write the debug views to {}: {:?}", dir, error);
+            exit(1);
+        }
+    }
+}
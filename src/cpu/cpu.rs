@@ -1,11 +1,17 @@
 use crate::{
-    bus_read,
-    cpu::{CpuRegisters, Instruction},
-    Command, Memory,
+    bus_read, bus_tick, bus_write,
+    cpu::{CpuRegisters, Instruction, Register16Bit},
+    state::{StateError, StateReader, StateWriter},
+    Command, Interrupt, Memory,
 };
 pub struct Cpu {
     pub registers: CpuRegisters,
     pub halted: bool,
+    // STOP waits for a joypad line to go low
+    pub stopped: bool,
+    pub ime: bool,
+    // EI only enables interrupts after the following instruction
+    ime_pending: bool,
 }
 
 impl Cpu {
@@ -13,10 +19,31 @@ impl Cpu {
         let registers = CpuRegisters::new();
         let halted = false;
 
-        Cpu { registers, halted }
+        Cpu {
+            registers,
+            halted,
+            stopped: false,
+            ime: false,
+            ime_pending: false,
+        }
     }
 
     pub fn step(&mut self, memory: &mut Memory) {
+        let stall = memory.take_stall();
+        if stall > 0 {
+            bus_tick(memory, stall);
+            return;
+        }
+        if self.stopped {
+            if memory.io.get(0xFF00) & 0xF == 0xF {
+                bus_tick(memory, 4);
+                return;
+            }
+            self.stopped = false;
+        }
+        if self.service_interrupt(memory) {
+            return;
+        }
         let mut instruction = Instruction::new();
         if !self.halted {
             let opcode = bus_read(&memory, self.registers.pc).unwrap();
@@ -30,8 +57,93 @@ impl Cpu {
                 ),
                 _ => (opcode, command),
             };
-            instruction.execute(&mut self.registers, memory, opcode, command);
+            let mut enable_ime = std::mem::take(&mut self.ime_pending);
+            match command {
+                Command::HALT => {
+                    self.halted = true;
+                    instruction.no_op();
+                }
+                // Two bytes long, and resets DIV
+                Command::Stop => {
+                    self.stopped = true;
+                    bus_write(memory, 0xFF04, 0);
+                    instruction.no_op();
+                    instruction.length = 2;
+                }
+                Command::EI => {
+                    self.ime_pending = true;
+                    instruction.no_op();
+                }
+                Command::DI => {
+                    self.ime = false;
+                    enable_ime = false;
+                    instruction.no_op();
+                }
+                Command::RETI => {
+                    instruction.ret(&mut self.registers, memory);
+                    enable_ime = true;
+                }
+                _ => instruction.execute(&mut self.registers, memory, opcode, command),
+            }
+            if enable_ime {
+                self.ime = true;
+            }
             self.registers.pc = self.registers.pc.wrapping_add(instruction.length as u16);
+            bus_tick(memory, instruction.cycle as u32);
+        } else {
+            bus_tick(memory, 4);
+        }
+    }
+
+    // Any pending interrupt wakes the CPU from HALT, it's only dispatched with IME set.
+    // Dispatching pushes PC and jumps to the vector in 5 M-cycles
+    fn service_interrupt(&mut self, memory: &mut Memory) -> bool {
+        let pending = memory.io.interrupt_flags() & bus_read(memory, 0xFFFF).unwrap();
+        if pending == 0 {
+            return false;
+        }
+        self.halted = false;
+        if !self.ime {
+            return false;
         }
+        let Some(interrupt) = Interrupt::ALL
+            .into_iter()
+            .find(|interrupt| pending & interrupt.mask() != 0)
+        else {
+            return false;
+        };
+        self.ime = false;
+        self.ime_pending = false;
+        let flags = memory.io.get(0xFF0F) & !interrupt.mask();
+        memory.io.set(0xFF0F, flags);
+        Instruction::new().push_16bit_reg(&mut self.registers, memory, Register16Bit::PC);
+        self.registers.pc = interrupt.vector();
+        bus_tick(memory, 20);
+        true
+    }
+
+    pub fn save_state(&self, state: &mut StateWriter) {
+        let r = &self.registers;
+        state.bytes(&[r.a, r.b, r.c, r.d, r.e, r.f, r.h, r.l]);
+        state.u16(r.sp);
+        state.u16(r.pc);
+        state.bool(self.halted);
+        state.bool(self.stopped);
+        state.bool(self.ime);
+        state.bool(self.ime_pending);
+    }
+
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        let r = &mut self.registers;
+        let mut bytes = [0; 8];
+        state.bytes(&mut bytes)?;
+        [r.a, r.b, r.c, r.d, r.e, r.f, r.h, r.l] = bytes;
+        r.sp = state.u16()?;
+        r.pc = state.u16()?;
+        self.halted = state.bool()?;
+        self.stopped = state.bool()?;
+        self.ime = state.bool()?;
+        self.ime_pending = state.bool()?;
+        Ok(())
     }
 }
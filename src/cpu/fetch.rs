@@ -2,6 +2,9 @@
 pub enum Command {
     NOP,
     HALT,
+    EI,
+    DI,
+    RETI,
     LD_16Bit,
     LD_Mem_Reg,
     LD_Mem_Reg_A,
@@ -121,6 +124,7 @@ impl Command {
         let (row, col) = (opcode >> 4, opcode & 0xf);
         match (row, col) {
             (0x0, 0x0) => Command::NOP,
+            (0x1, 0x0) => Command::Stop,
             (0x0, 0x7) => Command::RLCA,
             (0x1, 0x7) => Command::RLA,
             (0x2, 0x7) => Command::DAA,
@@ -232,6 +236,7 @@ impl Command {
                 }
             }
             (0xC, 0x9) => Command::RET,
+            (0xD, 0x9) => Command::RETI,
             (0xC, 0x8) => Command::RET_Eq_Zero,
             (0xD, 0x8) => Command::RET_Eq_Carry,
             (0xC, 0x0) => Command::RET_Not_Eq_Zero,
@@ -268,6 +273,8 @@ impl Command {
             (0xF, 0x9) => Command::LD_HL_SP,
             (0xE, 0x2) => Command::LD_A_C,
             (0xF, 0x2) => Command::LD_C_A,
+            (0xF, 0x3) => Command::DI,
+            (0xF, 0xB) => Command::EI,
             _ => Command::None,
         }
     }
@@ -0,0 +1,88 @@
+use crate::state::{StateError, StateReader, StateWriter};
+
+// OAM DMA copies 160 bytes from XX00-XX9F into OAM, one byte per M-cycle.
+// A write to 0xFF46 only takes over after a one M-cycle setup, so a restarted
+// transfer keeps the old one running (and OAM locked) until then
+pub struct OamDma {
+    source: u16,
+    index: u16,
+    active: bool,
+    pending: Option<u8>,
+    pub bus_value: u8,
+}
+
+impl OamDma {
+    pub const LENGTH: u16 = 0xA0;
+
+    pub fn new() -> OamDma {
+        OamDma {
+            source: 0,
+            index: 0,
+            active: false,
+            pending: None,
+            bus_value: 0xFF,
+        }
+    }
+
+    pub fn start(&mut self, value: u8) {
+        self.pending = Some(value);
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    // Advances the transfer by one M-cycle and returns the (source, destination) pair to copy
+    pub fn step(&mut self) -> Option<(u16, u16)> {
+        let transfer = if self.active {
+            let source = self.source + self.index;
+            let destination = 0xFE00 + self.index;
+            self.index += 1;
+            if self.index == Self::LENGTH {
+                self.active = false;
+            }
+            Some((source, destination))
+        } else {
+            None
+        };
+
+        if let Some(value) = self.pending.take() {
+            // Sources above 0xDFFF end up in the echo of work RAM
+            let source = (value as u16) << 8;
+            self.source = if source >= 0xE000 {
+                source - 0x2000
+            } else {
+                source
+            };
+            self.index = 0;
+            self.active = true;
+        }
+        transfer
+    }
+
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.u16(self.source);
+        state.u16(self.index);
+        state.bool(self.active);
+        state.bool(self.pending.is_some());
+        state.u8(self.pending.unwrap_or(0));
+        state.u8(self.bus_value);
+    }
+
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        self.source = state.u16()?;
+        self.index = state.u16()?;
+        self.active = state.bool()?;
+        let pending = state.bool()?;
+        let value = state.u8()?;
+        self.pending = pending.then_some(value);
+        self.bus_value = state.u8()?;
+        Ok(())
+    }
+}
+
+impl Default for OamDma {
+    fn default() -> Self {
+        Self::new()
+    }
+}
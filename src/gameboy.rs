@@ -0,0 +1,392 @@
+use std::fs::File;
+use std::io::BufWriter;
+
+use crate::audio::device::AudioDevice;
+use crate::audio::export::WavRecorder;
+use crate::audio::{AudioError, AudioOutput};
+use crate::cpu::Cpu;
+use crate::joypad::Buttons;
+use crate::memory::{bus_peek, bus_write, Memory};
+use crate::movie::rom_hash;
+use crate::rom::Catridge;
+use crate::state::{StateError, StateReader, StateWriter, HEADER_SIZE, MAGIC, VERSION};
+use crate::vgm::VgmWriter;
+use crate::video::debug::{oam_table, DebugPalette};
+use crate::video::recorder::{RecordFormat, Recorder};
+use crate::video::{screenshot, VideoError, VideoOutput};
+
+// The whole console: CPU, bus and the video output stage. Needs no window,
+// so it can run games headless for tests and tools
+pub struct GameBoy {
+    pub cpu: Cpu,
+    pub memory: Memory,
+    pub video: VideoOutput,
+    pub recorder: Option<Recorder>,
+    pub audio: Option<AudioOutput>,
+    pub wav: Option<WavRecorder>,
+    pub vgm: Option<VgmWriter<BufWriter<File>>>,
+}
+
+impl GameBoy {
+    // T-cycles in one frame, 154 lines of 456 dots
+    pub const FRAME_CYCLES: u64 = 70224;
+
+    pub fn new() -> GameBoy {
+        GameBoy {
+            cpu: Cpu::new(),
+            memory: Memory::new(),
+            video: VideoOutput::new(),
+            recorder: None,
+            audio: None,
+            wav: None,
+            vgm: None,
+        }
+    }
+
+    // Loads the catridge and puts everything in the state the boot ROM leaves it in
+    pub fn from_catridge(catridge: Catridge) -> GameBoy {
+        let mut gameboy = GameBoy::new();
+        gameboy.memory.load_catridge(catridge);
+        gameboy.skip_boot();
+        gameboy
+    }
+
+    // Same as from_catridge, running the game on a Super Game Boy. Only games with
+    // the SGB flag in their header send it commands
+    pub fn from_catridge_sgb(catridge: Catridge) -> GameBoy {
+        let mut gameboy = GameBoy::new();
+        gameboy.memory.load_catridge(catridge);
+        gameboy.memory.enable_sgb();
+        gameboy.skip_boot();
+        gameboy
+    }
+
+    pub fn skip_boot(&mut self) {
+        let registers = &mut self.cpu.registers;
+        if self.memory.ppu.sgb.is_some() {
+            (registers.a, registers.f) = (0x01, 0x00);
+            (registers.b, registers.c) = (0x00, 0x14);
+            (registers.d, registers.e) = (0x00, 0x00);
+            (registers.h, registers.l) = (0xC0, 0x60);
+        } else if self.memory.cgb_mode {
+            (registers.a, registers.f) = (0x11, 0x80);
+            (registers.b, registers.c) = (0x00, 0x00);
+            (registers.d, registers.e) = (0xFF, 0x56);
+            (registers.h, registers.l) = (0x00, 0x0D);
+        } else {
+            (registers.a, registers.f) = (0x01, 0xB0);
+            (registers.b, registers.c) = (0x00, 0x13);
+            (registers.d, registers.e) = (0x00, 0xD8);
+            (registers.h, registers.l) = (0x01, 0x4D);
+        }
+        registers.sp = 0xFFFE;
+        registers.pc = 0x100;
+
+        for (address, value) in [
+            (0xFF10, 0x80),
+            (0xFF11, 0xBF),
+            (0xFF12, 0xF3),
+            (0xFF14, 0xBF),
+            (0xFF16, 0x3F),
+            (0xFF19, 0xBF),
+            (0xFF1A, 0x7F),
+            (0xFF1B, 0xFF),
+            (0xFF1C, 0x9F),
+            (0xFF1E, 0xBF),
+            (0xFF20, 0xFF),
+            (0xFF23, 0xBF),
+            (0xFF24, 0x77),
+            (0xFF25, 0xF3),
+            (0xFF26, 0xF1),
+            (0xFF47, 0xFC),
+            (0xFF40, 0x91),
+        ] {
+            bus_write(&mut self.memory, address, value);
+        }
+    }
+
+    // Frontends call this whenever the pressed buttons change
+    pub fn set_buttons(&mut self, buttons: Buttons) {
+        let memory = &mut self.memory;
+        memory.joypad.set_buttons(buttons, &mut memory.io);
+    }
+
+    // The buttons of controller 0-3, the game only sees past the first on a Super Game
+    // Boy after asking for more players with MLT_REQ
+    pub fn set_player_buttons(&mut self, player: usize, buttons: Buttons) {
+        let memory = &mut self.memory;
+        memory
+            .joypad
+            .set_player_buttons(player, buttons, &mut memory.io);
+    }
+
+    pub fn step(&mut self) {
+        self.cpu.step(&mut self.memory);
+    }
+
+    // Runs until the PPU finishes a frame, or for a frame's worth of cycles if the LCD is off
+    pub fn run_frame(&mut self) {
+        let start = self.memory.cycles;
+        while !self.memory.ppu.take_frame() && self.memory.cycles - start < Self::FRAME_CYCLES {
+            self.step();
+        }
+        if self.recorder.is_some() {
+            let frame = self.rgba();
+            if let Some(recorder) = &mut self.recorder {
+                // Errors are kept by the recorder and reported when it stops
+                let _ = recorder.write_frame(&frame);
+            }
+        }
+        self.flush_audio();
+    }
+
+    // Hands the samples and register writes collected since the last call to the audio
+    // output, the WAV file, the recording and the VGM log
+    pub(crate) fn flush_audio(&mut self) {
+        if let Some(vgm) = &mut self.vgm {
+            for (cycle, address, data) in self
+                .memory
+                .apu_log
+                .as_mut()
+                .map(std::mem::take)
+                .unwrap_or_default()
+            {
+                // Errors show up again when the log is finished
+                let _ = vgm.write(cycle, address, data);
+            }
+        }
+        if !self.capturing() {
+            return;
+        }
+        let samples = self.memory.apu.take_samples();
+        let channels = self.memory.apu.take_channel_samples();
+        if let Some(audio) = &mut self.audio {
+            // A device that can't keep up just loses the frame's audio
+            let _ = audio.update(&samples);
+        }
+        if let Some(recorder) = &mut self.recorder {
+            // Errors are kept by the recorder and reported when it stops
+            let _ = recorder.write_audio(&samples);
+        }
+        if let Some(wav) = &mut self.wav {
+            // Errors show up again when the file is finished
+            let _ = wav.write(&samples, &channels);
+        }
+    }
+
+    fn capturing(&self) -> bool {
+        self.audio.is_some()
+            || self.wav.is_some()
+            || self.recorder.as_ref().is_some_and(Recorder::has_audio)
+    }
+
+    // The APU only collects samples while something takes them
+    pub(crate) fn update_capture(&mut self) {
+        self.memory.apu.set_capture(self.capturing());
+        let stems = self.wav.as_ref().is_some_and(|wav| wav.has_stems());
+        self.memory.apu.set_channel_capture(stems);
+    }
+
+    // Sends the sound to `device` from now on, resampled to its rate
+    pub fn start_audio(&mut self, device: Box<dyn AudioDevice>) {
+        self.audio = Some(AudioOutput::new(device));
+        self.update_capture();
+    }
+
+    pub fn stop_audio(&mut self) {
+        self.audio = None;
+        self.update_capture();
+    }
+
+    // Writes the sound of every frame run_frame finishes to a WAV file at `sample_rate`,
+    // and every channel to its own file too with `stems` (see WavRecorder)
+    pub fn start_wav(
+        &mut self,
+        filename: &str,
+        sample_rate: u32,
+        stems: bool,
+    ) -> Result<(), AudioError> {
+        self.wav = Some(WavRecorder::create(filename, sample_rate, stems)?);
+        self.update_capture();
+        Ok(())
+    }
+
+    // Finishes the files and returns the hash of the mix (see WavRecorder::hash)
+    pub fn stop_wav(&mut self) -> Result<Option<u64>, AudioError> {
+        let wav = self.wav.take();
+        self.update_capture();
+        wav.map(WavRecorder::finish).transpose()
+    }
+
+    // Logs every APU register write from now on to a VGM file. It starts with the
+    // current state of the registers, so the log plays from the middle of a song too
+    pub fn start_vgm(&mut self, filename: &str) -> Result<(), AudioError> {
+        let cycle = self.memory.cycles;
+        let mut vgm = VgmWriter::create(filename, cycle).map_err(|_| AudioError::Save)?;
+        let io = &self.memory.io;
+        let power = io.get(0xFF26) & 0x80;
+        let mut state = vec![(0xFF26, power)];
+        if power != 0 {
+            // Wave RAM while channel 3 is still off, the trigger bits only for the
+            // channels that are playing
+            state.extend((0xFF30..=0xFF3F).map(|address| (address, io.get(address))));
+            for address in
+                (0xFF10..=0xFF25).filter(|&address| address != 0xFF15 && address != 0xFF1F)
+            {
+                let value = match address {
+                    0xFF14 | 0xFF19 | 0xFF1E | 0xFF23 => {
+                        let channel = ((address - 0xFF14) / 5) as usize;
+                        let trigger = (self.memory.apu.channel_on(channel) as u8) << 7;
+                        io.get(address) & 0x7F | trigger
+                    }
+                    _ => io.get(address),
+                };
+                state.push((address, value));
+            }
+        }
+        for (address, data) in state {
+            vgm.write(cycle, address, data)
+                .map_err(|_| AudioError::Save)?;
+        }
+        self.memory.apu_log = Some(Vec::new());
+        self.vgm = Some(vgm);
+        Ok(())
+    }
+
+    pub fn stop_vgm(&mut self) -> Result<(), AudioError> {
+        self.flush_audio();
+        self.memory.apu_log = None;
+        match self.vgm.take() {
+            Some(vgm) => vgm
+                .finish(self.memory.cycles)
+                .map(|_| ())
+                .map_err(|_| AudioError::Save),
+            None => Ok(()),
+        }
+    }
+
+    // Every frame run_frame finishes from now on goes to the file, the format is picked
+    // from the extension (.y4m, .png/.apng or .gif), see RecordFormat
+    pub fn start_recording(&mut self, filename: &str) -> Result<(), VideoError> {
+        let format = RecordFormat::from_filename(filename).ok_or(VideoError::Save)?;
+        let (width, height) = self.video.size();
+        self.recorder = Some(Recorder::create(filename, format, width, height)?);
+        self.update_capture();
+        Ok(())
+    }
+
+    pub fn stop_recording(&mut self) -> Result<(), VideoError> {
+        let recorder = self.recorder.take();
+        self.update_capture();
+        match recorder {
+            Some(recorder) => recorder.finish(),
+            None => Ok(()),
+        }
+    }
+
+    // Runs until the CPU is about to execute LD B,B, the breakpoint test ROMs like the
+    // acid2 ones use to signal they're done. Gives up after max_frames frames
+    pub fn run_until_breakpoint(&mut self, max_frames: u64) -> bool {
+        let end = self.memory.cycles + max_frames * Self::FRAME_CYCLES;
+        while self.memory.cycles < end {
+            if !self.cpu.halted && bus_peek(&self.memory, self.cpu.registers.pc) == Some(0x40) {
+                return true;
+            }
+            self.step();
+        }
+        false
+    }
+
+    pub fn rgba(&mut self) -> Vec<u8> {
+        self.video.rgba(&self.memory.ppu)
+    }
+
+    // Writes the current frame as a PNG, through the selected palette and colour correction
+    pub fn screenshot(&mut self, filename: &str) -> Result<(), VideoError> {
+        let (width, height) = self.video.size();
+        screenshot::write_png(filename, width as u32, height as u32, &self.rgba())
+    }
+
+    pub fn frame_hash(&self) -> u64 {
+        screenshot::frame_hash(&self.memory.ppu)
+    }
+
+    // Writes the tile sheet, both tile maps and the OAM view as PNGs into `dir`,
+    // and returns the OAM table
+    pub fn dump_vram(&self, dir: &str) -> Result<String, VideoError> {
+        let palette = if self.memory.cgb_mode {
+            DebugPalette::CgbBackground(0)
+        } else {
+            DebugPalette::Bgp
+        };
+        let path = |name: &str| format!("{}/{}", dir, name);
+        let video = &self.video;
+        video
+            .tile_sheet(&self.memory, palette)
+            .save(&path("tiles.png"))?;
+        video
+            .tile_map(&self.memory, 0x9800)
+            .save(&path("map_9800.png"))?;
+        video
+            .tile_map(&self.memory, 0x9C00)
+            .save(&path("map_9C00.png"))?;
+        video.oam_sheet(&self.memory).save(&path("oam.png"))?;
+        Ok(oam_table(&self.memory))
+    }
+
+    // ---------------------SAVE STATES--------------------
+
+    pub fn save_state(&self, filename: &str) -> Result<(), StateError> {
+        std::fs::write(filename, self.state_data()).map_err(|_| StateError::Save)
+    }
+
+    pub fn load_state(&mut self, filename: &str) -> Result<(), StateError> {
+        let data = std::fs::read(filename).map_err(|_| StateError::Load)?;
+        self.load_state_data(&data)
+    }
+
+    // A save state of the console as it is, see state.rs for what goes in
+    pub fn state_data(&self) -> Vec<u8> {
+        let mut state = StateWriter::new();
+        state.bytes(MAGIC);
+        state.u8(VERSION);
+        state.u64(rom_hash(&self.memory.catridge().data));
+        self.cpu.save_state(&mut state);
+        self.memory.save_state(&mut state);
+        state.data
+    }
+
+    // Puts the console back where the state was saved. The catridge has to be the one
+    // it was saved with, and a state that doesn't load leaves the console as it was
+    pub fn load_state_data(&mut self, data: &[u8]) -> Result<(), StateError> {
+        if data.len() < HEADER_SIZE || &data[0..4] != MAGIC || data[4] != VERSION {
+            return Err(StateError::Header);
+        }
+        let hash = u64::from_le_bytes(data[5..HEADER_SIZE].try_into().unwrap());
+        if hash != rom_hash(&self.memory.catridge().data) {
+            return Err(StateError::Rom);
+        }
+        let backup = self.state_data();
+        let result = self.read_state(&data[HEADER_SIZE..]);
+        if result.is_err() {
+            self.read_state(&backup[HEADER_SIZE..]).unwrap();
+        }
+        result
+    }
+
+    fn read_state(&mut self, data: &[u8]) -> Result<(), StateError> {
+        let mut state = StateReader::new(data);
+        self.cpu.load_state(&mut state)?;
+        self.memory.load_state(&mut state)?;
+        if !state.finished() {
+            return Err(StateError::Data);
+        }
+        Ok(())
+    }
+}
+
+impl Default for GameBoy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
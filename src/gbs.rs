@@ -0,0 +1,238 @@
+use crate::audio::export::PCM_HASH_START;
+use crate::audio::AudioError;
+use crate::cpu::Cpu;
+use crate::gameboy::GameBoy;
+use crate::io::Interrupt;
+use crate::memory::{bus_tick, bus_write, Memory};
+use crate::rom::{Catridge, CatridgeType};
+
+const CLOCK_RATE: u64 = 4194304;
+const HEADER_SIZE: usize = 0x70;
+// Where init and play return to, an endless JR in the image the CPU never gets to run
+const IDLE: u16 = 0x0070;
+
+#[derive(Debug)]
+pub enum GbsError {
+    Load,
+    Header,
+}
+
+// A Game Boy Sound System rip: the music code and data of a game plus the addresses
+// to call it at
+pub struct Gbs {
+    pub version: u8,
+    pub songs: u8,
+    // 1 based, like in the header
+    pub first_song: u8,
+    pub load_address: u16,
+    pub init_address: u16,
+    pub play_address: u16,
+    pub stack_pointer: u16,
+    pub timer_modulo: u8,
+    pub timer_control: u8,
+    pub title: String,
+    pub author: String,
+    pub copyright: String,
+    pub data: Vec<u8>,
+}
+
+impl Gbs {
+    pub fn new(filename: &str) -> Result<Gbs, GbsError> {
+        let data = std::fs::read(filename).map_err(|_| GbsError::Load)?;
+        Self::from_bytes(&data)
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Gbs, GbsError> {
+        if data.len() < HEADER_SIZE || &data[0..3] != b"GBS" {
+            return Err(GbsError::Header);
+        }
+        let word = |offset: usize| u16::from_le_bytes([data[offset], data[offset + 1]]);
+        let text = |offset: usize| {
+            let text = &data[offset..offset + 32];
+            let end = text.iter().position(|&c| c == 0).unwrap_or(text.len());
+            String::from_utf8_lossy(&text[..end]).trim().to_string()
+        };
+        let gbs = Gbs {
+            version: data[0x03],
+            songs: data[0x04],
+            first_song: data[0x05],
+            load_address: word(0x06),
+            init_address: word(0x08),
+            play_address: word(0x0A),
+            stack_pointer: word(0x0C),
+            timer_modulo: data[0x0E],
+            timer_control: data[0x0F],
+            title: text(0x10),
+            author: text(0x30),
+            copyright: text(0x50),
+            data: data[HEADER_SIZE..].to_vec(),
+        };
+        // The code has to stay clear of the vectors the image puts below it
+        if gbs.songs == 0 || gbs.load_address < 0x400 || gbs.load_address >= 0x8000 {
+            return Err(GbsError::Header);
+        }
+        Ok(gbs)
+    }
+
+    // Play is called from the timer interrupt instead of V-blank
+    pub fn uses_timer(&self) -> bool {
+        self.timer_control & 0x04 != 0
+    }
+
+    // How often play is called, in Hz
+    pub fn play_rate(&self) -> f64 {
+        if !self.uses_timer() {
+            return CLOCK_RATE as f64 / GameBoy::FRAME_CYCLES as f64;
+        }
+        let divider = [1024, 16, 64, 256][(self.timer_control & 0x3) as usize] as f64;
+        CLOCK_RATE as f64 / divider / (256 - self.timer_modulo as u16) as f64
+    }
+
+    // A ROM only catridge with the data at the load address. The RST vectors jump to the
+    // same offset from the load address, the interrupt vectors only return since play
+    // is called by the player
+    pub fn image(&self) -> Catridge {
+        let load_address = self.load_address as usize;
+        let size = (load_address + self.data.len())
+            .next_multiple_of(0x4000)
+            .max(0x8000);
+        let mut rom = vec![0xFF; size];
+        rom[load_address..load_address + self.data.len()].copy_from_slice(&self.data);
+        for vector in (0x00..0x40).step_by(8) {
+            let [low, high] = (self.load_address + vector as u16).to_le_bytes();
+            rom[vector..vector + 3].copy_from_slice(&[0xC3, low, high]);
+        }
+        for vector in (0x40..=0x60).step_by(8) {
+            rom[vector] = 0xD9;
+        }
+        rom[IDLE as usize..IDLE as usize + 2].copy_from_slice(&[0x18, 0xFE]);
+
+        let mut catridge = Catridge::new_empty();
+        catridge.title = self.title.clone();
+        catridge.catridge_type = vec![CatridgeType::Rom];
+        catridge.rom_size = size / 1024;
+        catridge.ram_size = 0x2000;
+        catridge.ram = vec![0; 0x2000];
+        catridge.data = rom;
+        catridge
+    }
+}
+
+// Plays a GBS file on the CPU, the bus and the APU, with the LCD off. Init runs once per
+// track, play on every V-blank or timer interrupt after that
+pub struct GbsPlayer {
+    pub gbs: Gbs,
+    pub gameboy: GameBoy,
+    track: u8,
+    // Play is due but the CPU is still busy with the last call
+    pending: bool,
+    next_frame: u64,
+}
+
+impl GbsPlayer {
+    // Starts the first song of the file
+    pub fn new(gbs: Gbs) -> GbsPlayer {
+        let track = gbs.first_song.saturating_sub(1);
+        let mut player = GbsPlayer {
+            gbs,
+            gameboy: GameBoy::new(),
+            track,
+            pending: false,
+            next_frame: 0,
+        };
+        player.start(track);
+        player
+    }
+
+    pub fn track(&self) -> u8 {
+        self.track
+    }
+
+    // Resets the console and runs init for the 0 based track. The audio outputs and the
+    // VGM log keep going, so the time doesn't start over
+    pub fn start(&mut self, track: u8) {
+        self.track = track.min(self.gbs.songs - 1);
+        let gameboy = &mut self.gameboy;
+        let (cycles, apu_log) = (gameboy.memory.cycles, gameboy.memory.apu_log.take());
+        gameboy.cpu = Cpu::new();
+        gameboy.memory = Memory::new();
+        (gameboy.memory.cycles, gameboy.memory.apu_log) = (cycles, apu_log);
+        gameboy.memory.load_gbs(self.gbs.image());
+        gameboy.skip_boot();
+        gameboy.update_capture();
+        let memory = &mut gameboy.memory;
+        bus_write(memory, 0xFF40, 0x00);
+        if self.gbs.uses_timer() {
+            bus_write(memory, 0xFF06, self.gbs.timer_modulo);
+            bus_write(memory, 0xFF07, self.gbs.timer_control & 0x07);
+        }
+        gameboy.cpu.registers.sp = self.gbs.stack_pointer;
+        gameboy.cpu.registers.a = self.track;
+        self.call(self.gbs.init_address);
+        self.pending = false;
+        self.next_frame = self.gameboy.memory.cycles + GameBoy::FRAME_CYCLES;
+    }
+
+    // Pushes the idle address and jumps to the routine, for it to return to idle
+    fn call(&mut self, address: u16) {
+        let registers = &mut self.gameboy.cpu.registers;
+        registers.sp = registers.sp.wrapping_sub(2);
+        let [low, high] = IDLE.to_le_bytes();
+        bus_write(&mut self.gameboy.memory, registers.sp, low);
+        bus_write(&mut self.gameboy.memory, registers.sp.wrapping_add(1), high);
+        registers.pc = address;
+    }
+
+    fn update_pending(&mut self) {
+        let memory = &mut self.gameboy.memory;
+        if self.gbs.uses_timer() {
+            let flags = memory.io.get(0xFF0F);
+            if flags & Interrupt::Timer.mask() != 0 {
+                memory.io.set(0xFF0F, flags & !Interrupt::Timer.mask());
+                self.pending = true;
+            }
+        } else if memory.cycles >= self.next_frame {
+            self.next_frame += GameBoy::FRAME_CYCLES;
+            self.pending = true;
+        }
+    }
+
+    // Runs for the given amount of T-cycles and hands the sound to the audio outputs
+    pub fn run(&mut self, cycles: u64) {
+        let end = self.gameboy.memory.cycles + cycles;
+        while self.gameboy.memory.cycles < end {
+            self.update_pending();
+            if self.gameboy.cpu.registers.pc != IDLE {
+                self.gameboy.step();
+            } else if self.pending {
+                self.pending = false;
+                self.call(self.gbs.play_address);
+            } else {
+                bus_tick(&mut self.gameboy.memory, 4);
+            }
+        }
+        self.gameboy.flush_audio();
+    }
+
+    pub fn run_frame(&mut self) {
+        self.run(GameBoy::FRAME_CYCLES);
+    }
+
+    // Writes `seconds` of the current track to a WAV file, and its stems with `stems`.
+    // Returns the PCM hash of the mix
+    pub fn render_wav(
+        &mut self,
+        filename: &str,
+        sample_rate: u32,
+        stems: bool,
+        seconds: u32,
+    ) -> Result<u64, AudioError> {
+        self.gameboy.start_wav(filename, sample_rate, stems)?;
+        let end = self.gameboy.memory.cycles + seconds as u64 * CLOCK_RATE;
+        while self.gameboy.memory.cycles < end {
+            let cycles = (end - self.gameboy.memory.cycles).min(GameBoy::FRAME_CYCLES);
+            self.run(cycles);
+        }
+        Ok(self.gameboy.stop_wav()?.unwrap_or(PCM_HASH_START))
+    }
+}
@@ -0,0 +1,99 @@
+use crate::state::{StateError, StateReader, StateWriter};
+
+// CGB VRAM DMA. A write to HDMA5 with bit 7 clear copies everything at once (general purpose),
+// with bit 7 set it copies 16 bytes every H-blank until the length runs out or it gets cancelled
+pub struct Hdma {
+    source: u16,
+    destination: u16,
+    remaining: u8,
+    active: bool,
+}
+
+impl Hdma {
+    pub const BLOCK_SIZE: u16 = 0x10;
+    // Copying a block keeps the CPU halted for 8 M-cycles
+    pub const BLOCK_CYCLES: u32 = 32;
+
+    pub fn new() -> Hdma {
+        Hdma {
+            source: 0,
+            destination: 0,
+            remaining: 0x7F,
+            active: false,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    pub fn read(&self, address: u16) -> u8 {
+        match address {
+            0xFF55 => {
+                if self.active {
+                    self.remaining
+                } else {
+                    0x80 | self.remaining
+                }
+            }
+            _ => 0xFF,
+        }
+    }
+
+    // Returns the amount of blocks that have to be copied right away
+    pub fn write(&mut self, address: u16, data: u8) -> u8 {
+        match address {
+            0xFF51 => self.source = (data as u16) << 8 | self.source & 0xF0,
+            0xFF52 => self.source = self.source & 0xFF00 | (data & 0xF0) as u16,
+            0xFF53 => self.destination = ((data & 0x1F) as u16) << 8 | self.destination & 0xF0,
+            0xFF54 => self.destination = self.destination & 0x1F00 | (data & 0xF0) as u16,
+            0xFF55 => {
+                if self.active && data & 0x80 == 0 {
+                    self.active = false;
+                    return 0;
+                }
+                self.remaining = data & 0x7F;
+                if data & 0x80 == 0 {
+                    return self.remaining + 1;
+                }
+                self.active = true;
+            }
+            _ => (),
+        }
+        0
+    }
+
+    // Moves to the next block and returns the (source, destination) of the block just claimed
+    pub fn next_block(&mut self) -> (u16, u16) {
+        let block = (self.source, 0x8000 | self.destination);
+        self.source = self.source.wrapping_add(Self::BLOCK_SIZE);
+        self.destination = (self.destination + Self::BLOCK_SIZE) & 0x1FF0;
+        let (remaining, finished) = self.remaining.overflowing_sub(1);
+        self.remaining = remaining & 0x7F;
+        if finished {
+            self.active = false;
+        }
+        block
+    }
+
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.u16(self.source);
+        state.u16(self.destination);
+        state.u8(self.remaining);
+        state.bool(self.active);
+    }
+
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        self.source = state.u16()?;
+        self.destination = state.u16()?;
+        self.remaining = state.u8()?;
+        self.active = state.bool()?;
+        Ok(())
+    }
+}
+
+impl Default for Hdma {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,323 @@
+use std::collections::HashSet;
+
+use serde_json::Value;
+
+use crate::joypad::Buttons;
+
+#[derive(Debug)]
+pub enum InputError {
+    Load,
+    Parse,
+}
+
+// A physical input, named the way SDL names them (Keycode::name, Button::string and
+// Axis::string), compared without case. An axis is split into its two directions
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Input {
+    Key(String),
+    Button(String),
+    Axis(String, bool),
+}
+
+impl Input {
+    // "key:X", "button:a" or "axis:lefty+"
+    pub fn parse(text: &str) -> Option<Input> {
+        let (kind, name) = text.split_once(':')?;
+        let name = name.trim().to_lowercase();
+        match kind.trim() {
+            "key" => Some(Input::key(&name)),
+            "button" => Some(Input::button(&name)),
+            "axis" => {
+                let positive = match name.chars().last()? {
+                    '+' => true,
+                    '-' => false,
+                    _ => return None,
+                };
+                Some(Input::Axis(name[..name.len() - 1].to_string(), positive))
+            }
+            _ => None,
+        }
+        .filter(|input| !input.name().is_empty())
+    }
+
+    pub fn key(name: &str) -> Input {
+        Input::Key(name.to_lowercase())
+    }
+
+    pub fn button(name: &str) -> Input {
+        Input::Button(name.to_lowercase())
+    }
+
+    fn name(&self) -> &str {
+        match self {
+            Input::Key(name) | Input::Button(name) | Input::Axis(name, _) => name,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Hotkey {
+    SaveState,
+    LoadState,
+    // Held, not toggled
+    FastForward,
+    Screenshot,
+    Pause,
+    Quit,
+}
+
+impl Hotkey {
+    const NAMES: [(&'static str, Hotkey); 6] = [
+        ("save_state", Hotkey::SaveState),
+        ("load_state", Hotkey::LoadState),
+        ("fast_forward", Hotkey::FastForward),
+        ("screenshot", Hotkey::Screenshot),
+        ("pause", Hotkey::Pause),
+        ("quit", Hotkey::Quit),
+    ];
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Action {
+    Press(Buttons),
+    // Pressed and released every `turbo_rate` frames while held
+    Turbo(Buttons),
+    Hotkey(Hotkey),
+}
+
+const BUTTON_NAMES: [(&str, Buttons); 8] = [
+    ("a", Buttons::A),
+    ("b", Buttons::B),
+    ("select", Buttons::SELECT),
+    ("start", Buttons::START),
+    ("right", Buttons::RIGHT),
+    ("left", Buttons::LEFT),
+    ("up", Buttons::UP),
+    ("down", Buttons::DOWN),
+];
+
+const DEFAULT_MAP: &str = r#"{
+    "deadzone": 8000,
+    "turbo_rate": 3,
+    "buttons": {
+        "a": ["key:X", "button:a"],
+        "b": ["key:Z", "button:b"],
+        "select": ["key:Backspace", "button:back"],
+        "start": ["key:Return", "button:start"],
+        "right": ["key:Right", "button:dpright", "axis:leftx+"],
+        "left": ["key:Left", "button:dpleft", "axis:leftx-"],
+        "up": ["key:Up", "button:dpup", "axis:lefty-"],
+        "down": ["key:Down", "button:dpdown", "axis:lefty+"]
+    },
+    "turbo": {
+        "a": ["key:S", "button:x"],
+        "b": ["key:A", "button:y"]
+    },
+    "hotkeys": {
+        "save_state": ["key:F5"],
+        "load_state": ["key:F7"],
+        "fast_forward": ["key:Tab", "axis:triggerright+"],
+        "screenshot": ["key:F12"],
+        "pause": ["key:P", "button:guide"],
+        "quit": ["key:Escape"]
+    }
+}"#;
+
+// Which inputs do what, read from a JSON file like DEFAULT_MAP. Every entry lists the
+// inputs for one button or hotkey, missing sections keep the defaults
+pub struct InputMap {
+    bindings: Vec<(Input, Action)>,
+    // How far a stick has to move out of the centre to count, out of 32767
+    pub deadzone: i16,
+    // Frames a turbo button stays pressed, then released
+    pub turbo_rate: u32,
+}
+
+impl InputMap {
+    pub fn new(filename: &str) -> Result<InputMap, InputError> {
+        let json = std::fs::read_to_string(filename).map_err(|_| InputError::Load)?;
+        Self::from_json(&json)
+    }
+
+    pub fn from_json(json: &str) -> Result<InputMap, InputError> {
+        let value: Value = serde_json::from_str(json).map_err(|_| InputError::Parse)?;
+        let mut map = InputMap {
+            bindings: Vec::new(),
+            deadzone: 0,
+            turbo_rate: 1,
+        };
+        let defaults: Value = serde_json::from_str(DEFAULT_MAP).unwrap();
+        map.apply(&defaults)?;
+        map.apply(&value)?;
+        Ok(map)
+    }
+
+    // Settings and sections in `value` replace the current ones
+    fn apply(&mut self, value: &Value) -> Result<(), InputError> {
+        let object = value.as_object().ok_or(InputError::Parse)?;
+        if let Some(deadzone) = object.get("deadzone") {
+            let deadzone = deadzone.as_u64().filter(|&deadzone| deadzone < 32767);
+            self.deadzone = deadzone.ok_or(InputError::Parse)? as i16;
+        }
+        if let Some(rate) = object.get("turbo_rate") {
+            let rate = rate
+                .as_u64()
+                .filter(|&rate| rate > 0 && rate <= u32::MAX as u64);
+            self.turbo_rate = rate.ok_or(InputError::Parse)? as u32;
+        }
+        for section in ["buttons", "turbo", "hotkeys"] {
+            let Some(entries) = object.get(section) else {
+                continue;
+            };
+            let entries = entries.as_object().ok_or(InputError::Parse)?;
+            let mut bindings = Vec::new();
+            for (name, inputs) in entries {
+                let action = action(section, name).ok_or(InputError::Parse)?;
+                for input in inputs.as_array().ok_or(InputError::Parse)? {
+                    let input = input.as_str().and_then(Input::parse);
+                    bindings.push((input.ok_or(InputError::Parse)?, action));
+                }
+            }
+            self.bindings
+                .retain(|(_, action)| !in_section(section, action));
+            self.bindings.extend(bindings);
+        }
+        Ok(())
+    }
+
+    pub fn actions<'a>(&'a self, input: &'a Input) -> impl Iterator<Item = Action> + 'a {
+        self.bindings
+            .iter()
+            .filter(move |(bound, _)| bound == input)
+            .map(|&(_, action)| action)
+    }
+}
+
+impl Default for InputMap {
+    fn default() -> Self {
+        Self::from_json(DEFAULT_MAP).unwrap()
+    }
+}
+
+// What the entry `name` of a section of the map file binds its inputs to
+fn action(section: &str, name: &str) -> Option<Action> {
+    let button = || {
+        let button = BUTTON_NAMES.iter().find(|(button, _)| *button == name);
+        button.map(|&(_, buttons)| buttons)
+    };
+    match section {
+        "buttons" => button().map(Action::Press),
+        "turbo" => button().map(Action::Turbo),
+        _ => {
+            let hotkey = Hotkey::NAMES.iter().find(|(hotkey, _)| *hotkey == name);
+            hotkey.map(|&(_, hotkey)| Action::Hotkey(hotkey))
+        }
+    }
+}
+
+fn in_section(section: &str, action: &Action) -> bool {
+    matches!(
+        (section, action),
+        ("buttons", Action::Press(_))
+            | ("turbo", Action::Turbo(_))
+            | ("hotkeys", Action::Hotkey(_))
+    )
+}
+
+// Where an input comes from, so unplugging a controller releases only what it held
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Device {
+    Keyboard,
+    Controller(u32),
+}
+
+// What's held right now on the keyboard and every connected controller, turned into
+// Game Boy buttons and hotkeys through the map
+pub struct InputState {
+    pub map: InputMap,
+    held: HashSet<(Device, Input)>,
+    // Hotkeys pressed since the last take_hotkeys
+    hotkeys: Vec<Hotkey>,
+    frame: u64,
+}
+
+impl InputState {
+    pub fn new(map: InputMap) -> InputState {
+        InputState {
+            map,
+            held: HashSet::new(),
+            hotkeys: Vec::new(),
+            frame: 0,
+        }
+    }
+
+    pub fn press(&mut self, device: Device, input: Input) {
+        if !self.held.insert((device, input.clone())) {
+            return;
+        }
+        for action in self.map.actions(&input) {
+            if let Action::Hotkey(hotkey) = action {
+                self.hotkeys.push(hotkey);
+            }
+        }
+    }
+
+    pub fn release(&mut self, device: Device, input: &Input) {
+        self.held.remove(&(device, input.clone()));
+    }
+
+    // A stick or trigger moved. Past the deadzone it presses that direction
+    pub fn axis(&mut self, device: Device, axis: &str, value: i16) {
+        let axis = axis.to_lowercase();
+        let deadzone = self.map.deadzone as i32;
+        for positive in [true, false] {
+            let input = Input::Axis(axis.clone(), positive);
+            let past = if positive {
+                value as i32 > deadzone
+            } else {
+                (value as i32) < -deadzone
+            };
+            if past {
+                self.press(device, input);
+            } else {
+                self.release(device, &input);
+            }
+        }
+    }
+
+    // Lets go of everything a controller held, for when it gets unplugged
+    pub fn remove_device(&mut self, device: Device) {
+        self.held.retain(|(held, _)| *held != device);
+    }
+
+    fn held_actions(&self) -> impl Iterator<Item = Action> + '_ {
+        self.held
+            .iter()
+            .flat_map(|(_, input)| self.map.actions(input))
+    }
+
+    // The buttons to give the game this frame
+    pub fn buttons(&self) -> Buttons {
+        let turbo_on = (self.frame / self.map.turbo_rate as u64).is_multiple_of(2);
+        self.held_actions()
+            .fold(Buttons::NONE, |buttons, action| match action {
+                Action::Press(pressed) => buttons | pressed,
+                Action::Turbo(pressed) if turbo_on => buttons | pressed,
+                _ => buttons,
+            })
+    }
+
+    pub fn hotkey_held(&self, hotkey: Hotkey) -> bool {
+        self.held_actions()
+            .any(|action| action == Action::Hotkey(hotkey))
+    }
+
+    pub fn take_hotkeys(&mut self) -> Vec<Hotkey> {
+        std::mem::take(&mut self.hotkeys)
+    }
+
+    // Call once per emulated frame, for the turbo buttons
+    pub fn next_frame(&mut self) {
+        self.frame += 1;
+    }
+}
@@ -0,0 +1,407 @@
+use crate::state::{StateError, StateReader, StateWriter};
+
+// The IO registers from 0xFF00 to 0xFF7F. Every register has three masks:
+//   unused     -> bits that do not exist, they always read as 1 and ignore writes
+//   read_only  -> bits the hardware updates, writes from the CPU are ignored
+//   write_only -> bits that can be written but always read as 1
+// Addresses without a register behave as if every bit is unused
+pub struct Io {
+    values: [u8; Io::SIZE],
+}
+
+#[derive(Clone, Copy)]
+pub struct IoRegister {
+    pub name: &'static str,
+    pub unused: u8,
+    pub read_only: u8,
+    pub write_only: u8,
+    pub cgb_only: bool,
+}
+
+impl IoRegister {
+    const fn new(name: &'static str, unused: u8, read_only: u8, write_only: u8) -> Self {
+        IoRegister {
+            name,
+            unused,
+            read_only,
+            write_only,
+            cgb_only: false,
+        }
+    }
+
+    const fn cgb(name: &'static str, unused: u8, read_only: u8, write_only: u8) -> Self {
+        IoRegister {
+            name,
+            unused,
+            read_only,
+            write_only,
+            cgb_only: true,
+        }
+    }
+
+    pub const UNMAPPED: IoRegister = IoRegister::new("UNMAPPED", 0xFF, 0x00, 0x00);
+
+    pub fn get(address: u16, cgb: bool) -> IoRegister {
+        let register = match address {
+            // ---------------------JOYPAD, SERIAL AND TIMER--------------------
+            0xFF00 => IoRegister::new("P1", 0xC0, 0x0F, 0x00),
+            0xFF01 => IoRegister::new("SB", 0x00, 0x00, 0x00),
+            // Bit 1 (clock speed) only exists on the CGB
+            0xFF02 if cgb => IoRegister::new("SC", 0x7C, 0x00, 0x00),
+            0xFF02 => IoRegister::new("SC", 0x7E, 0x00, 0x00),
+            0xFF04 => IoRegister::new("DIV", 0x00, 0x00, 0x00),
+            0xFF05 => IoRegister::new("TIMA", 0x00, 0x00, 0x00),
+            0xFF06 => IoRegister::new("TMA", 0x00, 0x00, 0x00),
+            0xFF07 => IoRegister::new("TAC", 0xF8, 0x00, 0x00),
+            0xFF0F => IoRegister::new("IF", 0xE0, 0x00, 0x00),
+
+            // ---------------------SOUND--------------------
+            0xFF10 => IoRegister::new("NR10", 0x80, 0x00, 0x00),
+            0xFF11 => IoRegister::new("NR11", 0x00, 0x00, 0x3F),
+            0xFF12 => IoRegister::new("NR12", 0x00, 0x00, 0x00),
+            0xFF13 => IoRegister::new("NR13", 0x00, 0x00, 0xFF),
+            0xFF14 => IoRegister::new("NR14", 0x38, 0x00, 0x87),
+            0xFF16 => IoRegister::new("NR21", 0x00, 0x00, 0x3F),
+            0xFF17 => IoRegister::new("NR22", 0x00, 0x00, 0x00),
+            0xFF18 => IoRegister::new("NR23", 0x00, 0x00, 0xFF),
+            0xFF19 => IoRegister::new("NR24", 0x38, 0x00, 0x87),
+            0xFF1A => IoRegister::new("NR30", 0x7F, 0x00, 0x00),
+            0xFF1B => IoRegister::new("NR31", 0x00, 0x00, 0xFF),
+            0xFF1C => IoRegister::new("NR32", 0x9F, 0x00, 0x00),
+            0xFF1D => IoRegister::new("NR33", 0x00, 0x00, 0xFF),
+            0xFF1E => IoRegister::new("NR34", 0x38, 0x00, 0x87),
+            0xFF20 => IoRegister::new("NR41", 0xC0, 0x00, 0x3F),
+            0xFF21 => IoRegister::new("NR42", 0x00, 0x00, 0x00),
+            0xFF22 => IoRegister::new("NR43", 0x00, 0x00, 0x00),
+            0xFF23 => IoRegister::new("NR44", 0x3F, 0x00, 0x80),
+            0xFF24 => IoRegister::new("NR50", 0x00, 0x00, 0x00),
+            0xFF25 => IoRegister::new("NR51", 0x00, 0x00, 0x00),
+            0xFF26 => IoRegister::new("NR52", 0x70, 0x0F, 0x00),
+            0xFF30..=0xFF3F => IoRegister::new("WAVE", 0x00, 0x00, 0x00),
+
+            // ---------------------LCD--------------------
+            0xFF40 => IoRegister::new("LCDC", 0x00, 0x00, 0x00),
+            0xFF41 => IoRegister::new("STAT", 0x80, 0x07, 0x00),
+            0xFF42 => IoRegister::new("SCY", 0x00, 0x00, 0x00),
+            0xFF43 => IoRegister::new("SCX", 0x00, 0x00, 0x00),
+            0xFF44 => IoRegister::new("LY", 0x00, 0xFF, 0x00),
+            0xFF45 => IoRegister::new("LYC", 0x00, 0x00, 0x00),
+            0xFF46 => IoRegister::new("DMA", 0x00, 0x00, 0x00),
+            0xFF47 => IoRegister::new("BGP", 0x00, 0x00, 0x00),
+            0xFF48 => IoRegister::new("OBP0", 0x00, 0x00, 0x00),
+            0xFF49 => IoRegister::new("OBP1", 0x00, 0x00, 0x00),
+            0xFF4A => IoRegister::new("WY", 0x00, 0x00, 0x00),
+            0xFF4B => IoRegister::new("WX", 0x00, 0x00, 0x00),
+
+            // ---------------------CGB--------------------
+            0xFF4D => IoRegister::cgb("KEY1", 0x7E, 0x80, 0x00),
+            0xFF4F => IoRegister::cgb("VBK", 0xFE, 0x00, 0x00),
+            0xFF51 => IoRegister::cgb("HDMA1", 0x00, 0x00, 0xFF),
+            0xFF52 => IoRegister::cgb("HDMA2", 0x00, 0x00, 0xFF),
+            0xFF53 => IoRegister::cgb("HDMA3", 0x00, 0x00, 0xFF),
+            0xFF54 => IoRegister::cgb("HDMA4", 0x00, 0x00, 0xFF),
+            0xFF55 => IoRegister::cgb("HDMA5", 0x00, 0x00, 0x00),
+            0xFF56 => IoRegister::cgb("RP", 0x3C, 0x02, 0x00),
+            0xFF68 => IoRegister::cgb("BCPS", 0x40, 0x00, 0x00),
+            0xFF69 => IoRegister::cgb("BCPD", 0x00, 0x00, 0x00),
+            0xFF6A => IoRegister::cgb("OCPS", 0x40, 0x00, 0x00),
+            0xFF6B => IoRegister::cgb("OCPD", 0x00, 0x00, 0x00),
+            0xFF6C => IoRegister::cgb("OPRI", 0xFE, 0x00, 0x00),
+            0xFF70 => IoRegister::cgb("SVBK", 0xF8, 0x00, 0x00),
+            0xFF72 => IoRegister::cgb("FF72", 0x00, 0x00, 0x00),
+            0xFF73 => IoRegister::cgb("FF73", 0x00, 0x00, 0x00),
+            0xFF74 => IoRegister::cgb("FF74", 0x00, 0x00, 0x00),
+            0xFF75 => IoRegister::cgb("FF75", 0x8F, 0x00, 0x00),
+            0xFF76 => IoRegister::cgb("PCM12", 0x00, 0xFF, 0x00),
+            0xFF77 => IoRegister::cgb("PCM34", 0x00, 0xFF, 0x00),
+            _ => IoRegister::UNMAPPED,
+        };
+        if register.cgb_only && !cgb {
+            IoRegister::UNMAPPED
+        } else {
+            register
+        }
+    }
+
+    // Bits that always read back as 1
+    pub fn read_mask(&self) -> u8 {
+        self.unused | self.write_only
+    }
+
+    // Bits that the CPU is allowed to change
+    pub fn write_mask(&self) -> u8 {
+        !(self.unused | self.read_only)
+    }
+}
+
+impl Io {
+    pub const SIZE: usize = 0x80;
+
+    pub fn new() -> Io {
+        let mut values = [0; Self::SIZE];
+        // No buttons pressed, the inputs are active low
+        values[0x00] = 0x0F;
+        // The APU is powered on, like the boot ROM leaves it
+        values[0x26] = 0x80;
+        Io { values }
+    }
+
+    fn index(address: u16) -> usize {
+        (address as usize - 0xFF00) % Self::SIZE
+    }
+
+    pub fn read(&self, address: u16, cgb: bool) -> u8 {
+        let register = IoRegister::get(address, cgb);
+        self.values[Self::index(address)] | register.read_mask()
+    }
+
+    pub fn write(&mut self, address: u16, data: u8, cgb: bool) {
+        let mask = IoRegister::get(address, cgb).write_mask();
+        let value = &mut self.values[Self::index(address)];
+        *value = *value & !mask | data & mask;
+    }
+
+    // Raw access for the hardware side, it skips all the masks
+    pub fn get(&self, address: u16) -> u8 {
+        self.values[Self::index(address)]
+    }
+
+    pub fn set(&mut self, address: u16, data: u8) {
+        self.values[Self::index(address)] = data;
+    }
+
+    pub fn request_interrupt(&mut self, interrupt: Interrupt) {
+        self.values[Self::index(0xFF0F)] |= interrupt.mask();
+    }
+
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.bytes(&self.values);
+    }
+
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        state.bytes(&mut self.values)
+    }
+
+    // ---------------------TYPED REGISTERS--------------------
+
+    pub fn p1(&self) -> P1 {
+        P1(self.get(0xFF00))
+    }
+
+    pub fn sc(&self) -> Sc {
+        Sc(self.get(0xFF02))
+    }
+
+    pub fn tac(&self) -> Tac {
+        Tac(self.get(0xFF07))
+    }
+
+    pub fn interrupt_flags(&self) -> u8 {
+        self.get(0xFF0F) & 0x1F
+    }
+
+    pub fn nr52(&self) -> Nr52 {
+        Nr52(self.get(0xFF26))
+    }
+
+    pub fn lcdc(&self) -> Lcdc {
+        Lcdc(self.get(0xFF40))
+    }
+
+    pub fn stat(&self) -> Stat {
+        Stat(self.get(0xFF41))
+    }
+}
+
+impl Default for Io {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Interrupt {
+    VBlank,
+    Stat,
+    Timer,
+    Serial,
+    Joypad,
+}
+
+impl Interrupt {
+    pub const ALL: [Interrupt; 5] = [
+        Interrupt::VBlank,
+        Interrupt::Stat,
+        Interrupt::Timer,
+        Interrupt::Serial,
+        Interrupt::Joypad,
+    ];
+
+    pub fn mask(&self) -> u8 {
+        match self {
+            Interrupt::VBlank => 1 << 0,
+            Interrupt::Stat => 1 << 1,
+            Interrupt::Timer => 1 << 2,
+            Interrupt::Serial => 1 << 3,
+            Interrupt::Joypad => 1 << 4,
+        }
+    }
+
+    pub fn vector(&self) -> u16 {
+        match self {
+            Interrupt::VBlank => 0x40,
+            Interrupt::Stat => 0x48,
+            Interrupt::Timer => 0x50,
+            Interrupt::Serial => 0x58,
+            Interrupt::Joypad => 0x60,
+        }
+    }
+}
+
+// P1 (0xFF00), the selection bits are active low
+#[derive(Clone, Copy)]
+pub struct P1(pub u8);
+
+impl P1 {
+    pub fn select_buttons(&self) -> bool {
+        self.0 & 0x20 == 0
+    }
+
+    pub fn select_directions(&self) -> bool {
+        self.0 & 0x10 == 0
+    }
+}
+
+// SC (0xFF02)
+#[derive(Clone, Copy)]
+pub struct Sc(pub u8);
+
+impl Sc {
+    pub fn transfer_enable(&self) -> bool {
+        self.0 & 0x80 != 0
+    }
+
+    pub fn fast_clock(&self) -> bool {
+        self.0 & 0x02 != 0
+    }
+
+    pub fn internal_clock(&self) -> bool {
+        self.0 & 0x01 != 0
+    }
+}
+
+// TAC (0xFF07)
+#[derive(Clone, Copy)]
+pub struct Tac(pub u8);
+
+impl Tac {
+    pub fn enabled(&self) -> bool {
+        self.0 & 0x4 != 0
+    }
+
+    // The bit of the system counter that clocks TIMA
+    pub fn counter_bit(&self) -> u8 {
+        match self.0 & 0x3 {
+            0 => 9,
+            1 => 3,
+            2 => 5,
+            _ => 7,
+        }
+    }
+}
+
+// NR52 (0xFF26)
+#[derive(Clone, Copy)]
+pub struct Nr52(pub u8);
+
+impl Nr52 {
+    pub fn power(&self) -> bool {
+        self.0 & 0x80 != 0
+    }
+
+    pub fn channel_on(&self, channel: u8) -> bool {
+        self.0 & (1 << channel) != 0
+    }
+}
+
+// LCDC (0xFF40)
+#[derive(Clone, Copy)]
+pub struct Lcdc(pub u8);
+
+impl Lcdc {
+    pub fn lcd_enable(&self) -> bool {
+        self.0 & 0x80 != 0
+    }
+
+    pub fn window_tile_map(&self) -> u16 {
+        if self.0 & 0x40 != 0 {
+            0x9C00
+        } else {
+            0x9800
+        }
+    }
+
+    pub fn window_enable(&self) -> bool {
+        self.0 & 0x20 != 0
+    }
+
+    // True for the 8000 addressing mode, false for the 8800 one
+    pub fn unsigned_tile_data(&self) -> bool {
+        self.0 & 0x10 != 0
+    }
+
+    pub fn bg_tile_map(&self) -> u16 {
+        if self.0 & 0x08 != 0 {
+            0x9C00
+        } else {
+            0x9800
+        }
+    }
+
+    pub fn sprite_height(&self) -> u8 {
+        if self.0 & 0x04 != 0 {
+            16
+        } else {
+            8
+        }
+    }
+
+    pub fn sprite_enable(&self) -> bool {
+        self.0 & 0x02 != 0
+    }
+
+    // On the CGB this is the BG/window master priority instead
+    pub fn bg_enable(&self) -> bool {
+        self.0 & 0x01 != 0
+    }
+}
+
+// STAT (0xFF41)
+#[derive(Clone, Copy)]
+pub struct Stat(pub u8);
+
+impl Stat {
+    pub fn mode(&self) -> u8 {
+        self.0 & 0x3
+    }
+
+    pub fn lyc_equal(&self) -> bool {
+        self.0 & 0x04 != 0
+    }
+
+    pub fn hblank_interrupt(&self) -> bool {
+        self.0 & 0x08 != 0
+    }
+
+    pub fn vblank_interrupt(&self) -> bool {
+        self.0 & 0x10 != 0
+    }
+
+    pub fn oam_interrupt(&self) -> bool {
+        self.0 & 0x20 != 0
+    }
+
+    pub fn lyc_interrupt(&self) -> bool {
+        self.0 & 0x40 != 0
+    }
+}
@@ -0,0 +1,146 @@
+use std::cell::Cell;
+use std::ops::BitOr;
+
+use crate::io::{Interrupt, Io};
+use crate::state::{StateError, StateReader, StateWriter};
+
+// Pressed buttons, one bit each. The low nibble are the buttons and the high nibble the
+// directions, in the order of their P1 lines
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub struct Buttons(pub u8);
+
+impl Buttons {
+    pub const NONE: Buttons = Buttons(0);
+    pub const A: Buttons = Buttons(1 << 0);
+    pub const B: Buttons = Buttons(1 << 1);
+    pub const SELECT: Buttons = Buttons(1 << 2);
+    pub const START: Buttons = Buttons(1 << 3);
+    pub const RIGHT: Buttons = Buttons(1 << 4);
+    pub const LEFT: Buttons = Buttons(1 << 5);
+    pub const UP: Buttons = Buttons(1 << 6);
+    pub const DOWN: Buttons = Buttons(1 << 7);
+
+    pub fn contains(&self, buttons: Buttons) -> bool {
+        self.0 & buttons.0 == buttons.0
+    }
+
+    pub fn set(&mut self, buttons: Buttons, pressed: bool) {
+        if pressed {
+            self.0 |= buttons.0;
+        } else {
+            self.0 &= !buttons.0;
+        }
+    }
+}
+
+impl BitOr for Buttons {
+    type Output = Buttons;
+
+    fn bitor(self, other: Buttons) -> Buttons {
+        Buttons(self.0 | other.0)
+    }
+}
+
+// The button matrix behind P1: bits 4 and 5 pick the directions and the buttons, the
+// low nibble reads the picked lines, all active low. A line going low requests the
+// joypad interrupt and gets the CPU out of STOP
+pub struct Joypad {
+    // One set per controller, only a Super Game Boy after MLT_REQ reads past the first
+    buttons: [Buttons; Joypad::PLAYERS],
+    // The controller P1 shows the lines of
+    player: usize,
+    // P1 was read since the last take_polled, a frame without is a lag frame
+    polled: Cell<bool>,
+}
+
+impl Joypad {
+    pub const PLAYERS: usize = 4;
+
+    pub fn new() -> Joypad {
+        Joypad {
+            buttons: [Buttons::NONE; Self::PLAYERS],
+            player: 0,
+            polled: Cell::new(false),
+        }
+    }
+
+    pub fn buttons(&self, player: usize) -> Buttons {
+        self.buttons[player]
+    }
+
+    // The buttons of player 1
+    pub fn set_buttons(&mut self, buttons: Buttons, io: &mut Io) {
+        self.set_player_buttons(0, buttons, io);
+    }
+
+    pub fn set_player_buttons(&mut self, player: usize, buttons: Buttons, io: &mut Io) {
+        self.buttons[player] = buttons;
+        self.update(io);
+    }
+
+    pub fn player(&self) -> usize {
+        self.player
+    }
+
+    // Switches P1 over to the lines of another controller
+    pub fn set_player(&mut self, player: usize, io: &mut Io) {
+        self.player = player;
+        self.update(io);
+    }
+
+    // Reads go through a shared borrow of the memory, hence the cell
+    pub fn poll(&self) {
+        self.polled.set(true);
+    }
+
+    pub fn take_polled(&self) -> bool {
+        self.polled.replace(false)
+    }
+
+    // The low nibble of P1 for the given selection bits, from the current controller
+    pub fn lines(&self, p1: u8) -> u8 {
+        let mut pressed = 0;
+        let buttons = self.buttons[self.player];
+        if p1 & 0x20 == 0 {
+            pressed |= buttons.0 & 0xF;
+        }
+        if p1 & 0x10 == 0 {
+            pressed |= buttons.0 >> 4;
+        }
+        !pressed & 0xF
+    }
+
+    // Puts the lines into P1 after the buttons or the selection changed
+    pub fn update(&self, io: &mut Io) {
+        let p1 = io.get(0xFF00);
+        let lines = self.lines(p1);
+        if p1 & !lines & 0xF != 0 {
+            io.request_interrupt(Interrupt::Joypad);
+        }
+        io.set(0xFF00, p1 & 0xF0 | lines);
+    }
+
+    pub fn save_state(&self, state: &mut StateWriter) {
+        for buttons in self.buttons {
+            state.u8(buttons.0);
+        }
+        state.u8(self.player as u8);
+    }
+
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        for buttons in &mut self.buttons {
+            *buttons = Buttons(state.u8()?);
+        }
+        self.player = state.u8()? as usize;
+        if self.player >= Self::PLAYERS {
+            return Err(StateError::Data);
+        }
+        Ok(())
+    }
+}
+
+impl Default for Joypad {
+    fn default() -> Self {
+        Self::new()
+    }
+}
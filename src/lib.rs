@@ -1,9 +1,64 @@
+mod apu;
+mod audio;
 mod cpu;
+mod dma;
+mod gameboy;
+mod gbs;
+mod hdma;
+mod input;
+mod io;
+mod joypad;
+mod mbc;
 mod memory;
+mod movie;
+mod ppu;
 mod rom;
-pub use crate::memory::{bus_read, bus_write, Memory};
+mod sgb;
+mod state;
+mod timer;
+mod vgm;
+mod video;
+mod wav;
+pub use apu::noise::Noise;
+pub use apu::square::Square;
+pub use apu::wave::Wave;
+pub use apu::Apu;
+pub use audio::blip::{BlipBuffer, HighPass};
+#[cfg(feature = "sdl")]
+pub use audio::device::SdlAudio;
+pub use audio::device::{AudioDevice, NullAudio};
+pub use audio::export::{pcm_hash, stem_filename, WavRecorder, PCM_HASH_START};
+pub use audio::{AudioError, AudioOutput, RateControl, Resampler};
+pub use crate::memory::{bus_hblank, bus_peek, bus_read, bus_tick, bus_write, Memory};
 pub use cpu::*;
-pub use rom::Catridge;
+pub use dma::OamDma;
+pub use gameboy::GameBoy;
+pub use gbs::{Gbs, GbsError, GbsPlayer};
+pub use hdma::Hdma;
+pub use input::{Action, Device, Hotkey, Input, InputError, InputMap, InputState};
+pub use io::{Interrupt, Io, IoRegister, Lcdc, Nr52, Sc, Stat, Tac, P1};
+pub use joypad::{Buttons, Joypad};
+pub use mbc::{Mbc, Mbc6, RtcClock, Tama5};
+pub use movie::{rom_hash, Model, Movie, MovieError, MoviePlayer, MovieRecorder};
+pub use ppu::{Pixel, Ppu, PpuMode, Renderer, Sprite, DMG_GREYS, SCREEN_HEIGHT, SCREEN_WIDTH};
+pub use rom::{Catridge, CatridgeType, RomError};
+pub use sgb::border::{Border, BORDER_HEIGHT, BORDER_WIDTH};
+pub use sgb::{Sgb, SgbCommand, SgbMask};
+pub use state::{StateError, StateReader, StateWriter};
+pub use timer::Timer;
+pub use video::correction::ColorCorrection;
+pub use video::debug::{
+    oam_entries, oam_table, DebugImage, DebugPalette, BACKDROP_COLOR, VIEWPORT_COLOR,
+};
+pub use video::filter::{Ghosting, LcdGrid};
+pub use video::palette::{rgb_to_rgb555, CompatPalette, DmgPalette};
+pub use video::recorder::{RecordFormat, Recorder};
+pub use video::scaler::Scaler;
+pub use video::screenshot::{frame_hash, write_png};
+pub use video::terminal::{ansi256, TerminalColors, TerminalRenderer};
+pub use video::{VideoError, VideoOutput};
+pub use vgm::VgmWriter;
+pub use wav::WavWriter;
 
 pub fn get_bit(data: u8, pos: u8) -> u8 {
     (data >> pos) & 1
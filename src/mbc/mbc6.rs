@@ -0,0 +1,213 @@
+use crate::mbc::rom_read;
+use crate::rom::Catridge;
+use crate::state::{StateError, StateReader, StateWriter};
+
+// The MBC6 splits 0x4000-0x7FFF into two 8KB windows (A and B) that can each map a ROM bank
+// or a bank of the 1MB flash chip. RAM is split the same way into two 4KB windows
+pub struct Mbc6 {
+    ram_access: bool,
+    ram_bank_a: u8,
+    ram_bank_b: u8,
+    flash_access: bool,
+    flash_write: bool,
+    bank_a: u8,
+    bank_b: u8,
+    flash_a: bool,
+    flash_b: bool,
+    flash_state: FlashState,
+    pub flash: Vec<u8>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum FlashState {
+    Ready,
+    Unlock1,
+    Unlock2,
+    Identify,
+    Program,
+    Erase,
+    EraseUnlock1,
+    EraseUnlock2,
+}
+
+impl Mbc6 {
+    pub const RAM_SIZE: usize = 0x8000;
+    pub const FLASH_SIZE: usize = 0x100000;
+    pub const FLASH_SECTOR_SIZE: usize = 0x20000;
+    const MANUFACTURER_ID: u8 = 0xC2;
+    const DEVICE_ID: u8 = 0x81;
+
+    pub fn new() -> Mbc6 {
+        Mbc6 {
+            ram_access: false,
+            ram_bank_a: 0,
+            ram_bank_b: 0,
+            flash_access: false,
+            flash_write: false,
+            bank_a: 0,
+            bank_b: 0,
+            flash_a: false,
+            flash_b: false,
+            flash_state: FlashState::Ready,
+            flash: vec![0xFF; Self::FLASH_SIZE],
+        }
+    }
+
+    // Returns the bank and whether it is a flash bank for the window containing the address
+    fn window(&self, address: u16) -> (u8, bool) {
+        if address < 0x6000 {
+            (self.bank_a, self.flash_a)
+        } else {
+            (self.bank_b, self.flash_b)
+        }
+    }
+
+    fn flash_address(bank: u8, address: u16) -> usize {
+        ((bank as usize) << 13 | (address as usize & 0x1FFF)) % Self::FLASH_SIZE
+    }
+
+    fn ram_address(&self, address: u16) -> usize {
+        let bank = if address < 0xB000 {
+            self.ram_bank_a
+        } else {
+            self.ram_bank_b
+        };
+        ((bank as usize & 0x7) << 12 | (address as usize & 0xFFF)) % Self::RAM_SIZE
+    }
+
+    pub fn read(&self, catridge: &Catridge, address: u16) -> Option<u8> {
+        match address {
+            0x0000..=0x3FFF => Some(rom_read(catridge, address as usize)),
+            0x4000..=0x7FFF => {
+                let (bank, flash) = self.window(address);
+                if !flash {
+                    let offset = (bank as usize) << 13 | (address as usize & 0x1FFF);
+                    return Some(rom_read(catridge, offset));
+                }
+                if !self.flash_access {
+                    return Some(0xFF);
+                }
+                let flash_address = Self::flash_address(bank, address);
+                let value = match self.flash_state {
+                    FlashState::Identify => match flash_address & 0xFF {
+                        0x00 => Self::MANUFACTURER_ID,
+                        0x01 => Self::DEVICE_ID,
+                        _ => 0x00,
+                    },
+                    _ => self.flash[flash_address],
+                };
+                Some(value)
+            }
+            0xA000..=0xBFFF => {
+                if !self.ram_access {
+                    return Some(0xFF);
+                }
+                Some(catridge.ram[self.ram_address(address)])
+            }
+            _ => None,
+        }
+    }
+
+    pub fn write(&mut self, catridge: &mut Catridge, address: u16, data: u8) -> bool {
+        match address {
+            0x0000..=0x03FF => self.ram_access = data & 0xF == 0xA,
+            0x0400..=0x07FF => self.ram_bank_a = data,
+            0x0800..=0x0BFF => self.ram_bank_b = data,
+            0x0C00..=0x0FFF => self.flash_access = data & 1 == 1,
+            0x1000 => self.flash_write = data & 1 == 1,
+            0x1001..=0x1FFF => (),
+            0x2000..=0x27FF => self.bank_a = data & 0x7F,
+            0x2800..=0x2FFF => self.flash_a = data == 0x08,
+            0x3000..=0x37FF => self.bank_b = data & 0x7F,
+            0x3800..=0x3FFF => self.flash_b = data == 0x08,
+            0x4000..=0x7FFF => {
+                let (bank, flash) = self.window(address);
+                if flash && self.flash_access {
+                    self.flash_command(Self::flash_address(bank, address), data);
+                }
+            }
+            0xA000..=0xBFFF => {
+                if self.ram_access {
+                    let ram_address = self.ram_address(address);
+                    catridge.ram[ram_address] = data;
+                }
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    // ---------------------FLASH COMMANDS--------------------
+
+    // The flash chip follows the usual AA/55 unlock sequence at 0x5555 and 0x2AAA
+    fn flash_command(&mut self, address: usize, data: u8) {
+        let unlock_address = address & 0x7FFF;
+        self.flash_state = match (self.flash_state, unlock_address, data) {
+            (FlashState::Program, _, _) => {
+                // Programming can only clear bits, erasing is the only way to set them again.
+                // The byte is data here, even 0xF0
+                self.flash[address] &= data;
+                FlashState::Ready
+            }
+            (_, _, 0xF0) => FlashState::Ready,
+            (FlashState::Ready | FlashState::Identify, 0x5555, 0xAA) => FlashState::Unlock1,
+            (FlashState::Unlock1, 0x2AAA, 0x55) => FlashState::Unlock2,
+            (FlashState::Unlock2, 0x5555, 0x90) => FlashState::Identify,
+            (FlashState::Unlock2, 0x5555, 0xA0) if self.flash_write => FlashState::Program,
+            (FlashState::Unlock2, 0x5555, 0x80) if self.flash_write => FlashState::Erase,
+            (FlashState::Erase, 0x5555, 0xAA) => FlashState::EraseUnlock1,
+            (FlashState::EraseUnlock1, 0x2AAA, 0x55) => FlashState::EraseUnlock2,
+            (FlashState::EraseUnlock2, 0x5555, 0x10) => {
+                self.flash.fill(0xFF);
+                FlashState::Ready
+            }
+            (FlashState::EraseUnlock2, _, 0x30) => {
+                let start = address / Self::FLASH_SECTOR_SIZE * Self::FLASH_SECTOR_SIZE;
+                self.flash[start..start + Self::FLASH_SECTOR_SIZE].fill(0xFF);
+                FlashState::Ready
+            }
+            (FlashState::Identify, _, _) => FlashState::Identify,
+            _ => FlashState::Ready,
+        };
+    }
+
+    pub fn save_state(&self, state: &mut StateWriter) {
+        for flag in [self.ram_access, self.flash_access, self.flash_write] {
+            state.bool(flag);
+        }
+        state.bytes(&[self.ram_bank_a, self.ram_bank_b, self.bank_a, self.bank_b]);
+        state.bool(self.flash_a);
+        state.bool(self.flash_b);
+        state.u8(self.flash_state as u8);
+        state.vec(&self.flash);
+    }
+
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        self.ram_access = state.bool()?;
+        self.flash_access = state.bool()?;
+        self.flash_write = state.bool()?;
+        let mut banks = [0; 4];
+        state.bytes(&mut banks)?;
+        [self.ram_bank_a, self.ram_bank_b, self.bank_a, self.bank_b] = banks;
+        self.flash_a = state.bool()?;
+        self.flash_b = state.bool()?;
+        self.flash_state = match state.u8()? {
+            0 => FlashState::Ready,
+            1 => FlashState::Unlock1,
+            2 => FlashState::Unlock2,
+            3 => FlashState::Identify,
+            4 => FlashState::Program,
+            5 => FlashState::Erase,
+            6 => FlashState::EraseUnlock1,
+            7 => FlashState::EraseUnlock2,
+            _ => return Err(StateError::Data),
+        };
+        state.vec_into(&mut self.flash)
+    }
+}
+
+impl Default for Mbc6 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
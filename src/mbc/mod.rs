@@ -0,0 +1,166 @@
+pub mod mbc6;
+pub mod tama5;
+pub use mbc6::Mbc6;
+pub use tama5::{RtcClock, Tama5};
+
+use crate::rom::{Catridge, CatridgeType};
+use crate::state::{StateError, StateReader, StateWriter};
+
+// Mappers that take over the whole catridge address range. Everything else still goes
+// through the MBC1/MBC2 banking in memory.rs
+pub enum Mbc {
+    None,
+    // 32KB of ROM and optionally 8KB of RAM, no banking
+    RomOnly,
+    // A GBS rip loaded as ROM only, with the bank register the rips expect at
+    // 0x2000-0x3FFF switching 0x4000-0x7FFF
+    Gbs(usize),
+    Tama5(Tama5),
+    Mbc6(Mbc6),
+}
+
+impl Mbc {
+    pub fn new(catridge: &mut Catridge) -> Mbc {
+        if catridge.catridge_type.contains(&CatridgeType::Tama5) {
+            Mbc::Tama5(Tama5::new())
+        } else if catridge.catridge_type.contains(&CatridgeType::Mbc6) {
+            if catridge.ram.len() < Mbc6::RAM_SIZE {
+                catridge.ram.resize(Mbc6::RAM_SIZE, 0);
+            }
+            Mbc::Mbc6(Mbc6::new())
+        } else if catridge.catridge_type.first() == Some(&CatridgeType::Rom) {
+            Mbc::RomOnly
+        } else {
+            Mbc::None
+        }
+    }
+
+    // Returns None when the address is not handled by the mapper
+    pub fn read(&self, catridge: &Catridge, address: u16) -> Option<u8> {
+        match self {
+            Mbc::Tama5(tama5) => tama5.read(catridge, address),
+            Mbc::Mbc6(mbc6) => mbc6.read(catridge, address),
+            Mbc::RomOnly => match address {
+                0x0000..=0x7FFF => Some(rom_read(catridge, address as usize)),
+                0xA000..=0xBFFF => Some(
+                    catridge
+                        .ram
+                        .get(address as usize - 0xA000)
+                        .copied()
+                        .unwrap_or(0xFF),
+                ),
+                _ => None,
+            },
+            Mbc::Gbs(bank) => match address {
+                0x0000..=0x3FFF => Some(rom_read(catridge, address as usize)),
+                0x4000..=0x7FFF => Some(rom_read(
+                    catridge,
+                    bank * 0x4000 + address as usize - 0x4000,
+                )),
+                _ => Mbc::RomOnly.read(catridge, address),
+            },
+            Mbc::None => None,
+        }
+    }
+
+    // Returns false when the address is not handled by the mapper
+    pub fn write(&mut self, catridge: &mut Catridge, address: u16, data: u8) -> bool {
+        match self {
+            Mbc::Tama5(tama5) => tama5.write(address, data),
+            Mbc::Mbc6(mbc6) => mbc6.write(catridge, address, data),
+            Mbc::RomOnly => match address {
+                0x0000..=0x7FFF => true,
+                0xA000..=0xBFFF => {
+                    if let Some(value) = catridge.ram.get_mut(address as usize - 0xA000) {
+                        *value = data;
+                    }
+                    true
+                }
+                _ => false,
+            },
+            Mbc::Gbs(bank) => match address {
+                0x2000..=0x3FFF => {
+                    *bank = (data as usize).max(1);
+                    true
+                }
+                _ => Mbc::RomOnly.write(catridge, address, data),
+            },
+            Mbc::None => false,
+        }
+    }
+
+    // Lets the catridge clocks see the T-cycles go by
+    pub fn tick(&mut self, cycles: u32) {
+        if let Mbc::Tama5(tama5) = self {
+            tama5.rtc.tick(cycles);
+        }
+    }
+
+    pub fn set_rtc_clock(&mut self, clock: RtcClock) {
+        if let Mbc::Tama5(tama5) = self {
+            tama5.rtc.clock = clock;
+        }
+    }
+
+    // ---------------------SAVE STATES--------------------
+
+    // Which mapper it is comes from the catridge, only its registers are saved
+    pub fn save_state(&self, state: &mut StateWriter) {
+        match self {
+            Mbc::Tama5(tama5) => tama5.save_state(state),
+            Mbc::Mbc6(mbc6) => mbc6.save_state(state),
+            Mbc::Gbs(bank) => state.u32(*bank as u32),
+            Mbc::RomOnly | Mbc::None => (),
+        }
+    }
+
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        match self {
+            Mbc::Tama5(tama5) => tama5.load_state(state),
+            Mbc::Mbc6(mbc6) => mbc6.load_state(state),
+            Mbc::Gbs(bank) => {
+                *bank = state.u32()? as usize;
+                Ok(())
+            }
+            Mbc::RomOnly | Mbc::None => Ok(()),
+        }
+    }
+
+    // ---------------------SAVE DATA--------------------
+
+    pub fn save_data(&self, catridge: &Catridge) -> Vec<u8> {
+        match self {
+            Mbc::Tama5(tama5) => tama5.save_data(),
+            Mbc::Mbc6(mbc6) => {
+                let mut data = catridge.ram.clone();
+                data.extend_from_slice(&mbc6.flash);
+                data
+            }
+            Mbc::RomOnly | Mbc::Gbs(_) | Mbc::None => catridge.ram.clone(),
+        }
+    }
+
+    pub fn load_save_data(&mut self, catridge: &mut Catridge, data: &[u8]) {
+        match self {
+            Mbc::Tama5(tama5) => tama5.load_save_data(data),
+            Mbc::Mbc6(mbc6) => {
+                let (ram, flash) = data.split_at(data.len().min(Mbc6::RAM_SIZE));
+                Self::copy_into(&mut catridge.ram, ram);
+                Self::copy_into(&mut mbc6.flash, flash);
+            }
+            Mbc::RomOnly | Mbc::Gbs(_) | Mbc::None => Self::copy_into(&mut catridge.ram, data),
+        }
+    }
+
+    fn copy_into(to: &mut [u8], from: &[u8]) {
+        let len = to.len().min(from.len());
+        to[..len].copy_from_slice(&from[..len]);
+    }
+}
+
+pub fn rom_read(catridge: &Catridge, address: usize) -> u8 {
+    if catridge.data.is_empty() {
+        return 0xFF;
+    }
+    catridge.data[address % catridge.data.len()]
+}
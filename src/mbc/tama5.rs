@@ -0,0 +1,376 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::mbc::rom_read;
+use crate::rom::Catridge;
+use crate::state::{StateError, StateReader, StateWriter};
+
+// The TAMA5 is only reachable through 0xA000 (data) and 0xA001 (register select).
+// Every value is a nibble, so bytes have to be assembled from a low and a high register
+pub struct Tama5 {
+    register: u8,
+    registers: [u8; 8],
+    pub ram: [u8; Tama5::RAM_SIZE],
+    pub rtc: Rtc,
+}
+
+// Registers that can be selected through 0xA001
+const BANK_LO: u8 = 0x0;
+const BANK_HI: u8 = 0x1;
+const WRITE_LO: u8 = 0x4;
+const WRITE_HI: u8 = 0x5;
+const ADDR_HI: u8 = 0x6;
+const ADDR_LO: u8 = 0x7;
+const ACTIVE: u8 = 0xA;
+const READ_LO: u8 = 0xC;
+const READ_HI: u8 = 0xD;
+
+// Commands stored in bits 1-3 of ADDR_HI, they run when ADDR_LO is written
+const CMD_RAM_WRITE: u8 = 0x0;
+const CMD_RAM_READ: u8 = 0x1;
+const CMD_RTC_WRITE: u8 = 0x2;
+const CMD_RTC_READ: u8 = 0x3;
+const CMD_RTC_LATCH: u8 = 0x4;
+
+impl Tama5 {
+    pub const RAM_SIZE: usize = 32;
+    pub const SAVE_SIZE: usize = Self::RAM_SIZE + Rtc::SAVE_SIZE;
+
+    pub fn new() -> Tama5 {
+        Tama5 {
+            register: 0,
+            registers: [0; 8],
+            ram: [0; Self::RAM_SIZE],
+            rtc: Rtc::new(),
+        }
+    }
+
+    fn rom_bank(&self) -> usize {
+        (self.registers[BANK_LO as usize] | (self.registers[BANK_HI as usize] & 1) << 4) as usize
+    }
+
+    fn command(&self) -> u8 {
+        self.registers[ADDR_HI as usize] >> 1
+    }
+
+    fn address(&self) -> usize {
+        ((self.registers[ADDR_HI as usize] & 1) << 4 | self.registers[ADDR_LO as usize]) as usize
+    }
+
+    fn value(&self) -> u8 {
+        self.registers[WRITE_HI as usize] << 4 | self.registers[WRITE_LO as usize]
+    }
+
+    pub fn read(&self, catridge: &Catridge, address: u16) -> Option<u8> {
+        match address {
+            0x0000..=0x3FFF => Some(rom_read(catridge, address as usize)),
+            0x4000..=0x7FFF => {
+                let offset = (address - 0x4000) as usize;
+                Some(rom_read(catridge, self.rom_bank() * 0x4000 + offset))
+            }
+            0xA000..=0xBFFF if address & 1 == 1 => Some(0xFF),
+            0xA000..=0xBFFF => {
+                let value = match self.register {
+                    ACTIVE => return Some(0xF1),
+                    READ_LO | READ_HI => match self.command() {
+                        CMD_RAM_READ => self.ram[self.address()],
+                        CMD_RTC_READ => self.rtc.read(self.address() & 0xF),
+                        _ => 0,
+                    },
+                    _ => return Some(0xFF),
+                };
+                let value = if self.register == READ_HI {
+                    value >> 4
+                } else {
+                    value & 0xF
+                };
+                Some(0xF0 | value)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn write(&mut self, address: u16, data: u8) -> bool {
+        match address {
+            0x0000..=0x7FFF => true,
+            0xA000..=0xBFFF if address & 1 == 1 => {
+                self.register = data & 0xF;
+                true
+            }
+            0xA000..=0xBFFF => {
+                let data = data & 0xF;
+                if (self.register as usize) < self.registers.len() {
+                    self.registers[self.register as usize] = data;
+                }
+                if self.register == ADDR_LO {
+                    self.run_command();
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn run_command(&mut self) {
+        match self.command() {
+            CMD_RAM_WRITE => self.ram[self.address()] = self.value(),
+            CMD_RTC_WRITE => self.rtc.write(self.address() & 0xF, self.value() & 0xF),
+            CMD_RTC_LATCH => self.rtc.update(),
+            _ => (),
+        }
+    }
+
+    pub fn save_data(&self) -> Vec<u8> {
+        let mut data = self.ram.to_vec();
+        data.extend_from_slice(&self.rtc.save_data());
+        data
+    }
+
+    pub fn load_save_data(&mut self, data: &[u8]) {
+        if data.len() < Self::SAVE_SIZE {
+            return;
+        }
+        self.ram.copy_from_slice(&data[..Self::RAM_SIZE]);
+        self.rtc.load_save_data(&data[Self::RAM_SIZE..]);
+    }
+
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.u8(self.register);
+        state.bytes(&self.registers);
+        state.bytes(&self.ram);
+        self.rtc.save_state(state);
+    }
+
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        self.register = state.u8()?;
+        state.bytes(&mut self.registers)?;
+        state.bytes(&mut self.ram)?;
+        self.rtc.load_state(state)
+    }
+}
+
+impl Default for Tama5 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// ---------------------REAL TIME CLOCK--------------------
+
+// Where the clock takes the time from
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RtcClock {
+    // The host clock, the time also goes on while the emulator is closed
+    Host,
+    // The T-cycles the console ran, the same inputs always see the same time
+    Emulated,
+}
+
+// T-cycles in a second of emulated time
+const CYCLES_PER_SECOND: u64 = 4194304;
+
+// The clock is kept in binary and only split into BCD nibbles when the game reads it.
+// It advances by the time that passed on its clock since it was last updated
+pub struct Rtc {
+    pub seconds: u8,
+    pub minutes: u8,
+    pub hours: u8,
+    pub day_of_week: u8,
+    pub day: u8,
+    pub month: u8,
+    pub year: u8,
+    pub clock: RtcClock,
+    // Host time of the last update, in seconds since the epoch
+    last_update: u64,
+    // Emulated T-cycles not yet counted as a second
+    cycles: u64,
+}
+
+impl Rtc {
+    pub const SAVE_SIZE: usize = 7 + 8;
+
+    pub fn new() -> Rtc {
+        Rtc {
+            seconds: 0,
+            minutes: 0,
+            hours: 0,
+            day_of_week: 0,
+            day: 1,
+            month: 1,
+            year: 0,
+            clock: RtcClock::Host,
+            last_update: Self::now(),
+            cycles: 0,
+        }
+    }
+
+    fn now() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|v| v.as_secs())
+            .unwrap_or(0)
+    }
+
+    fn days_in_month(&self) -> u8 {
+        match self.month {
+            2 if self.year.is_multiple_of(4) => 29,
+            2 => 28,
+            4 | 6 | 9 | 11 => 30,
+            _ => 31,
+        }
+    }
+
+    pub fn update(&mut self) {
+        match self.clock {
+            RtcClock::Host => {
+                let now = Self::now();
+                let elapsed = now.saturating_sub(self.last_update);
+                self.last_update = now;
+                self.advance(elapsed);
+            }
+            RtcClock::Emulated => {
+                self.advance(self.cycles / CYCLES_PER_SECOND);
+                self.cycles %= CYCLES_PER_SECOND;
+            }
+        }
+    }
+
+    // Counts T-cycles run by the console, only the emulated clock goes by them
+    pub fn tick(&mut self, cycles: u32) {
+        if self.clock == RtcClock::Emulated {
+            self.cycles += cycles as u64;
+        }
+    }
+
+    pub fn advance(&mut self, seconds: u64) {
+        let total = self.seconds as u64 + seconds;
+        self.seconds = (total % 60) as u8;
+        let total = self.minutes as u64 + total / 60;
+        self.minutes = (total % 60) as u8;
+        let total = self.hours as u64 + total / 60;
+        self.hours = (total % 24) as u8;
+        for _ in 0..total / 24 {
+            self.day_of_week = (self.day_of_week + 1) % 7;
+            self.day += 1;
+            if self.day > self.days_in_month() {
+                self.day = 1;
+                self.month += 1;
+                if self.month > 12 {
+                    self.month = 1;
+                    self.year = (self.year + 1) % 100;
+                }
+            }
+        }
+    }
+
+    // Registers 0x0-0xC hold the BCD digits: seconds, minutes, hours, day of week, day, month, year
+    pub fn read(&self, register: usize) -> u8 {
+        match register {
+            0x0 => self.seconds % 10,
+            0x1 => self.seconds / 10,
+            0x2 => self.minutes % 10,
+            0x3 => self.minutes / 10,
+            0x4 => self.hours % 10,
+            0x5 => self.hours / 10,
+            0x6 => self.day_of_week,
+            0x7 => self.day % 10,
+            0x8 => self.day / 10,
+            0x9 => self.month % 10,
+            0xA => self.month / 10,
+            0xB => self.year % 10,
+            0xC => self.year / 10,
+            _ => 0,
+        }
+    }
+
+    pub fn write(&mut self, register: usize, data: u8) {
+        fn set_digit(value: &mut u8, data: u8, tens: bool) {
+            *value = if tens {
+                data * 10 + *value % 10
+            } else {
+                *value / 10 * 10 + data
+            };
+        }
+        let data = data.min(9);
+        match register {
+            0x0 | 0x1 => set_digit(&mut self.seconds, data, register == 0x1),
+            0x2 | 0x3 => set_digit(&mut self.minutes, data, register == 0x3),
+            0x4 | 0x5 => set_digit(&mut self.hours, data, register == 0x5),
+            0x6 => self.day_of_week = data % 7,
+            0x7 | 0x8 => set_digit(&mut self.day, data, register == 0x8),
+            0x9 | 0xA => set_digit(&mut self.month, data, register == 0xA),
+            0xB | 0xC => set_digit(&mut self.year, data, register == 0xC),
+            _ => (),
+        }
+        self.seconds %= 60;
+        self.minutes %= 60;
+        self.hours %= 24;
+        self.month = self.month.clamp(1, 12);
+        self.day = self.day.clamp(1, 31);
+        self.year %= 100;
+    }
+
+    pub fn save_data(&self) -> Vec<u8> {
+        let mut data = vec![
+            self.seconds,
+            self.minutes,
+            self.hours,
+            self.day_of_week,
+            self.day,
+            self.month,
+            self.year,
+        ];
+        data.extend_from_slice(&self.last_update.to_le_bytes());
+        data
+    }
+
+    pub fn load_save_data(&mut self, data: &[u8]) {
+        if data.len() < Self::SAVE_SIZE {
+            return;
+        }
+        [
+            self.seconds,
+            self.minutes,
+            self.hours,
+            self.day_of_week,
+            self.day,
+            self.month,
+            self.year,
+        ] = [
+            data[0], data[1], data[2], data[3], data[4], data[5], data[6],
+        ];
+        let mut timestamp = [0u8; 8];
+        timestamp.copy_from_slice(&data[7..15]);
+        self.last_update = u64::from_le_bytes(timestamp);
+        self.update();
+    }
+
+    // The time and where the clock counts from, the clock source stays as it is
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.bytes(&self.save_data()[..7]);
+        state.u64(self.last_update);
+        state.u64(self.cycles);
+    }
+
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        let mut time = [0; 7];
+        state.bytes(&mut time)?;
+        [
+            self.seconds,
+            self.minutes,
+            self.hours,
+            self.day_of_week,
+            self.day,
+            self.month,
+            self.year,
+        ] = time;
+        self.last_update = state.u64()?;
+        self.cycles = state.u64()?;
+        Ok(())
+    }
+}
+
+impl Default for Rtc {
+    fn default() -> Self {
+        Self::new()
+    }
+}
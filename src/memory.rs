@@ -1,6 +1,19 @@
 use rand::Rng;
 
-use crate::{get_bit, rom::Catridge};
+use crate::{
+    apu::Apu,
+    dma::OamDma,
+    get_bit,
+    hdma::Hdma,
+    io::{Io, IoRegister},
+    joypad::Joypad,
+    mbc::{Mbc, RtcClock},
+    ppu::{Ppu, PpuMode},
+    rom::{Catridge, RomError},
+    sgb::Sgb,
+    state::{StateError, StateReader, StateWriter},
+    timer::Timer,
+};
 
 pub enum RomMode {
     Simple,
@@ -13,9 +26,55 @@ pub struct Memory {
     ram_access: bool,
     rom_mode: RomMode,
     catridge: Catridge,
+    mbc: Mbc,
+    pub oam_dma: OamDma,
+    pub vram: [[u8; Self::VRAM_BANK_SIZE]; 2],
+    pub wram: [[u8; Self::WRAM_BANK_SIZE]; 8],
+    vram_bank: usize,
+    wram_bank: usize,
+    pub cgb_mode: bool,
+    pub hdma: Hdma,
+    stall: u32,
+    pub io: Io,
+    pub timer: Timer,
+    pub joypad: Joypad,
+    pub apu: Apu,
+    // (cycle, address, data) of every APU register write while logging, for VGM
+    pub apu_log: Option<Vec<(u64, u16, u8)>>,
+    pub ppu: Ppu,
+    // T-cycles since power on
+    pub cycles: u64,
 }
 
 pub fn bus_read(memory: &Memory, address: u16) -> Option<u8> {
+    // The CPU only keeps access to the IO registers and HRAM while OAM DMA is running
+    if memory.oam_dma.is_active() {
+        match address {
+            0xFE00..=0xFE9F => return Some(0xFF),
+            0x0000..=0xFDFF => return Some(memory.oam_dma.bus_value),
+            _ => (),
+        }
+    }
+    match address {
+        0x8000..=0x9FFF if memory.ppu.vram_blocked() => return Some(0xFF),
+        0xFE00..=0xFE9F if memory.ppu.oam_blocked() => return Some(0xFF),
+        // Only the CPU reading P1 counts as the game looking at the buttons
+        0xFF00 => memory.joypad.poll(),
+        _ => (),
+    }
+    mapped_read(memory, address)
+}
+
+// What's at `address` for debuggers and frontends, without the DMA or PPU blocking it
+// and without counting as a read of P1
+pub fn bus_peek(memory: &Memory, address: u16) -> Option<u8> {
+    mapped_read(memory, address)
+}
+
+fn mapped_read(memory: &Memory, address: u16) -> Option<u8> {
+    if let Some(value) = memory.mbc.read(&memory.catridge, address) {
+        return Some(value);
+    }
     match address {
         0x0..=0x3FFF => match memory.rom_mode {
             RomMode::Advanced => {
@@ -39,10 +98,47 @@ pub fn bus_read(memory: &Memory, address: u16) -> Option<u8> {
             }
             _ => Some(0),
         },
+        0x8000..=0x9FFF => Some(memory.vram[memory.vram_bank][(address - 0x8000) as usize]),
+        0xC000..=0xFDFF => {
+            let (bank, offset) = wram_address(memory, address);
+            Some(memory.wram[bank][offset])
+        }
+        0xFF4F if memory.cgb_mode => Some(0xFE | memory.vram_bank as u8),
+        0xFF51..=0xFF55 if memory.cgb_mode => Some(memory.hdma.read(address)),
+        0xFF70 if memory.cgb_mode => Some(0xF8 | memory.wram_bank as u8),
+        0xFF68..=0xFF6B if memory.cgb_mode => {
+            let blocked = memory.ppu.vram_blocked();
+            Some(memory.ppu.palettes.read(address, blocked))
+        }
+        0xFF04..=0xFF07 => {
+            let mask = IoRegister::get(address, memory.cgb_mode).read_mask();
+            Some(memory.timer.read(address) | mask)
+        }
+        0xFF10..=0xFF3F => Some(memory.apu.read(&memory.io, address, memory.cgb_mode)),
+        0xFF00..=0xFF7F => match &memory.ppu.sgb {
+            // With no button group selected the SGB puts the current controller id on P1
+            Some(sgb) if address == 0xFF00 && memory.io.get(0xFF00) & 0x30 == 0x30 => {
+                Some(0xF0 | sgb.joypad_id())
+            }
+            _ => Some(memory.io.read(address, memory.cgb_mode)),
+        },
         _ => Some(memory.data[address as usize]),
     }
 }
 
+// 0xC000-0xCFFF is always bank 0, 0xD000-0xDFFF is switchable on the CGB and 0xE000-0xFDFF echoes both
+fn wram_address(memory: &Memory, address: u16) -> (usize, usize) {
+    let address = if address >= 0xE000 {
+        address - 0x2000
+    } else {
+        address
+    };
+    match address {
+        0xC000..=0xCFFF => (0, (address - 0xC000) as usize),
+        _ => (memory.wram_bank.max(1), (address - 0xD000) as usize),
+    }
+}
+
 pub fn bus_read_16bit_value(memory: &Memory, addr: u16) -> Option<u16> {
     let value =
         (bus_read(memory, addr.wrapping_add(1))? as u16) << 8 | bus_read(memory, addr)? as u16;
@@ -50,6 +146,17 @@ pub fn bus_read_16bit_value(memory: &Memory, addr: u16) -> Option<u16> {
 }
 
 pub fn bus_write(memory: &mut Memory, address: u16, data: u8) {
+    if memory.oam_dma.is_active() && address < 0xFF00 {
+        return;
+    }
+    match address {
+        0x8000..=0x9FFF if memory.ppu.vram_blocked() => return,
+        0xFE00..=0xFE9F if memory.ppu.oam_blocked() => return,
+        _ => (),
+    }
+    if memory.mbc.write(&mut memory.catridge, address, data) {
+        return;
+    }
     match address {
         0x0..=0x1FFF => {
             if data & 0xF == 0b1010 {
@@ -97,16 +204,99 @@ pub fn bus_write(memory: &mut Memory, address: u16, data: u8) {
                 let effective_address = address & 0xFF;
                 memory.catridge.ram[effective_address as usize] = data & 0xF;
             } else {
-                memory.data[address as usize] = data; 
+                memory.data[address as usize] = data;
             }
         }
 
+        0x8000..=0x9FFF => memory.vram[memory.vram_bank][(address - 0x8000) as usize] = data,
+        0xC000..=0xFDFF => {
+            let (bank, offset) = wram_address(memory, address);
+            memory.wram[bank][offset] = data;
+        }
+        0xFF46 => {
+            memory.io.write(address, data, memory.cgb_mode);
+            memory.oam_dma.start(data);
+        }
+        0xFF4F if memory.cgb_mode => memory.vram_bank = (data & 1) as usize,
+        0xFF51..=0xFF55 if memory.cgb_mode => {
+            let blocks = memory.hdma.write(address, data);
+            hdma_copy(memory, blocks);
+        }
+        0xFF70 if memory.cgb_mode => memory.wram_bank = (data & 0x7) as usize,
+        0xFF68..=0xFF6B if memory.cgb_mode => {
+            let blocked = memory.ppu.vram_blocked();
+            memory.ppu.palettes.write(address, data, blocked);
+        }
+        0xFF04..=0xFF07 => memory.timer.write(address, data),
+        0xFF10..=0xFF3F => {
+            if let Some(log) = &mut memory.apu_log {
+                log.push((memory.cycles, address, data));
+            }
+            memory
+                .apu
+                .write(&mut memory.io, address, data, memory.cgb_mode);
+        }
+        0xFF00..=0xFF7F => {
+            memory.io.write(address, data, memory.cgb_mode);
+            if address == 0xFF00 {
+                let lcdc = memory.io.lcdc();
+                if let Some(sgb) = memory.ppu.sgb.as_mut() {
+                    sgb.write_p1(data, &memory.vram, lcdc);
+                    memory
+                        .joypad
+                        .set_player(sgb.current_player() as usize, &mut memory.io);
+                } else {
+                    memory.joypad.update(&mut memory.io);
+                }
+            }
+        }
         _ => memory.data[address as usize] = data,
     }
 }
 
+fn hdma_copy(memory: &mut Memory, blocks: u8) {
+    for _ in 0..blocks {
+        let (source, destination) = memory.hdma.next_block();
+        for i in 0..Hdma::BLOCK_SIZE {
+            let value = mapped_read(memory, source.wrapping_add(i)).unwrap_or(0xFF);
+            let offset = (destination + i - 0x8000) as usize;
+            memory.vram[memory.vram_bank][offset] = value;
+        }
+        memory.stall += Hdma::BLOCK_CYCLES;
+    }
+}
+
+// Called by the PPU every time it enters H-blank on a visible line
+pub fn bus_hblank(memory: &mut Memory) {
+    if memory.cgb_mode && memory.hdma.is_active() {
+        hdma_copy(memory, 1);
+    }
+}
+
+// Advances every peripheral on the bus by the given amount of T-cycles
+pub fn bus_tick(memory: &mut Memory, cycles: u32) {
+    memory.cycles += (cycles & !0x3) as u64;
+    memory.mbc.tick(cycles & !0x3);
+    for _ in 0..cycles / 4 {
+        memory.timer.step(&mut memory.io);
+        memory.apu.step(&mut memory.io, memory.timer.counter);
+        if let Some((source, destination)) = memory.oam_dma.step() {
+            let value = mapped_read(memory, source).unwrap_or(0xFF);
+            memory.data[destination as usize] = value;
+            memory.oam_dma.bus_value = value;
+        }
+        let oam = &memory.data[0xFE00..0xFEA0];
+        let cgb = memory.cgb_mode;
+        if memory.ppu.step(&mut memory.io, &memory.vram, oam, cgb) == Some(PpuMode::HBlank) {
+            bus_hblank(memory);
+        }
+    }
+}
+
 impl Memory {
     pub const MEMORY_SIZE: usize = 65536;
+    pub const VRAM_BANK_SIZE: usize = 0x2000;
+    pub const WRAM_BANK_SIZE: usize = 0x1000;
 
     pub fn new() -> Memory {
         let rom = Catridge::new_empty();
@@ -117,24 +307,170 @@ impl Memory {
             ram_bank_number: 0,
             rom_bank_number: 0,
             ram_access: false,
+            mbc: Mbc::None,
+            oam_dma: OamDma::new(),
+            vram: [[0; Self::VRAM_BANK_SIZE]; 2],
+            wram: [[0; Self::WRAM_BANK_SIZE]; 8],
+            vram_bank: 0,
+            wram_bank: 1,
+            cgb_mode: false,
+            hdma: Hdma::new(),
+            stall: 0,
+            io: Io::new(),
+            timer: Timer::new(),
+            joypad: Joypad::new(),
+            apu: Apu::new(),
+            apu_log: None,
+            ppu: Ppu::new(),
+            cycles: 0,
         }
     }
 
+    // Random bytes in every array behind the address space: the flat memory, both VRAM
+    // banks, all WRAM banks and the IO registers (through their write masks)
     pub fn new_random_values() -> Memory {
-        let mut data = [0u8; Self::MEMORY_SIZE];
+        let mut memory = Memory::new();
+        memory.rom_bank_number = 1;
         let mut thread_rng = rand::thread_rng();
-        for i in 0..Self::MEMORY_SIZE {
-            data[i] = thread_rng.gen::<u8>();
+        thread_rng.fill(&mut memory.data[..]);
+        for bank in memory.vram.iter_mut() {
+            thread_rng.fill(&mut bank[..]);
         }
-        let rom = Catridge::new_empty();
-        Memory {
-            data,
-            catridge: rom,
-            rom_mode: RomMode::Simple,
-            rom_bank_number: 1,
-            ram_bank_number: 0,
-            ram_access: false,
+        for bank in memory.wram.iter_mut() {
+            thread_rng.fill(&mut bank[..]);
+        }
+        for address in 0xFF00..=0xFF7F {
+            memory.io.write(address, thread_rng.gen(), false);
+        }
+        memory
+    }
+
+    pub fn load_catridge(&mut self, mut catridge: Catridge) {
+        self.mbc = Mbc::new(&mut catridge);
+        self.cgb_mode = catridge.cgb;
+        self.catridge = catridge;
+        self.rom_mode = RomMode::Simple;
+        self.rom_bank_number = 1;
+        self.ram_bank_number = 0;
+        self.ram_access = false;
+    }
+
+    // Loads the image of a GBS rip, see Mbc::Gbs
+    pub fn load_gbs(&mut self, catridge: Catridge) {
+        self.load_catridge(catridge);
+        self.mbc = Mbc::Gbs(1);
+    }
+
+    // Runs the game as on a Super Game Boy: DMG mode with the SGB colouring and border
+    pub fn enable_sgb(&mut self) {
+        self.cgb_mode = false;
+        self.ppu.sgb = Some(Box::new(Sgb::new()));
+    }
+
+    // Cycles the CPU has to sit out because a DMA took over the bus
+    pub fn take_stall(&mut self) -> u32 {
+        std::mem::take(&mut self.stall)
+    }
+
+    pub fn catridge(&self) -> &Catridge {
+        &self.catridge
+    }
+
+    pub fn mbc(&self) -> &Mbc {
+        &self.mbc
+    }
+
+    // Sets where the clock of the catridge, if it has one, takes the time from
+    pub fn set_rtc_clock(&mut self, clock: RtcClock) {
+        self.mbc.set_rtc_clock(clock);
+    }
+
+    // ---------------------SAVE STATES--------------------
+
+    // Everything on the bus except the ROM, which has to be the same catridge
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.bytes(&self.data);
+        state.u16(self.rom_bank_number);
+        state.u16(self.ram_bank_number);
+        state.bool(self.ram_access);
+        state.bool(matches!(self.rom_mode, RomMode::Advanced));
+        state.vec(&self.catridge.ram);
+        self.mbc.save_state(state);
+        self.oam_dma.save_state(state);
+        for bank in &self.vram {
+            state.bytes(bank);
+        }
+        for bank in &self.wram {
+            state.bytes(bank);
         }
+        state.u8(self.vram_bank as u8);
+        state.u8(self.wram_bank as u8);
+        state.bool(self.cgb_mode);
+        self.hdma.save_state(state);
+        state.u32(self.stall);
+        self.io.save_state(state);
+        self.timer.save_state(state);
+        self.joypad.save_state(state);
+        self.apu.save_state(state);
+        self.ppu.save_state(state);
+        state.u64(self.cycles);
+    }
+
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        state.bytes(&mut self.data)?;
+        self.rom_bank_number = state.u16()?;
+        self.ram_bank_number = state.u16()?;
+        self.ram_access = state.bool()?;
+        self.rom_mode = if state.bool()? {
+            RomMode::Advanced
+        } else {
+            RomMode::Simple
+        };
+        state.vec_into(&mut self.catridge.ram)?;
+        self.mbc.load_state(state)?;
+        self.oam_dma.load_state(state)?;
+        for bank in &mut self.vram {
+            state.bytes(bank)?;
+        }
+        for bank in &mut self.wram {
+            state.bytes(bank)?;
+        }
+        self.vram_bank = state.u8()? as usize;
+        self.wram_bank = state.u8()? as usize;
+        if self.vram_bank > 1 || self.wram_bank > 7 {
+            return Err(StateError::Data);
+        }
+        self.cgb_mode = state.bool()?;
+        self.hdma.load_state(state)?;
+        self.stall = state.u32()?;
+        self.io.load_state(state)?;
+        self.timer.load_state(state)?;
+        self.joypad.load_state(state)?;
+        self.apu.load_state(state)?;
+        self.ppu.load_state(state)?;
+        self.cycles = state.u64()?;
+        Ok(())
+    }
+
+    // ---------------------SAVE DATA--------------------
+
+    pub fn save_ram(&self, filename: &str) -> Result<(), RomError> {
+        std::fs::write(filename, self.save_data()).map_err(|_| RomError::Save)
+    }
+
+    pub fn load_ram(&mut self, filename: &str) -> Result<(), RomError> {
+        let data = std::fs::read(filename).map_err(|_| RomError::Load)?;
+        self.load_save_data(&data);
+        Ok(())
+    }
+
+    // What a .sav file holds: the battery backed RAM and the clock of the catridge
+    pub fn save_data(&self) -> Vec<u8> {
+        self.mbc.save_data(&self.catridge)
+    }
+
+    pub fn load_save_data(&mut self, data: &[u8]) {
+        self.mbc.load_save_data(&mut self.catridge, data);
     }
 
     pub fn load_section(&mut self, start: usize, data: &[u8]) {
@@ -143,11 +479,11 @@ impl Memory {
         }
     }
 
-    pub fn set_random_number_at_addr(&mut self, data: u16) {
-        let mut thread_rng = rand::thread_rng();
-
-        let random_value = thread_rng.gen::<u8>();
-        self.data[data as usize] = random_value;
+    // Writes through the bus like the CPU, so the value lands in whichever array backs
+    // the address
+    pub fn set_random_number_at_addr(&mut self, address: u16) {
+        let random_value = rand::thread_rng().gen::<u8>();
+        bus_write(self, address, random_value);
     }
 
     pub fn check(&mut self, addr: u16, data: u8) -> Option<()> {
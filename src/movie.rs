@@ -0,0 +1,295 @@
+use crate::gameboy::GameBoy;
+use crate::joypad::Buttons;
+use crate::mbc::RtcClock;
+use crate::rom::Catridge;
+
+const MAGIC: &[u8; 4] = b"BBMV";
+const VERSION: u8 = 2;
+const HEADER_SIZE: usize = 0x20;
+// Frames between two frame hashes, unless the movie says otherwise
+const HASH_INTERVAL: u16 = 60;
+
+#[derive(Debug, PartialEq)]
+pub enum MovieError {
+    Load,
+    Save,
+    Header,
+    // The ROM isn't the one the movie was made with
+    Rom,
+    // The catridge can't run on the model of the movie
+    Model,
+    // A frame hash didn't match the recording
+    Desync,
+    // The save state the movie starts from doesn't load
+    State,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Model {
+    Dmg,
+    Cgb,
+    Sgb,
+}
+
+impl Model {
+    pub fn of(gameboy: &GameBoy) -> Model {
+        if gameboy.memory.ppu.sgb.is_some() {
+            Model::Sgb
+        } else if gameboy.memory.cgb_mode {
+            Model::Cgb
+        } else {
+            Model::Dmg
+        }
+    }
+}
+
+// FNV-1a of the whole ROM, like the frame and PCM hashes
+pub fn rom_hash(data: &[u8]) -> u64 {
+    data.iter().fold(0xCBF29CE484222325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001B3)
+    })
+}
+
+// The buttons of every frame, plus what's needed to get the same console to start
+// from: the model, the ROM and either the battery save it had at power on or a save
+// state to start from. Catridge clocks run on emulated time, so the host clock can't
+// change a playback. Every `hash_interval` frames the frame hash is kept to notice
+// when a playback went another way.
+//
+// The file is a 0x20 byte header, "BBMV", version, model, hash interval (u16), ROM
+// hash (u64), frames (u32), save size (u32), hashes (u32), state size (u32), then the
+// save data, the state, one byte of buttons per frame and the hashes as u64. All
+// little endian. Version 1 had no state and zeros where its size goes
+pub struct Movie {
+    pub model: Model,
+    pub rom_hash: u64,
+    // A .sav image, empty to start with whatever the catridge had at power on
+    pub save_data: Vec<u8>,
+    // A save state (GameBoy::state_data) to start from, empty to start at power on
+    pub state: Vec<u8>,
+    pub hash_interval: u16,
+    pub frames: Vec<Buttons>,
+    // Frame hash after every `hash_interval` frames
+    pub hashes: Vec<u64>,
+}
+
+impl Movie {
+    // An empty movie of the catridge, the save data is loaded before power on
+    pub fn new(catridge: &Catridge, model: Model, save_data: Vec<u8>) -> Movie {
+        Movie {
+            model,
+            rom_hash: rom_hash(&catridge.data),
+            save_data,
+            state: Vec::new(),
+            hash_interval: HASH_INTERVAL,
+            frames: Vec::new(),
+            hashes: Vec::new(),
+        }
+    }
+
+    // An empty movie that starts from the console as it is now
+    pub fn from_state(gameboy: &GameBoy) -> Movie {
+        Movie {
+            state: gameboy.state_data(),
+            ..Self::new(gameboy.memory.catridge(), Model::of(gameboy), Vec::new())
+        }
+    }
+
+    pub fn load(filename: &str) -> Result<Movie, MovieError> {
+        let data = std::fs::read(filename).map_err(|_| MovieError::Load)?;
+        Self::from_bytes(&data)
+    }
+
+    pub fn save(&self, filename: &str) -> Result<(), MovieError> {
+        std::fs::write(filename, self.to_bytes()).map_err(|_| MovieError::Save)
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Movie, MovieError> {
+        if data.len() < HEADER_SIZE || &data[0..4] != MAGIC || !(1..=VERSION).contains(&data[4]) {
+            return Err(MovieError::Header);
+        }
+        let word = |offset: usize| u16::from_le_bytes([data[offset], data[offset + 1]]);
+        let long = |offset: usize| {
+            u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap()) as usize
+        };
+        let model = match data[5] {
+            0 => Model::Dmg,
+            1 => Model::Cgb,
+            2 => Model::Sgb,
+            _ => return Err(MovieError::Header),
+        };
+        let (frames, save_size, hashes) = (long(0x10), long(0x14), long(0x18));
+        let state_start = HEADER_SIZE + save_size;
+        let frames_start = state_start + long(0x1C);
+        let hashes_start = frames_start + frames;
+        if word(6) == 0 || data.len() != hashes_start + hashes * 8 {
+            return Err(MovieError::Header);
+        }
+        Ok(Movie {
+            model,
+            rom_hash: u64::from_le_bytes(data[8..16].try_into().unwrap()),
+            save_data: data[HEADER_SIZE..state_start].to_vec(),
+            state: data[state_start..frames_start].to_vec(),
+            hash_interval: word(6),
+            frames: data[frames_start..hashes_start]
+                .iter()
+                .map(|&buttons| Buttons(buttons))
+                .collect(),
+            hashes: data[hashes_start..]
+                .chunks_exact(8)
+                .map(|hash| u64::from_le_bytes(hash.try_into().unwrap()))
+                .collect(),
+        })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(HEADER_SIZE + self.save_data.len() + self.state.len());
+        data.extend_from_slice(MAGIC);
+        data.push(VERSION);
+        data.push(self.model as u8);
+        data.extend_from_slice(&self.hash_interval.to_le_bytes());
+        data.extend_from_slice(&self.rom_hash.to_le_bytes());
+        let lengths = [
+            self.frames.len(),
+            self.save_data.len(),
+            self.hashes.len(),
+            self.state.len(),
+        ];
+        for length in lengths {
+            data.extend_from_slice(&(length as u32).to_le_bytes());
+        }
+        data.resize(HEADER_SIZE, 0);
+        data.extend_from_slice(&self.save_data);
+        data.extend_from_slice(&self.state);
+        data.extend(self.frames.iter().map(|buttons| buttons.0));
+        for hash in &self.hashes {
+            data.extend_from_slice(&hash.to_le_bytes());
+        }
+        data
+    }
+
+    // The console the movie starts on, with the catridge it was made with
+    pub fn boot(&self, catridge: Catridge) -> Result<GameBoy, MovieError> {
+        if rom_hash(&catridge.data) != self.rom_hash {
+            return Err(MovieError::Rom);
+        }
+        if self.model == Model::Cgb && !catridge.cgb {
+            return Err(MovieError::Model);
+        }
+        let mut gameboy = GameBoy::new();
+        gameboy.memory.load_catridge(catridge);
+        gameboy.memory.set_rtc_clock(RtcClock::Emulated);
+        match self.model {
+            Model::Dmg => gameboy.memory.cgb_mode = false,
+            Model::Cgb => (),
+            Model::Sgb => gameboy.memory.enable_sgb(),
+        }
+        if !self.save_data.is_empty() {
+            gameboy.memory.load_save_data(&self.save_data);
+        }
+        gameboy.skip_boot();
+        if !self.state.is_empty() {
+            gameboy
+                .load_state_data(&self.state)
+                .map_err(|_| MovieError::State)?;
+            if Model::of(&gameboy) != self.model {
+                return Err(MovieError::Model);
+            }
+        }
+        Ok(gameboy)
+    }
+}
+
+// Runs a frame with the buttons held, returns whether it was a lag frame, one where the
+// game never read P1
+fn run_frame(gameboy: &mut GameBoy, buttons: Buttons) -> bool {
+    gameboy.set_buttons(buttons);
+    gameboy.memory.joypad.take_polled();
+    gameboy.run_frame();
+    !gameboy.memory.joypad.take_polled()
+}
+
+// Adds the frames played on a console from Movie::boot to the movie
+pub struct MovieRecorder {
+    pub movie: Movie,
+    pub lag_frames: u64,
+}
+
+impl MovieRecorder {
+    pub fn new(movie: Movie) -> MovieRecorder {
+        MovieRecorder {
+            movie,
+            lag_frames: 0,
+        }
+    }
+
+    // Runs and records one frame, returns whether it was a lag frame
+    pub fn run_frame(&mut self, gameboy: &mut GameBoy, buttons: Buttons) -> bool {
+        let lag = run_frame(gameboy, buttons);
+        self.lag_frames += lag as u64;
+        let movie = &mut self.movie;
+        movie.frames.push(buttons);
+        if movie
+            .frames
+            .len()
+            .is_multiple_of(movie.hash_interval as usize)
+        {
+            movie.hashes.push(gameboy.frame_hash());
+        }
+        lag
+    }
+}
+
+// Plays a movie back on a console from Movie::boot, frame by frame
+pub struct MoviePlayer {
+    pub movie: Movie,
+    frame: usize,
+    pub lag_frames: u64,
+}
+
+impl MoviePlayer {
+    pub fn new(movie: Movie) -> MoviePlayer {
+        MoviePlayer {
+            movie,
+            frame: 0,
+            lag_frames: 0,
+        }
+    }
+
+    // Frames played so far
+    pub fn frame(&self) -> usize {
+        self.frame
+    }
+
+    pub fn finished(&self) -> bool {
+        self.frame >= self.movie.frames.len()
+    }
+
+    // Runs the next frame of the movie, returns whether it was a lag frame. A frame
+    // hash that differs from the recording is a desync, found at most `hash_interval`
+    // frames after it happened
+    pub fn run_frame(&mut self, gameboy: &mut GameBoy) -> Result<bool, MovieError> {
+        let Some(&buttons) = self.movie.frames.get(self.frame) else {
+            return Ok(false);
+        };
+        let lag = run_frame(gameboy, buttons);
+        self.lag_frames += lag as u64;
+        self.frame += 1;
+        if self.frame.is_multiple_of(self.movie.hash_interval as usize) {
+            let index = self.frame / self.movie.hash_interval as usize - 1;
+            let expected = self.movie.hashes.get(index);
+            if expected.is_some_and(|&hash| hash != gameboy.frame_hash()) {
+                return Err(MovieError::Desync);
+            }
+        }
+        Ok(lag)
+    }
+
+    // Plays the rest of the movie, stops at the first desync
+    pub fn run(&mut self, gameboy: &mut GameBoy) -> Result<(), MovieError> {
+        while !self.finished() {
+            self.run_frame(gameboy)?;
+        }
+        Ok(())
+    }
+}
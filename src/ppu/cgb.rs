@@ -0,0 +1,122 @@
+use crate::state::{StateError, StateReader, StateWriter};
+
+// BG and OBJ palette RAM, 8 palettes of 4 little endian RGB555 colours each.
+// BCPS/OCPS pick the byte BCPD/OCPD talk to and bit 7 makes it go up after every write
+pub struct CgbPalettes {
+    pub background: [u8; 64],
+    pub objects: [u8; 64],
+    bcps: u8,
+    ocps: u8,
+}
+
+impl CgbPalettes {
+    pub fn new() -> CgbPalettes {
+        CgbPalettes {
+            background: [0xFF; 64],
+            objects: [0xFF; 64],
+            bcps: 0,
+            ocps: 0,
+        }
+    }
+
+    // `blocked` is set while the PPU is in mode 3 and can't give the CPU palette RAM
+    pub fn read(&self, address: u16, blocked: bool) -> u8 {
+        match address {
+            0xFF68 => self.bcps | 0x40,
+            0xFF69 if blocked => 0xFF,
+            0xFF69 => self.background[(self.bcps & 0x3F) as usize],
+            0xFF6A => self.ocps | 0x40,
+            0xFF6B if blocked => 0xFF,
+            0xFF6B => self.objects[(self.ocps & 0x3F) as usize],
+            _ => 0xFF,
+        }
+    }
+
+    pub fn write(&mut self, address: u16, data: u8, blocked: bool) {
+        match address {
+            0xFF68 => self.bcps = data & 0xBF,
+            0xFF69 => {
+                if !blocked {
+                    self.background[(self.bcps & 0x3F) as usize] = data;
+                }
+                self.bcps = Self::increment(self.bcps);
+            }
+            0xFF6A => self.ocps = data & 0xBF,
+            0xFF6B => {
+                if !blocked {
+                    self.objects[(self.ocps & 0x3F) as usize] = data;
+                }
+                self.ocps = Self::increment(self.ocps);
+            }
+            _ => (),
+        }
+    }
+
+    fn increment(spec: u8) -> u8 {
+        if spec & 0x80 != 0 {
+            0x80 | (spec + 1) & 0x3F
+        } else {
+            spec
+        }
+    }
+
+    pub fn background_color(&self, palette: u8, color: u8) -> u16 {
+        Self::color(&self.background, palette, color)
+    }
+
+    pub fn object_color(&self, palette: u8, color: u8) -> u16 {
+        Self::color(&self.objects, palette, color)
+    }
+
+    fn color(ram: &[u8; 64], palette: u8, color: u8) -> u16 {
+        let index = (palette as usize & 0x7) * 8 + color as usize * 2;
+        u16::from_le_bytes([ram[index], ram[index + 1]]) & 0x7FFF
+    }
+
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.bytes(&self.background);
+        state.bytes(&self.objects);
+        state.u8(self.bcps);
+        state.u8(self.ocps);
+    }
+
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        state.bytes(&mut self.background)?;
+        state.bytes(&mut self.objects)?;
+        self.bcps = state.u8()?;
+        self.ocps = state.u8()?;
+        Ok(())
+    }
+}
+
+impl Default for CgbPalettes {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// BG map attributes, stored in VRAM bank 1 at the same offset as the tile number
+#[derive(Clone, Copy, Default)]
+pub struct BgAttributes(pub u8);
+
+impl BgAttributes {
+    pub fn palette(&self) -> u8 {
+        self.0 & 0x7
+    }
+
+    pub fn bank(&self) -> usize {
+        ((self.0 >> 3) & 1) as usize
+    }
+
+    pub fn x_flip(&self) -> bool {
+        self.0 & 0x20 != 0
+    }
+
+    pub fn y_flip(&self) -> bool {
+        self.0 & 0x40 != 0
+    }
+
+    pub fn priority(&self) -> bool {
+        self.0 & 0x80 != 0
+    }
+}
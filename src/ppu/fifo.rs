@@ -0,0 +1,331 @@
+use std::collections::VecDeque;
+
+use super::cgb::BgAttributes;
+use super::scanline::tile_address;
+use super::{Pixel, Ppu, Sprite, Vram, SCREEN_WIDTH};
+use crate::io::Io;
+use crate::state::{StateError, StateReader, StateWriter};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum FetcherStep {
+    Tile,
+    DataLow,
+    DataHigh,
+    Push,
+}
+
+// Dots the PPU spends on the throwaway tile fetch at the start of every line
+const STARTUP_DOTS: u8 = 6;
+// Dots a sprite fetch stalls the line for, on top of up to 5 dots to finish the
+// background tile under it, see sprite_penalty
+const SPRITE_FETCH_DOTS: u8 = 6;
+
+// The background and sprite shift registers plus the fetcher that fills them.
+// Everything is read from the registers as the pixels get pushed out, which is what
+// makes mid-scanline writes show up on screen and mode 3 change length
+pub struct PixelFifo {
+    background: VecDeque<Pixel>,
+    sprites: VecDeque<Pixel>,
+    step: FetcherStep,
+    step_dots: u8,
+    tile: u8,
+    attributes: BgAttributes,
+    tile_row: u8,
+    low: u8,
+    high: u8,
+    fetcher_x: u8,
+    lcd_x: u8,
+    discard: u8,
+    startup: u8,
+    in_window: bool,
+    // Bit n is set once the n-th sprite of the line has been fetched
+    fetched_sprites: u16,
+    pending_sprite: Option<usize>,
+    sprite_dots: u8,
+    // Background tile the last sprite waited for, the next ones over it don't wait again
+    penalty_tile: Option<u16>,
+}
+
+impl PixelFifo {
+    pub fn new() -> PixelFifo {
+        PixelFifo {
+            background: VecDeque::with_capacity(16),
+            sprites: VecDeque::with_capacity(8),
+            step: FetcherStep::Tile,
+            step_dots: 0,
+            tile: 0,
+            attributes: BgAttributes(0),
+            tile_row: 0,
+            low: 0,
+            high: 0,
+            fetcher_x: 0,
+            lcd_x: 0,
+            discard: 0,
+            startup: 0,
+            in_window: false,
+            fetched_sprites: 0,
+            pending_sprite: None,
+            sprite_dots: 0,
+            penalty_tile: None,
+        }
+    }
+
+    pub fn start_line(&mut self, scx: u8) {
+        self.background.clear();
+        self.sprites.clear();
+        self.step = FetcherStep::Tile;
+        self.step_dots = 0;
+        self.fetcher_x = 0;
+        self.lcd_x = 0;
+        self.discard = scx & 0x7;
+        self.startup = STARTUP_DOTS;
+        self.in_window = false;
+        self.fetched_sprites = 0;
+        self.pending_sprite = None;
+        self.penalty_tile = None;
+    }
+
+    pub fn save_state(&self, state: &mut StateWriter) {
+        for pixels in [&self.background, &self.sprites] {
+            state.u8(pixels.len() as u8);
+            for pixel in pixels {
+                pixel.save_state(state);
+            }
+        }
+        state.u8(self.step as u8);
+        state.bytes(&[self.step_dots, self.tile, self.attributes.0, self.tile_row]);
+        state.bytes(&[self.low, self.high, self.fetcher_x, self.lcd_x]);
+        state.bytes(&[self.discard, self.startup, self.sprite_dots]);
+        state.bool(self.in_window);
+        state.u16(self.fetched_sprites);
+        state.u8(self.pending_sprite.map_or(0xFF, |index| index as u8));
+        state.bool(self.penalty_tile.is_some());
+        state.u16(self.penalty_tile.unwrap_or(0));
+    }
+
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        for pixels in [&mut self.background, &mut self.sprites] {
+            pixels.clear();
+            for _ in 0..state.u8()? {
+                let mut pixel = Pixel::default();
+                pixel.load_state(state)?;
+                pixels.push_back(pixel);
+            }
+        }
+        self.step = match state.u8()? {
+            0 => FetcherStep::Tile,
+            1 => FetcherStep::DataLow,
+            2 => FetcherStep::DataHigh,
+            3 => FetcherStep::Push,
+            _ => return Err(StateError::Data),
+        };
+        let mut bytes = [0; 4];
+        state.bytes(&mut bytes)?;
+        [self.step_dots, self.tile, self.attributes.0, self.tile_row] = bytes;
+        state.bytes(&mut bytes)?;
+        [self.low, self.high, self.fetcher_x, self.lcd_x] = bytes;
+        let mut bytes = [0; 3];
+        state.bytes(&mut bytes)?;
+        [self.discard, self.startup, self.sprite_dots] = bytes;
+        self.in_window = state.bool()?;
+        self.fetched_sprites = state.u16()?;
+        let pending = state.u8()?;
+        self.pending_sprite = (pending != 0xFF).then_some(pending as usize);
+        let penalty = state.bool()?;
+        let tile = state.u16()?;
+        self.penalty_tile = penalty.then_some(tile);
+        Ok(())
+    }
+}
+
+impl Default for PixelFifo {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Ppu {
+    // Runs the FIFO for one dot, returns true once all 160 pixels of the line are out
+    pub fn fifo_tick(&mut self, io: &Io, vram: &Vram) -> bool {
+        if self.fifo.startup > 0 {
+            self.fifo.startup -= 1;
+            return false;
+        }
+        let lcdc = io.lcdc();
+
+        // Pixels stop shifting out while a sprite gets fetched
+        if let Some(index) = self.fifo.pending_sprite {
+            self.fifo.sprite_dots -= 1;
+            if self.fifo.sprite_dots == 0 {
+                self.mix_sprite(index, io, vram);
+                self.fifo.pending_sprite = None;
+            }
+            return false;
+        }
+
+        let wx = io.get(0xFF4B);
+        if !self.fifo.in_window
+            && lcdc.window_enable()
+            && self.window_triggered
+            && self.fifo.lcd_x as u16 + 7 >= wx as u16
+        {
+            let fifo = &mut self.fifo;
+            fifo.in_window = true;
+            fifo.penalty_tile = None;
+            fifo.background.clear();
+            fifo.step = FetcherStep::Tile;
+            fifo.step_dots = 0;
+            fifo.fetcher_x = 0;
+            fifo.discard = if fifo.lcd_x == 0 {
+                7u8.saturating_sub(wx)
+            } else {
+                0
+            };
+        }
+
+        if lcdc.sprite_enable() {
+            let lcd_x = self.fifo.lcd_x as u16;
+            let fetched = self.fifo.fetched_sprites;
+            let next =
+                self.sprites.iter().enumerate().position(|(i, sprite)| {
+                    fetched & (1 << i) == 0 && sprite.x as u16 <= lcd_x + 8
+                });
+            if let Some(index) = next {
+                self.fifo.fetched_sprites |= 1 << index;
+                self.fifo.pending_sprite = Some(index);
+                self.fifo.sprite_dots = self.sprite_penalty(self.sprites[index], io);
+                return false;
+            }
+        }
+
+        self.fetch_step(io, vram);
+        let Some(bg) = self.fifo.background.pop_front() else {
+            return false;
+        };
+        if self.fifo.discard > 0 {
+            self.fifo.discard -= 1;
+            return false;
+        }
+        let sprite = self.fifo.sprites.pop_front().unwrap_or_default();
+        self.put_pixel(io, self.fifo.lcd_x as usize, bg, sprite);
+
+        self.fifo.lcd_x += 1;
+        if self.fifo.lcd_x as usize == SCREEN_WIDTH {
+            if self.fifo.in_window {
+                self.window_line += 1;
+            }
+            return true;
+        }
+        false
+    }
+
+    // Each fetcher step takes 2 dots, pushing is retried every dot until the FIFO is empty
+    fn fetch_step(&mut self, io: &Io, vram: &Vram) {
+        let lcdc = io.lcdc();
+        let fifo = &mut self.fifo;
+        if fifo.step == FetcherStep::Push {
+            if fifo.background.is_empty() {
+                let attributes = fifo.attributes;
+                for column in 0..8 {
+                    let bit = if attributes.x_flip() {
+                        column
+                    } else {
+                        7 - column
+                    };
+                    let color = ((fifo.high >> bit) & 1) << 1 | (fifo.low >> bit) & 1;
+                    fifo.background.push_back(Pixel {
+                        color,
+                        palette: attributes.palette(),
+                        priority: attributes.priority(),
+                        index: 0,
+                    });
+                }
+                fifo.fetcher_x = fifo.fetcher_x.wrapping_add(1);
+                fifo.step = FetcherStep::Tile;
+                fifo.step_dots = 0;
+            }
+            return;
+        }
+
+        fifo.step_dots += 1;
+        if fifo.step_dots < 2 {
+            return;
+        }
+        fifo.step_dots = 0;
+        fifo.step = match fifo.step {
+            FetcherStep::Tile => {
+                let (map, x, y) = if fifo.in_window {
+                    (lcdc.window_tile_map(), fifo.fetcher_x, self.window_line)
+                } else {
+                    let scx = io.get(0xFF43);
+                    let x = (scx / 8).wrapping_add(fifo.fetcher_x) & 0x1F;
+                    (lcdc.bg_tile_map(), x, self.ly.wrapping_add(io.get(0xFF42)))
+                };
+                let offset = (map - 0x8000) as usize + (y / 8) as usize * 32 + (x & 0x1F) as usize;
+                fifo.tile = vram[0][offset];
+                fifo.attributes = if self.cgb_mode {
+                    BgAttributes(vram[1][offset])
+                } else {
+                    BgAttributes(0)
+                };
+                fifo.tile_row = if fifo.attributes.y_flip() {
+                    7 - y % 8
+                } else {
+                    y % 8
+                };
+                FetcherStep::DataLow
+            }
+            FetcherStep::DataLow => {
+                let address = tile_address(lcdc, fifo.tile) + fifo.tile_row as usize * 2;
+                fifo.low = vram[fifo.attributes.bank()][address];
+                FetcherStep::DataHigh
+            }
+            FetcherStep::DataHigh => {
+                let address = tile_address(lcdc, fifo.tile) + fifo.tile_row as usize * 2;
+                fifo.high = vram[fifo.attributes.bank()][address + 1];
+                FetcherStep::Push
+            }
+            FetcherStep::Push => FetcherStep::Push,
+        };
+    }
+
+    // 6 to 11 dots, like the hardware. The fetcher first finishes the background (or
+    // window) tile under the sprite's left edge, 5 dots at its first pixel down to none
+    // at the last two, but only the first sprite over a tile waits for that. A sprite at
+    // X 0 always takes 11
+    fn sprite_penalty(&mut self, sprite: Sprite, io: &Io) -> u8 {
+        if sprite.x == 0 {
+            return SPRITE_FETCH_DOTS + 5;
+        }
+        let scroll = if self.fifo.in_window {
+            255 - io.get(0xFF4B)
+        } else {
+            io.get(0xFF43)
+        };
+        let column = sprite.x as u16 + scroll as u16;
+        if self.fifo.penalty_tile == Some(column / 8) {
+            return SPRITE_FETCH_DOTS;
+        }
+        self.fifo.penalty_tile = Some(column / 8);
+        SPRITE_FETCH_DOTS + 5u8.saturating_sub((column % 8) as u8)
+    }
+
+    // Merges a fetched sprite into the sprite FIFO. Opaque pixels already in there win,
+    // except on the CGB where the lower OAM index does
+    fn mix_sprite(&mut self, index: usize, io: &Io, vram: &Vram) {
+        let sprite = self.sprites[index];
+        let x_priority = self.sprite_x_priority(io);
+        while self.fifo.sprites.len() < 8 {
+            self.fifo.sprites.push_back(Pixel::default());
+        }
+        // Sprites hanging off the left edge lose their first columns
+        let start = 8u8.saturating_sub(sprite.x);
+        for column in start..8 {
+            let pixel = self.sprite_pixel(&sprite, vram, column);
+            let slot = &mut self.fifo.sprites[(column - start) as usize];
+            if slot.color == 0 || (!x_priority && pixel.color != 0 && pixel.index < slot.index) {
+                *slot = pixel;
+            }
+        }
+    }
+}
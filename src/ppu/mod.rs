@@ -0,0 +1,451 @@
+pub mod cgb;
+pub mod fifo;
+pub mod scanline;
+
+use crate::io::{Interrupt, Io, Stat};
+use crate::sgb::Sgb;
+use crate::state::{StateError, StateReader, StateWriter};
+use cgb::CgbPalettes;
+use fifo::PixelFifo;
+use scanline::{shade, tile_color};
+
+pub const SCREEN_WIDTH: usize = 160;
+pub const SCREEN_HEIGHT: usize = 144;
+
+pub(crate) type Vram = [[u8; 0x2000]; 2];
+
+// RGB555 greys used for the colour framebuffer when running DMG games
+pub const DMG_GREYS: [u16; 4] = [0x7FFF, 0x56B5, 0x294A, 0x0000];
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PpuMode {
+    HBlank = 0,
+    VBlank = 1,
+    OamScan = 2,
+    Drawing = 3,
+}
+
+// The scanline renderer draws a whole line at the start of mode 3, which is fast but
+// ignores mid-line register writes. The FIFO one pushes pixels out dot by dot
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Renderer {
+    Scanline,
+    Fifo,
+}
+
+// A background or sprite pixel before it goes through the palettes
+#[derive(Clone, Copy, Default)]
+pub struct Pixel {
+    pub color: u8,
+    // OBP0/OBP1 for DMG sprites, one of the 8 palettes on the CGB
+    pub palette: u8,
+    // BG attribute priority for the background, "behind BG" for sprites
+    pub priority: bool,
+    // OAM index for sprites
+    pub index: u8,
+}
+
+impl Pixel {
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.bytes(&[self.color, self.palette, self.index]);
+        state.bool(self.priority);
+    }
+
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        let mut bytes = [0; 3];
+        state.bytes(&mut bytes)?;
+        [self.color, self.palette, self.index] = bytes;
+        self.priority = state.bool()?;
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Sprite {
+    pub y: u8,
+    pub x: u8,
+    pub tile: u8,
+    pub flags: u8,
+    pub index: u8,
+    // 8 or 16, as LCDC had it when OAM was scanned
+    pub height: u8,
+}
+
+pub struct Ppu {
+    pub mode: PpuMode,
+    pub ly: u8,
+    pub dot: u16,
+    pub window_line: u8,
+    window_triggered: bool,
+    sprites: Vec<Sprite>,
+    stat_line: bool,
+    lcd_on: bool,
+    // One shade (0-3) per pixel after going through the DMG palettes,
+    // or the colour index inside its palette in CGB mode
+    pub framebuffer: [u8; SCREEN_WIDTH * SCREEN_HEIGHT],
+    pub color_framebuffer: [u16; SCREEN_WIDTH * SCREEN_HEIGHT],
+    pub cgb_mode: bool,
+    pub palettes: CgbPalettes,
+    // DMG rendering goes through CGB palette RAM, like a CGB running a DMG game
+    pub compat_palette: bool,
+    // Super Game Boy colouring, DMG mode only
+    pub sgb: Option<Box<Sgb>>,
+    pub frame_ready: bool,
+    pub frames: u64,
+    pub renderer: Renderer,
+    fifo: PixelFifo,
+}
+
+impl Ppu {
+    pub const LINE_DOTS: u16 = 456;
+    pub const OAM_SCAN_DOTS: u16 = 80;
+    pub const DRAWING_DOTS: u16 = 172;
+    pub const LINES: u8 = 154;
+    pub const MAX_SPRITES: usize = 10;
+
+    pub fn new() -> Ppu {
+        Ppu {
+            mode: PpuMode::HBlank,
+            ly: 0,
+            dot: 0,
+            window_line: 0,
+            window_triggered: false,
+            sprites: Vec::with_capacity(Self::MAX_SPRITES),
+            stat_line: false,
+            lcd_on: false,
+            framebuffer: [0; SCREEN_WIDTH * SCREEN_HEIGHT],
+            color_framebuffer: [DMG_GREYS[0]; SCREEN_WIDTH * SCREEN_HEIGHT],
+            cgb_mode: false,
+            palettes: CgbPalettes::new(),
+            compat_palette: false,
+            sgb: None,
+            frame_ready: false,
+            frames: 0,
+            renderer: Renderer::Scanline,
+            fifo: PixelFifo::new(),
+        }
+    }
+
+    // Advances the PPU by one M-cycle (4 dots) and returns the mode it switched to, if any
+    pub fn step(&mut self, io: &mut Io, vram: &Vram, oam: &[u8], cgb: bool) -> Option<PpuMode> {
+        self.cgb_mode = cgb;
+        if !io.lcdc().lcd_enable() {
+            if self.lcd_on {
+                self.turn_off(io);
+            }
+            return None;
+        }
+        if !self.lcd_on {
+            self.lcd_on = true;
+            self.ly = 0;
+            self.dot = 0;
+            self.enter_mode(PpuMode::OamScan, io, oam);
+            self.update_registers(io);
+            return Some(PpuMode::OamScan);
+        }
+
+        let mut entered = None;
+        for _ in 0..4 {
+            if let Some(mode) = self.tick_dot(io, vram, oam) {
+                entered = Some(mode);
+            }
+        }
+        self.update_registers(io);
+        entered
+    }
+
+    fn tick_dot(&mut self, io: &mut Io, vram: &Vram, oam: &[u8]) -> Option<PpuMode> {
+        self.dot += 1;
+        let mut entered = None;
+        if self.dot == Self::LINE_DOTS {
+            self.dot = 0;
+            self.ly += 1;
+            if self.ly == Self::LINES {
+                self.ly = 0;
+            }
+            if self.ly < SCREEN_HEIGHT as u8 {
+                entered = Some(PpuMode::OamScan);
+            } else if self.ly == SCREEN_HEIGHT as u8 {
+                entered = Some(PpuMode::VBlank);
+            }
+        } else if self.ly < SCREEN_HEIGHT as u8 {
+            if self.dot == Self::OAM_SCAN_DOTS {
+                entered = Some(PpuMode::Drawing);
+            } else if self.mode == PpuMode::Drawing {
+                let done = match self.renderer {
+                    Renderer::Scanline => self.dot == Self::OAM_SCAN_DOTS + Self::DRAWING_DOTS,
+                    Renderer::Fifo => self.fifo_tick(io, vram),
+                };
+                if done {
+                    entered = Some(PpuMode::HBlank);
+                }
+            }
+        }
+
+        if let Some(mode) = entered {
+            self.enter_mode(mode, io, oam);
+            if mode == PpuMode::Drawing {
+                match self.renderer {
+                    Renderer::Scanline => self.render_scanline(io, vram),
+                    Renderer::Fifo => self.fifo.start_line(io.get(0xFF43)),
+                }
+            }
+        }
+        entered
+    }
+
+    fn enter_mode(&mut self, mode: PpuMode, io: &mut Io, oam: &[u8]) {
+        self.mode = mode;
+        match mode {
+            PpuMode::OamScan => {
+                if self.ly == 0 {
+                    self.window_line = 0;
+                    self.window_triggered = false;
+                }
+                if self.ly == io.get(0xFF4A) {
+                    self.window_triggered = true;
+                }
+                self.oam_scan(io, oam);
+            }
+            PpuMode::VBlank => {
+                io.request_interrupt(Interrupt::VBlank);
+                self.frame_ready = true;
+                self.frames += 1;
+            }
+            _ => (),
+        }
+    }
+
+    // Picks the first 10 sprites in OAM order that overlap the current line
+    fn oam_scan(&mut self, io: &Io, oam: &[u8]) {
+        let height = io.lcdc().sprite_height();
+        let line = self.ly + 16;
+        self.sprites.clear();
+        for (index, entry) in oam.chunks(4).take(40).enumerate() {
+            let y = entry[0];
+            if line >= y && line < y.wrapping_add(height) {
+                self.sprites.push(Sprite {
+                    y,
+                    x: entry[1],
+                    tile: entry[2],
+                    flags: entry[3],
+                    index: index as u8,
+                    height,
+                });
+                if self.sprites.len() == Self::MAX_SPRITES {
+                    break;
+                }
+            }
+        }
+    }
+
+    fn update_registers(&mut self, io: &mut Io) {
+        let coincidence = self.ly == io.get(0xFF45);
+        let stat = io.get(0xFF41) & 0xF8 | (coincidence as u8) << 2 | self.mode as u8;
+        io.set(0xFF41, stat);
+        io.set(0xFF44, self.ly);
+
+        // The STAT interrupt fires on the rising edge of all its sources OR'ed together
+        let stat = Stat(stat);
+        let line = match self.mode {
+            PpuMode::HBlank => stat.hblank_interrupt(),
+            PpuMode::VBlank => stat.vblank_interrupt(),
+            PpuMode::OamScan => stat.oam_interrupt(),
+            PpuMode::Drawing => false,
+        } || (coincidence && stat.lyc_interrupt());
+        if line && !self.stat_line {
+            io.request_interrupt(Interrupt::Stat);
+        }
+        self.stat_line = line;
+    }
+
+    fn turn_off(&mut self, io: &mut Io) {
+        self.lcd_on = false;
+        self.ly = 0;
+        self.dot = 0;
+        self.mode = PpuMode::HBlank;
+        self.stat_line = false;
+        self.framebuffer.fill(0);
+        self.color_framebuffer.fill(0x7FFF);
+        io.set(0xFF41, io.get(0xFF41) & 0xF8);
+        io.set(0xFF44, 0);
+    }
+
+    // DMG sprites with a lower X win, on the CGB it's the OAM order unless OPRI says otherwise
+    fn sprite_x_priority(&self, io: &Io) -> bool {
+        !self.cgb_mode || io.get(0xFF6C) & 1 != 0
+    }
+
+    // Column (0-7, before flipping) of a sprite on the current line
+    // The height is the one the sprite was picked with, a later LCDC write can't put the
+    // line outside of it
+    fn sprite_pixel(&self, sprite: &Sprite, vram: &Vram, column: u8) -> Pixel {
+        let height = sprite.height;
+        let mut row = self.ly + 16 - sprite.y;
+        if sprite.flags & 0x40 != 0 {
+            row = height - 1 - row;
+        }
+        let column = if sprite.flags & 0x20 != 0 {
+            7 - column
+        } else {
+            column
+        };
+        let tile = if height == 16 {
+            sprite.tile & 0xFE
+        } else {
+            sprite.tile
+        };
+        let (bank, palette) = if self.cgb_mode {
+            (((sprite.flags >> 3) & 1) as usize, sprite.flags & 0x7)
+        } else {
+            (0, (sprite.flags >> 4) & 1)
+        };
+        // The bottom half of a 8x16 sprite is the next tile, which directly follows in VRAM
+        Pixel {
+            color: tile_color(&vram[bank], tile as usize * 16, column, row),
+            palette,
+            priority: sprite.flags & 0x80 != 0,
+            index: sprite.index,
+        }
+    }
+
+    // Picks between the background and sprite pixel and writes the result to the framebuffers
+    fn put_pixel(&mut self, io: &Io, x: usize, background: Pixel, sprite: Pixel) {
+        let lcdc = io.lcdc();
+        let index = self.ly as usize * SCREEN_WIDTH + x;
+        let sprite_visible = sprite.color != 0 && lcdc.sprite_enable();
+        if self.cgb_mode {
+            // With LCDC bit 0 clear sprites always end up on top
+            let background_wins = background.color != 0
+                && lcdc.bg_enable()
+                && (background.priority || sprite.priority);
+            let (color, rgb) = if sprite_visible && !background_wins {
+                let rgb = self.palettes.object_color(sprite.palette, sprite.color);
+                (sprite.color, rgb)
+            } else {
+                let rgb = self
+                    .palettes
+                    .background_color(background.palette, background.color);
+                (background.color, rgb)
+            };
+            self.framebuffer[index] = color;
+            self.color_framebuffer[index] = rgb;
+        } else {
+            let bg_color = if lcdc.bg_enable() {
+                background.color
+            } else {
+                0
+            };
+            let (value, rgb) = if sprite_visible && (!sprite.priority || bg_color == 0) {
+                let palette = if sprite.palette == 1 { 0xFF49 } else { 0xFF48 };
+                let value = shade(io.get(palette), sprite.color);
+                (value, self.palettes.object_color(sprite.palette, value))
+            } else {
+                let value = shade(io.get(0xFF47), bg_color);
+                (value, self.palettes.background_color(0, value))
+            };
+            self.framebuffer[index] = value;
+            self.color_framebuffer[index] = if let Some(sgb) = &self.sgb {
+                sgb.color(x, self.ly as usize, value, self.color_framebuffer[index])
+            } else if self.compat_palette {
+                rgb
+            } else {
+                DMG_GREYS[value as usize]
+            };
+        }
+    }
+
+    // The CPU can't see VRAM while pixels are being drawn or OAM while the PPU is using it
+    pub fn vram_blocked(&self) -> bool {
+        self.lcd_on && self.mode == PpuMode::Drawing
+    }
+
+    pub fn oam_blocked(&self) -> bool {
+        self.lcd_on && matches!(self.mode, PpuMode::OamScan | PpuMode::Drawing)
+    }
+
+    // Returns true once per frame, when the PPU enters V-blank
+    pub fn take_frame(&mut self) -> bool {
+        std::mem::take(&mut self.frame_ready)
+    }
+
+    // The renderer is a setting of the frontend and isn't saved
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.u8(self.mode as u8);
+        state.u8(self.ly);
+        state.u16(self.dot);
+        state.u8(self.window_line);
+        state.bool(self.window_triggered);
+        state.u8(self.sprites.len() as u8);
+        for sprite in &self.sprites {
+            let bytes = [sprite.y, sprite.x, sprite.tile, sprite.flags, sprite.index];
+            state.bytes(&bytes);
+            state.u8(sprite.height);
+        }
+        state.bool(self.stat_line);
+        state.bool(self.lcd_on);
+        state.bytes(&self.framebuffer);
+        state.u16s(&self.color_framebuffer);
+        state.bool(self.cgb_mode);
+        self.palettes.save_state(state);
+        state.bool(self.compat_palette);
+        state.bool(self.sgb.is_some());
+        if let Some(sgb) = &self.sgb {
+            sgb.save_state(state);
+        }
+        state.bool(self.frame_ready);
+        state.u64(self.frames);
+        self.fifo.save_state(state);
+    }
+
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        self.mode = match state.u8()? {
+            0 => PpuMode::HBlank,
+            1 => PpuMode::VBlank,
+            2 => PpuMode::OamScan,
+            3 => PpuMode::Drawing,
+            _ => return Err(StateError::Data),
+        };
+        self.ly = state.u8()?;
+        self.dot = state.u16()?;
+        self.window_line = state.u8()?;
+        self.window_triggered = state.bool()?;
+        self.sprites.clear();
+        for _ in 0..state.u8()? {
+            let mut bytes = [0; 6];
+            state.bytes(&mut bytes)?;
+            let [y, x, tile, flags, index, height] = bytes;
+            self.sprites.push(Sprite {
+                y,
+                x,
+                tile,
+                flags,
+                index,
+                height,
+            });
+        }
+        self.stat_line = state.bool()?;
+        self.lcd_on = state.bool()?;
+        state.bytes(&mut self.framebuffer)?;
+        state.u16s(&mut self.color_framebuffer)?;
+        self.cgb_mode = state.bool()?;
+        self.palettes.load_state(state)?;
+        self.compat_palette = state.bool()?;
+        self.sgb = if state.bool()? {
+            let mut sgb = Box::new(Sgb::new());
+            sgb.load_state(state)?;
+            Some(sgb)
+        } else {
+            None
+        };
+        self.frame_ready = state.bool()?;
+        self.frames = state.u64()?;
+        self.fifo.load_state(state)
+    }
+}
+
+impl Default for Ppu {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,106 @@
+use super::cgb::BgAttributes;
+use super::{Pixel, Ppu, Vram, SCREEN_WIDTH};
+use crate::io::{Io, Lcdc};
+
+// Maps a 2 bit colour index to a shade through BGP, OBP0 or OBP1
+pub fn shade(palette: u8, color: u8) -> u8 {
+    (palette >> (color * 2)) & 0x3
+}
+
+// Colour index of pixel (x, y) inside the tile starting at `address` in VRAM
+pub fn tile_color(bank: &[u8], address: usize, x: u8, y: u8) -> u8 {
+    let lo = bank[address + y as usize * 2];
+    let hi = bank[address + y as usize * 2 + 1];
+    let bit = 7 - x;
+    ((hi >> bit) & 1) << 1 | (lo >> bit) & 1
+}
+
+pub fn tile_address(lcdc: Lcdc, tile: u8) -> usize {
+    if lcdc.unsigned_tile_data() {
+        tile as usize * 16
+    } else {
+        (0x1000 + tile as i8 as i32 * 16) as usize
+    }
+}
+
+impl Ppu {
+    // Draws the whole current line at once, using the registers as they are when mode 3 starts
+    pub fn render_scanline(&mut self, io: &Io, vram: &Vram) {
+        let lcdc = io.lcdc();
+        let ly = self.ly;
+        let (scy, scx) = (io.get(0xFF42), io.get(0xFF43));
+        let wx = io.get(0xFF4B);
+
+        let mut background = [Pixel::default(); SCREEN_WIDTH];
+        // On the CGB LCDC bit 0 only takes priority away from the background
+        if lcdc.bg_enable() || self.cgb_mode {
+            let window_visible = lcdc.window_enable() && self.window_triggered && wx <= 166;
+            let mut window_drawn = false;
+            for (x, pixel) in background.iter_mut().enumerate() {
+                *pixel = if window_visible && x + 7 >= wx as usize {
+                    window_drawn = true;
+                    let px = (x + 7 - wx as usize) as u8;
+                    self.bg_pixel(lcdc, vram, lcdc.window_tile_map(), px, self.window_line)
+                } else {
+                    let (px, py) = ((x as u8).wrapping_add(scx), ly.wrapping_add(scy));
+                    self.bg_pixel(lcdc, vram, lcdc.bg_tile_map(), px, py)
+                };
+            }
+            if window_drawn {
+                self.window_line += 1;
+            }
+        }
+
+        let mut sprites = [Pixel::default(); SCREEN_WIDTH];
+        if lcdc.sprite_enable() {
+            let mut order = self.sprites.clone();
+            if self.sprite_x_priority(io) {
+                order.sort_by_key(|sprite| sprite.x);
+            }
+            for (x, pixel) in sprites.iter_mut().enumerate() {
+                for sprite in &order {
+                    let Some(column) = (x + 8).checked_sub(sprite.x as usize).filter(|&c| c < 8)
+                    else {
+                        continue;
+                    };
+                    let candidate = self.sprite_pixel(sprite, vram, column as u8);
+                    if candidate.color != 0 {
+                        *pixel = candidate;
+                        break;
+                    }
+                }
+            }
+        }
+
+        for x in 0..SCREEN_WIDTH {
+            self.put_pixel(io, x, background[x], sprites[x]);
+        }
+    }
+
+    // Pixel (x, y) of a 256x256 tile map, with the CGB attributes applied
+    fn bg_pixel(&self, lcdc: Lcdc, vram: &Vram, map: u16, x: u8, y: u8) -> Pixel {
+        let offset = (map - 0x8000) as usize + (y / 8) as usize * 32 + (x / 8) as usize;
+        let tile = vram[0][offset];
+        let attributes = if self.cgb_mode {
+            BgAttributes(vram[1][offset])
+        } else {
+            BgAttributes::default()
+        };
+        let px = if attributes.x_flip() {
+            7 - x % 8
+        } else {
+            x % 8
+        };
+        let py = if attributes.y_flip() {
+            7 - y % 8
+        } else {
+            y % 8
+        };
+        Pixel {
+            color: tile_color(&vram[attributes.bank()], tile_address(lcdc, tile), px, py),
+            palette: attributes.palette(),
+            priority: attributes.priority(),
+            index: 0,
+        }
+    }
+}
@@ -8,13 +8,16 @@ pub enum RomError {
     RomSize,
     RamSize,
     Load,
+    Save,
 }
 
+#[derive(Clone)]
 pub struct Catridge {
     pub nintendo_logo: Vec<u8>,
     pub title: String,
     pub license_code: String,
     pub sgb: bool,
+    pub cgb: bool,
     pub catridge_type: Vec<CatridgeType>,
     pub rom_size: usize,
     pub ram_size: usize,
@@ -26,7 +29,7 @@ pub struct Catridge {
     pub ram: Vec<u8>,
 }
 
-#[derive(PartialEq)]
+#[derive(PartialEq, Clone, Copy)]
 pub enum CatridgeType {
     Rom,
     Mbc1,
@@ -52,11 +55,19 @@ impl Catridge {
             Ok(v) => v,
             _ => return Err(RomError::Load),
         };
+        Self::from_bytes(data)
+    }
+
+    pub fn from_bytes(data: Vec<u8>) -> Result<Catridge, RomError> {
+        if data.len() < 0x150 {
+            return Err(RomError::Load);
+        }
         let logo = Self::load_logo(&data)?;
         let title = Self::load_title(&data)?;
         let license_code = Self::load_license_code(&data)?;
         let catridge_type = Self::load_catridge_type(&data)?;
         let sgb = data[0x146] == 3;
+        let cgb = data[0x143] & 0x80 != 0;
         let rom_size: usize = 32 << data[0x148];
         let ram_size = (1 << 10) * Self::get_ram(&data)? as usize;
         let destination_code = data[0x14A] == 1;
@@ -70,6 +81,7 @@ impl Catridge {
             license_code,
             catridge_type,
             sgb,
+            cgb,
             rom_size,
             ram_size,
             japanese: destination_code,
@@ -77,7 +89,7 @@ impl Catridge {
             header_checksum,
             global_checksum,
             data,
-            ram: Vec::with_capacity(ram_size),
+            ram: vec![0; ram_size],
         };
 
         Ok(result)
@@ -99,6 +111,7 @@ impl Catridge {
             title: title,
             license_code: code,
             sgb: false,
+            cgb: false,
             catridge_type,
             rom_size,
             ram_size,
@@ -111,10 +124,30 @@ impl Catridge {
         }
     }
 
+    // Sum of the 16 title bytes, the CGB boot ROM uses it to pick a palette for DMG games
+    pub fn title_checksum(&self) -> u8 {
+        self.data
+            .get(0x134..=0x143)
+            .map_or(0, |title| title.iter().fold(0, |sum, &c| sum.wrapping_add(c)))
+    }
+
+    pub fn nintendo_licensed(&self) -> bool {
+        match self.data.get(0x14B) {
+            Some(0x01) => true,
+            Some(0x33) => self.data[0x144..=0x145] == *b"01",
+            _ => false,
+        }
+    }
+
     fn load_title(data: &Vec<u8>) -> Result<String, RomError> {
         let title = &data[0x134..=0x143];
-        let title = title.to_ascii_uppercase();
-        if title.eq(&[0; 16]) {
+        // CGB carts reuse the tail of the title for the manufacturer code and the CGB flag
+        let title: Vec<u8> = title
+            .iter()
+            .take_while(|&&c| c.is_ascii_graphic() || c == b' ')
+            .map(|c| c.to_ascii_uppercase())
+            .collect();
+        if title.is_empty() {
             return Err(RomError::Title);
         }
         match String::from_utf8(title) {
@@ -124,18 +157,13 @@ impl Catridge {
     }
 
     fn get_ram(data: &Vec<u8>) -> Result<u8, RomError> {
-        let result = match data[0x149] {
-            0x2 => 8,
-            0x3 => 32,
-            0x4 => 128,
-            0x5 => 64,
-            _ => 0,
-        };
-
-        if result == 0 {
-            Err(RomError::RamSize)
-        } else {
-            Ok(result)
+        match data[0x149] {
+            0x0 => Ok(0),
+            0x2 => Ok(8),
+            0x3 => Ok(32),
+            0x4 => Ok(128),
+            0x5 => Ok(64),
+            _ => Err(RomError::RamSize),
         }
     }
     fn check_header_checksum(data: &Vec<u8>) -> Result<bool, RomError> {
@@ -285,7 +313,14 @@ impl Catridge {
             };
             return Ok(manufacturer.to_string());
         } else {
-            todo!("Yet to implement the old manufacturer stuff");
+            // Only the codes the loader needs to tell apart, the old licensee names
+            // aren't mapped yet
+            let manufacturer = match data[0x014B] {
+                0x00 => "None",
+                0x01 => "Nintendo",
+                code => return Ok(format!("{:02X}", code)),
+            };
+            Ok(manufacturer.to_string())
         }
     }
 
@@ -0,0 +1,116 @@
+use crate::ppu::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use crate::state::{StateError, StateReader, StateWriter};
+
+pub const BORDER_WIDTH: usize = 256;
+pub const BORDER_HEIGHT: usize = 224;
+// Where the game screen sits inside the border
+pub const SCREEN_X: usize = 48;
+pub const SCREEN_Y: usize = 40;
+const MAP_WIDTH: usize = 32;
+const MAP_HEIGHT: usize = 28;
+// SNES 4bpp tiles
+const TILE_SIZE: usize = 32;
+
+// The 256x224 picture around the game, set up with CHR_TRN and PCT_TRN
+pub struct Border {
+    tiles: Vec<u8>,
+    // Tile number (bits 0-7), palette (10-12), x flip (14) and y flip (15) per entry
+    map: Vec<u16>,
+    // Border palettes 4-7 as RGB555, colour 0 of each is see-through
+    pub palettes: [[u16; 16]; 4],
+}
+
+impl Border {
+    pub fn new() -> Border {
+        Border {
+            tiles: vec![0; 256 * TILE_SIZE],
+            map: vec![0; MAP_WIDTH * MAP_HEIGHT],
+            palettes: [[0; 16]; 4],
+        }
+    }
+
+    // CHR_TRN, 128 tiles at a time
+    pub fn load_tiles(&mut self, upper: bool, data: &[u8]) {
+        let start = if upper { 128 * TILE_SIZE } else { 0 };
+        let length = (128 * TILE_SIZE).min(data.len());
+        self.tiles[start..start + length].copy_from_slice(&data[..length]);
+    }
+
+    // PCT_TRN, the 32x32 tile map (only 28 rows are shown) followed by the palettes
+    pub fn load_map(&mut self, data: &[u8]) {
+        for (i, entry) in self.map.iter_mut().enumerate() {
+            *entry = u16::from_le_bytes([data[i * 2], data[i * 2 + 1]]);
+        }
+        for (i, palette) in self.palettes.iter_mut().enumerate() {
+            for (j, color) in palette.iter_mut().enumerate() {
+                let address = 0x800 + (i * 16 + j) * 2;
+                *color = u16::from_le_bytes([data[address], data[address + 1]]) & 0x7FFF;
+            }
+        }
+    }
+
+    // Border colour at (x, y), None where it's see-through
+    pub fn pixel(&self, x: usize, y: usize) -> Option<u16> {
+        let entry = self.map[y / 8 * MAP_WIDTH + x / 8];
+        let tile = (entry & 0xFF) as usize * TILE_SIZE;
+        let column = if entry & 0x4000 != 0 {
+            x % 8
+        } else {
+            7 - x % 8
+        };
+        let row = if entry & 0x8000 != 0 {
+            7 - y % 8
+        } else {
+            y % 8
+        };
+        // Bitplanes 0 and 1 interleaved in the first 16 bytes, 2 and 3 in the next 16
+        let color = (0..4).fold(0, |color, plane| {
+            let byte = self.tiles[tile + (plane / 2) * 16 + row * 2 + plane % 2];
+            color | ((byte >> column) & 1) << plane
+        });
+        if color == 0 {
+            return None;
+        }
+        let palette = ((entry >> 10) & 0x7) as usize;
+        Some(self.palettes[palette.saturating_sub(4) & 0x3][color as usize])
+    }
+
+    // The whole 256x224 picture in RGB555, `screen` is the 160x144 game image
+    // and `backdrop` shows wherever neither covers
+    pub fn compose(&self, screen: &[u16], backdrop: u16) -> Vec<u16> {
+        let mut output = vec![backdrop; BORDER_WIDTH * BORDER_HEIGHT];
+        for (y, row) in screen.chunks(SCREEN_WIDTH).take(SCREEN_HEIGHT).enumerate() {
+            let start = (SCREEN_Y + y) * BORDER_WIDTH + SCREEN_X;
+            output[start..start + SCREEN_WIDTH].copy_from_slice(row);
+        }
+        for (i, color) in output.iter_mut().enumerate() {
+            if let Some(border) = self.pixel(i % BORDER_WIDTH, i / BORDER_WIDTH) {
+                *color = border;
+            }
+        }
+        output
+    }
+
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.bytes(&self.tiles);
+        state.u16s(&self.map);
+        for palette in &self.palettes {
+            state.u16s(palette);
+        }
+    }
+
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        state.bytes(&mut self.tiles)?;
+        state.u16s(&mut self.map)?;
+        for palette in &mut self.palettes {
+            state.u16s(palette)?;
+        }
+        Ok(())
+    }
+}
+
+impl Default for Border {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,462 @@
+pub mod border;
+
+use crate::io::Lcdc;
+use crate::ppu::scanline::tile_address;
+use crate::ppu::Vram;
+use crate::state::{StateError, StateReader, StateWriter};
+use border::Border;
+
+// Palettes are applied per 8x8 cell of the screen
+pub const ATTRIBUTE_WIDTH: usize = 20;
+pub const ATTRIBUTE_HEIGHT: usize = 18;
+const ATTRIBUTE_CELLS: usize = ATTRIBUTE_WIDTH * ATTRIBUTE_HEIGHT;
+const ATTRIBUTE_FILE_SIZE: usize = ATTRIBUTE_CELLS / 4;
+const ATTRIBUTE_FILES: usize = 45;
+const SYSTEM_PALETTES: usize = 512;
+// *_TRN commands copy this much of what's on screen
+const TRANSFER_SIZE: usize = 0x1000;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SgbCommand {
+    Pal01,
+    Pal23,
+    Pal03,
+    Pal12,
+    AttrBlk,
+    AttrLin,
+    AttrDiv,
+    AttrChr,
+    PalSet,
+    PalTrn,
+    MltReq,
+    ChrTrn,
+    PctTrn,
+    AttrTrn,
+    AttrSet,
+    MaskEn,
+    // Sound, SNES program and other commands that don't change the picture
+    Unsupported(u8),
+}
+
+impl SgbCommand {
+    pub fn from_code(code: u8) -> SgbCommand {
+        match code {
+            0x00 => SgbCommand::Pal01,
+            0x01 => SgbCommand::Pal23,
+            0x02 => SgbCommand::Pal03,
+            0x03 => SgbCommand::Pal12,
+            0x04 => SgbCommand::AttrBlk,
+            0x05 => SgbCommand::AttrLin,
+            0x06 => SgbCommand::AttrDiv,
+            0x07 => SgbCommand::AttrChr,
+            0x0A => SgbCommand::PalSet,
+            0x0B => SgbCommand::PalTrn,
+            0x11 => SgbCommand::MltReq,
+            0x13 => SgbCommand::ChrTrn,
+            0x14 => SgbCommand::PctTrn,
+            0x15 => SgbCommand::AttrTrn,
+            0x16 => SgbCommand::AttrSet,
+            0x17 => SgbCommand::MaskEn,
+            _ => SgbCommand::Unsupported(code),
+        }
+    }
+}
+
+// MASK_EN, what the screen shows instead of the game
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SgbMask {
+    Cancel,
+    // Keeps the last picture
+    Freeze,
+    Black,
+    Color0,
+}
+
+pub struct Sgb {
+    // The 4 screen palettes as RGB555, colour 0 is shared by all of them
+    pub palettes: [[u16; 4]; 4],
+    // Palette number (0-3) for every 8x8 cell
+    pub attributes: [u8; ATTRIBUTE_CELLS],
+    pub mask: SgbMask,
+    pub border: Border,
+    // Set by PAL_TRN, picked from with PAL_SET
+    system_palettes: Vec<[u16; 4]>,
+    // Set by ATTR_TRN, 2 bits per cell
+    attribute_files: Vec<[u8; ATTRIBUTE_FILE_SIZE]>,
+    players: u8,
+    current_player: u8,
+    // Packet receiver, fed by the P14/P15 lines of P1
+    lines: u8,
+    receiving: bool,
+    packet: [u8; 16],
+    bits: usize,
+    command: Vec<u8>,
+    pub last_command: Option<SgbCommand>,
+}
+
+impl Sgb {
+    pub fn new() -> Sgb {
+        Sgb {
+            palettes: [[0x7FFF, 0x56B5, 0x294A, 0x0000]; 4],
+            attributes: [0; ATTRIBUTE_CELLS],
+            mask: SgbMask::Cancel,
+            border: Border::new(),
+            system_palettes: vec![[0; 4]; SYSTEM_PALETTES],
+            attribute_files: vec![[0; ATTRIBUTE_FILE_SIZE]; ATTRIBUTE_FILES],
+            players: 1,
+            current_player: 0,
+            lines: 0x30,
+            receiving: false,
+            packet: [0; 16],
+            bits: 0,
+            command: Vec::new(),
+            last_command: None,
+        }
+    }
+
+    pub fn players(&self) -> u8 {
+        self.players
+    }
+
+    // The controller (0-3) the game reads the buttons of, only changes after MLT_REQ
+    pub fn current_player(&self) -> u8 {
+        self.current_player
+    }
+
+    // Low nibble of P1 while neither P14 nor P15 is selected: 0xF for player 1, 0xE for player 2...
+    pub fn joypad_id(&self) -> u8 {
+        0xF - self.current_player
+    }
+
+    // Final colour of a DMG shade at (x, y), `current` is what's on screen right now
+    pub fn color(&self, x: usize, y: usize, shade: u8, current: u16) -> u16 {
+        match self.mask {
+            SgbMask::Cancel => {
+                let palette = self.attributes[y / 8 * ATTRIBUTE_WIDTH + x / 8];
+                self.palettes[palette as usize & 0x3][shade as usize & 0x3]
+            }
+            SgbMask::Freeze => current,
+            SgbMask::Black => 0x0000,
+            SgbMask::Color0 => self.palettes[0][0],
+        }
+    }
+
+    // Every write to P1. Both lines low resets the receiver, then each pulse of P14 is a 0
+    // and each pulse of P15 a 1, LSB first, 128 bits per packet plus a 0 stop bit
+    pub fn write_p1(&mut self, data: u8, vram: &Vram, lcdc: Lcdc) {
+        let lines = data & 0x30;
+        match lines {
+            0x00 => {
+                self.receiving = true;
+                self.bits = 0;
+                self.packet = [0; 16];
+            }
+            0x10 | 0x20 if self.receiving && self.lines == 0x30 => {
+                if self.bits < 128 {
+                    if lines == 0x10 {
+                        self.packet[self.bits / 8] |= 1 << (self.bits % 8);
+                    }
+                    self.bits += 1;
+                } else {
+                    self.receiving = false;
+                    self.receive_packet(vram, lcdc);
+                }
+            }
+            // P15 going high moves on to the next controller
+            0x30 if !self.receiving && self.lines == 0x10 && self.players > 1 => {
+                self.current_player = (self.current_player + 1) % self.players;
+            }
+            _ => (),
+        }
+        self.lines = lines;
+    }
+
+    fn receive_packet(&mut self, vram: &Vram, lcdc: Lcdc) {
+        self.command.extend_from_slice(&self.packet);
+        // The first packet says how many more belong to the command
+        let length = (self.command[0] & 0x7).max(1) as usize;
+        if self.command.len() >= length * 16 {
+            let command = std::mem::take(&mut self.command);
+            self.execute(&command, vram, lcdc);
+        }
+    }
+
+    fn execute(&mut self, data: &[u8], vram: &Vram, lcdc: Lcdc) {
+        let command = SgbCommand::from_code(data[0] >> 3);
+        match command {
+            SgbCommand::Pal01 => self.set_palettes(data, 0, 1),
+            SgbCommand::Pal23 => self.set_palettes(data, 2, 3),
+            SgbCommand::Pal03 => self.set_palettes(data, 0, 3),
+            SgbCommand::Pal12 => self.set_palettes(data, 1, 2),
+            SgbCommand::AttrBlk => self.attr_blk(data),
+            SgbCommand::AttrLin => self.attr_lin(data),
+            SgbCommand::AttrDiv => self.attr_div(data),
+            SgbCommand::AttrChr => self.attr_chr(data),
+            SgbCommand::PalSet => self.pal_set(data),
+            SgbCommand::PalTrn => {
+                let transfer = vram_transfer(vram, lcdc);
+                for (palette, colors) in self.system_palettes.iter_mut().zip(transfer.chunks(8)) {
+                    for (i, color) in palette.iter_mut().enumerate() {
+                        *color = u16::from_le_bytes([colors[i * 2], colors[i * 2 + 1]]);
+                    }
+                }
+            }
+            SgbCommand::MltReq => {
+                self.players = match data[1] & 0x3 {
+                    1 => 2,
+                    3 => 4,
+                    _ => 1,
+                };
+                self.current_player = 0;
+            }
+            SgbCommand::ChrTrn => {
+                let transfer = vram_transfer(vram, lcdc);
+                self.border.load_tiles(data[1] & 1 != 0, &transfer);
+            }
+            SgbCommand::PctTrn => self.border.load_map(&vram_transfer(vram, lcdc)),
+            SgbCommand::AttrTrn => {
+                let transfer = vram_transfer(vram, lcdc);
+                for (file, bytes) in self
+                    .attribute_files
+                    .iter_mut()
+                    .zip(transfer.chunks(ATTRIBUTE_FILE_SIZE))
+                {
+                    file.copy_from_slice(bytes);
+                }
+            }
+            SgbCommand::AttrSet => {
+                self.load_attribute_file(data[1] & 0x3F);
+                if data[1] & 0x40 != 0 {
+                    self.mask = SgbMask::Cancel;
+                }
+            }
+            SgbCommand::MaskEn => {
+                self.mask = match data[1] & 0x3 {
+                    1 => SgbMask::Freeze,
+                    2 => SgbMask::Black,
+                    3 => SgbMask::Color0,
+                    _ => SgbMask::Cancel,
+                };
+            }
+            SgbCommand::Unsupported(_) => (),
+        }
+        self.last_command = Some(command);
+    }
+
+    // PAL01/PAL23/PAL03/PAL12: the shared colour 0 followed by colours 1-3 of both palettes
+    fn set_palettes(&mut self, data: &[u8], first: usize, second: usize) {
+        let color = |i: usize| u16::from_le_bytes([data[1 + i * 2], data[2 + i * 2]]) & 0x7FFF;
+        for palette in self.palettes.iter_mut() {
+            palette[0] = color(0);
+        }
+        for i in 1..4 {
+            self.palettes[first][i] = color(i);
+            self.palettes[second][i] = color(i + 3);
+        }
+    }
+
+    fn set_cell(&mut self, x: usize, y: usize, palette: u8) {
+        if x < ATTRIBUTE_WIDTH && y < ATTRIBUTE_HEIGHT {
+            self.attributes[y * ATTRIBUTE_WIDTH + x] = palette & 0x3;
+        }
+    }
+
+    // Rectangles, with separate palettes for the inside, the outline and the outside
+    fn attr_blk(&mut self, data: &[u8]) {
+        let count = (data[1] & 0x1F) as usize;
+        for block in data[2..].chunks_exact(6).take(count) {
+            let mut control = block[0] & 0x7;
+            let (inside, mut border, outside) =
+                (block[1] & 0x3, (block[1] >> 2) & 0x3, (block[1] >> 4) & 0x3);
+            // Only inside or only outside changes the outline along with it
+            if control == 0x1 {
+                control |= 0x2;
+                border = inside;
+            } else if control == 0x4 {
+                control |= 0x2;
+                border = outside;
+            }
+            let (x1, y1, x2, y2) = (
+                block[2] as usize,
+                block[3] as usize,
+                block[4] as usize,
+                block[5] as usize,
+            );
+            for y in 0..ATTRIBUTE_HEIGHT {
+                for x in 0..ATTRIBUTE_WIDTH {
+                    let within = (x1..=x2).contains(&x) && (y1..=y2).contains(&y);
+                    let strictly = x1 < x && x < x2 && y1 < y && y < y2;
+                    let palette = if strictly {
+                        (control & 0x1 != 0).then_some(inside)
+                    } else if within {
+                        (control & 0x2 != 0).then_some(border)
+                    } else {
+                        (control & 0x4 != 0).then_some(outside)
+                    };
+                    if let Some(palette) = palette {
+                        self.set_cell(x, y, palette);
+                    }
+                }
+            }
+        }
+    }
+
+    // Whole rows or columns of cells
+    fn attr_lin(&mut self, data: &[u8]) {
+        let count = data[1] as usize;
+        for &line in data[2..].iter().take(count) {
+            let (number, palette) = ((line & 0x1F) as usize, (line >> 5) & 0x3);
+            if line & 0x80 != 0 {
+                for x in 0..ATTRIBUTE_WIDTH {
+                    self.set_cell(x, number, palette);
+                }
+            } else {
+                for y in 0..ATTRIBUTE_HEIGHT {
+                    self.set_cell(number, y, palette);
+                }
+            }
+        }
+    }
+
+    // Splits the screen in two at a row or column, the line itself gets its own palette
+    fn attr_div(&mut self, data: &[u8]) {
+        let (after, before, line) = (data[1] & 0x3, (data[1] >> 2) & 0x3, (data[1] >> 4) & 0x3);
+        let horizontal = data[1] & 0x40 != 0;
+        let split = data[2] as usize;
+        for y in 0..ATTRIBUTE_HEIGHT {
+            for x in 0..ATTRIBUTE_WIDTH {
+                let position = if horizontal { y } else { x };
+                let palette = match position.cmp(&split) {
+                    std::cmp::Ordering::Less => before,
+                    std::cmp::Ordering::Equal => line,
+                    std::cmp::Ordering::Greater => after,
+                };
+                self.set_cell(x, y, palette);
+            }
+        }
+    }
+
+    // Cell by cell from a starting point, 2 bits each, left to right or top to bottom
+    fn attr_chr(&mut self, data: &[u8]) {
+        let (mut x, mut y) = (data[1] as usize, data[2] as usize);
+        let count = u16::from_le_bytes([data[3], data[4]]) as usize;
+        let vertical = data[5] & 1 != 0;
+        for i in 0..count.min(ATTRIBUTE_CELLS) {
+            let Some(&byte) = data.get(6 + i / 4) else {
+                break;
+            };
+            self.set_cell(x, y, byte >> (6 - (i % 4) * 2));
+            if vertical {
+                y += 1;
+                if y == ATTRIBUTE_HEIGHT {
+                    (x, y) = (x + 1, 0);
+                }
+            } else {
+                x += 1;
+                if x == ATTRIBUTE_WIDTH {
+                    (x, y) = (0, y + 1);
+                }
+            }
+        }
+    }
+
+    // Picks the 4 screen palettes from the system palettes, optionally with an attribute file
+    fn pal_set(&mut self, data: &[u8]) {
+        for i in 0..4 {
+            let index = u16::from_le_bytes([data[1 + i * 2], data[2 + i * 2]]) & 0x1FF;
+            self.palettes[i] = self.system_palettes[index as usize];
+        }
+        let color0 = self.palettes[0][0];
+        for palette in self.palettes.iter_mut() {
+            palette[0] = color0;
+        }
+        if data[9] & 0x80 != 0 {
+            self.load_attribute_file(data[9] & 0x3F);
+        }
+        if data[9] & 0x40 != 0 {
+            self.mask = SgbMask::Cancel;
+        }
+    }
+
+    fn load_attribute_file(&mut self, file: u8) {
+        let Some(file) = self.attribute_files.get(file as usize) else {
+            return;
+        };
+        for (i, cell) in self.attributes.iter_mut().enumerate() {
+            *cell = (file[i / 4] >> (6 - (i % 4) * 2)) & 0x3;
+        }
+    }
+
+    // last_command is only there to look at and isn't saved
+    pub fn save_state(&self, state: &mut StateWriter) {
+        for palette in &self.palettes {
+            state.u16s(palette);
+        }
+        state.bytes(&self.attributes);
+        state.u8(self.mask as u8);
+        self.border.save_state(state);
+        for palette in &self.system_palettes {
+            state.u16s(palette);
+        }
+        for file in &self.attribute_files {
+            state.bytes(file);
+        }
+        state.bytes(&[self.players, self.current_player, self.lines]);
+        state.bool(self.receiving);
+        state.bytes(&self.packet);
+        state.u32(self.bits as u32);
+        state.vec(&self.command);
+    }
+
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        for palette in &mut self.palettes {
+            state.u16s(palette)?;
+        }
+        state.bytes(&mut self.attributes)?;
+        self.mask = match state.u8()? {
+            0 => SgbMask::Cancel,
+            1 => SgbMask::Freeze,
+            2 => SgbMask::Black,
+            3 => SgbMask::Color0,
+            _ => return Err(StateError::Data),
+        };
+        self.border.load_state(state)?;
+        for palette in &mut self.system_palettes {
+            state.u16s(palette)?;
+        }
+        for file in &mut self.attribute_files {
+            state.bytes(file)?;
+        }
+        let mut bytes = [0; 3];
+        state.bytes(&mut bytes)?;
+        [self.players, self.current_player, self.lines] = bytes;
+        self.receiving = state.bool()?;
+        state.bytes(&mut self.packet)?;
+        self.bits = state.u32()? as usize;
+        self.command = state.vec()?;
+        let valid = self.current_player < self.players.max(1) && self.bits <= 128;
+        if !valid || self.command.len() > 7 * 16 {
+            return Err(StateError::Data);
+        }
+        Ok(())
+    }
+}
+
+impl Default for Sgb {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// The data of the first 256 tiles on screen, row by row, which is how games hand
+// the SGB the *_TRN payloads
+fn vram_transfer(vram: &Vram, lcdc: Lcdc) -> Vec<u8> {
+    let map = (lcdc.bg_tile_map() - 0x8000) as usize;
+    let mut data = Vec::with_capacity(TRANSFER_SIZE);
+    for tile in 0..TRANSFER_SIZE / 16 {
+        let (row, column) = (tile / ATTRIBUTE_WIDTH, tile % ATTRIBUTE_WIDTH);
+        let address = tile_address(lcdc, vram[0][map + row * 32 + column]);
+        data.extend_from_slice(&vram[0][address..address + 16]);
+    }
+    data
+}
@@ -0,0 +1,145 @@
+// Save states: everything the console needs to carry on from where it was, without
+// the ROM and the frontend side (video output, audio, recorders). A state only loads
+// on the catridge it was saved with.
+//
+// The file is "BBST", version, the ROM hash (u64, see rom_hash), then every part of
+// the console one after the other, all little endian
+
+pub(crate) const MAGIC: &[u8; 4] = b"BBST";
+pub(crate) const VERSION: u8 = 1;
+pub(crate) const HEADER_SIZE: usize = 0xD;
+
+#[derive(Debug, PartialEq)]
+pub enum StateError {
+    Load,
+    Save,
+    Header,
+    // The state was saved with another ROM
+    Rom,
+    // The state ends early, has bytes left over or holds a value that can't be
+    Data,
+}
+
+pub struct StateWriter {
+    pub data: Vec<u8>,
+}
+
+impl StateWriter {
+    pub fn new() -> StateWriter {
+        StateWriter { data: Vec::new() }
+    }
+
+    pub fn u8(&mut self, value: u8) {
+        self.data.push(value);
+    }
+
+    pub fn bool(&mut self, value: bool) {
+        self.data.push(value as u8);
+    }
+
+    pub fn u16(&mut self, value: u16) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn u32(&mut self, value: u32) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn u64(&mut self, value: u64) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn bytes(&mut self, bytes: &[u8]) {
+        self.data.extend_from_slice(bytes);
+    }
+
+    pub fn u16s(&mut self, values: &[u16]) {
+        for &value in values {
+            self.u16(value);
+        }
+    }
+
+    // Bytes whose length isn't fixed, with the length in front
+    pub fn vec(&mut self, bytes: &[u8]) {
+        self.u32(bytes.len() as u32);
+        self.bytes(bytes);
+    }
+}
+
+impl Default for StateWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct StateReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> StateReader<'a> {
+    pub fn new(data: &'a [u8]) -> StateReader<'a> {
+        StateReader { data, position: 0 }
+    }
+
+    pub fn finished(&self) -> bool {
+        self.position == self.data.len()
+    }
+
+    fn take(&mut self, length: usize) -> Result<&'a [u8], StateError> {
+        let end = self.position + length;
+        let bytes = self.data.get(self.position..end).ok_or(StateError::Data)?;
+        self.position = end;
+        Ok(bytes)
+    }
+
+    pub fn u8(&mut self) -> Result<u8, StateError> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn bool(&mut self) -> Result<bool, StateError> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(StateError::Data),
+        }
+    }
+
+    pub fn u16(&mut self) -> Result<u16, StateError> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    pub fn u32(&mut self) -> Result<u32, StateError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    pub fn u64(&mut self) -> Result<u64, StateError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    // Fills `to` completely
+    pub fn bytes(&mut self, to: &mut [u8]) -> Result<(), StateError> {
+        to.copy_from_slice(self.take(to.len())?);
+        Ok(())
+    }
+
+    pub fn u16s(&mut self, to: &mut [u16]) -> Result<(), StateError> {
+        for value in to {
+            *value = self.u16()?;
+        }
+        Ok(())
+    }
+
+    pub fn vec(&mut self) -> Result<Vec<u8>, StateError> {
+        let length = self.u32()? as usize;
+        Ok(self.take(length)?.to_vec())
+    }
+
+    // A vec that has to come back with the length it was saved with
+    pub fn vec_into(&mut self, to: &mut [u8]) -> Result<(), StateError> {
+        if self.u32()? as usize != to.len() {
+            return Err(StateError::Data);
+        }
+        self.bytes(to)
+    }
+}
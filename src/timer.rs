@@ -0,0 +1,118 @@
+use crate::io::{Interrupt, Io, Tac};
+use crate::state::{StateError, StateReader, StateWriter};
+
+// DIV is the upper byte of a 16 bit counter that goes up every T-cycle.
+// TIMA goes up on the falling edge of (TAC enable AND the counter bit selected by TAC),
+// which is why resetting DIV or changing TAC can bump TIMA as well
+pub struct Timer {
+    pub counter: u16,
+    pub tima: u8,
+    pub tma: u8,
+    pub tac: u8,
+    // TIMA overflowed during the last M-cycle and reads as 0 until TMA gets reloaded
+    overflow: bool,
+    // TMA was loaded into TIMA during the current M-cycle
+    reloading: bool,
+}
+
+impl Timer {
+    pub fn new() -> Timer {
+        Timer {
+            counter: 0,
+            tima: 0,
+            tma: 0,
+            tac: 0,
+            overflow: false,
+            reloading: false,
+        }
+    }
+
+    fn signal(&self) -> bool {
+        let tac = Tac(self.tac);
+        tac.enabled() && (self.counter >> tac.counter_bit()) & 1 == 1
+    }
+
+    fn detect_falling_edge(&mut self, old_signal: bool) {
+        if old_signal && !self.signal() {
+            let (value, overflow) = self.tima.overflowing_add(1);
+            self.tima = value;
+            if overflow {
+                self.overflow = true;
+            }
+        }
+    }
+
+    // Advances the timer by one M-cycle
+    pub fn step(&mut self, io: &mut Io) {
+        self.reloading = false;
+        if self.overflow {
+            self.overflow = false;
+            self.reloading = true;
+            self.tima = self.tma;
+            io.request_interrupt(Interrupt::Timer);
+        }
+        let old_signal = self.signal();
+        self.counter = self.counter.wrapping_add(4);
+        self.detect_falling_edge(old_signal);
+    }
+
+    pub fn read(&self, address: u16) -> u8 {
+        match address {
+            0xFF04 => (self.counter >> 8) as u8,
+            0xFF05 => self.tima,
+            0xFF06 => self.tma,
+            0xFF07 => self.tac,
+            _ => 0xFF,
+        }
+    }
+
+    pub fn write(&mut self, address: u16, data: u8) {
+        match address {
+            0xFF04 => {
+                let old_signal = self.signal();
+                self.counter = 0;
+                self.detect_falling_edge(old_signal);
+            }
+            // A write on the reload cycle loses against TMA, a write before it cancels the reload
+            0xFF05 if !self.reloading => {
+                self.tima = data;
+                self.overflow = false;
+            }
+            0xFF06 => {
+                self.tma = data;
+                if self.reloading {
+                    self.tima = data;
+                }
+            }
+            0xFF07 => {
+                let old_signal = self.signal();
+                self.tac = data & 0x7;
+                self.detect_falling_edge(old_signal);
+            }
+            _ => (),
+        }
+    }
+
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.u16(self.counter);
+        state.bytes(&[self.tima, self.tma, self.tac]);
+        state.bool(self.overflow);
+        state.bool(self.reloading);
+    }
+
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        self.counter = state.u16()?;
+        let mut bytes = [0; 3];
+        state.bytes(&mut bytes)?;
+        [self.tima, self.tma, self.tac] = bytes;
+        self.overflow = state.bool()?;
+        self.reloading = state.bool()?;
+        Ok(())
+    }
+}
+
+impl Default for Timer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
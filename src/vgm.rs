@@ -0,0 +1,112 @@
+use std::fs::File;
+use std::io::{BufWriter, Result, Seek, SeekFrom, Write};
+
+const CLOCK_RATE: u64 = 4194304;
+const HEADER_SIZE: usize = 0x100;
+const VERSION: u32 = 0x171;
+// Game Boy DMG register write: register (address - 0xFF10), data
+const WRITE: u8 = 0xB3;
+const WAIT: u8 = 0x61;
+const WAIT_60HZ: u8 = 0x62;
+const WAIT_50HZ: u8 = 0x63;
+// 0x70 + n waits n + 1 samples
+const WAIT_SHORT: u8 = 0x70;
+const END: u8 = 0x66;
+
+// VGM 1.71 log of the DMG APU, one command per register write with waits in between.
+// VGM counts time in 44100 Hz samples, writes land on the sample they happened in.
+// The header sizes are only known at the end, like in WavWriter
+pub struct VgmWriter<W: Write + Seek> {
+    writer: W,
+    // Cycle the log starts at
+    start: u64,
+    // Samples waited so far
+    samples: u64,
+    // Bytes written after the header
+    length: u32,
+}
+
+impl VgmWriter<BufWriter<File>> {
+    pub fn create(filename: &str, cycle: u64) -> Result<Self> {
+        VgmWriter::new(BufWriter::new(File::create(filename)?), cycle)
+    }
+}
+
+impl<W: Write + Seek> VgmWriter<W> {
+    pub const SAMPLE_RATE: u64 = 44100;
+
+    // `cycle` is the T-cycle count (see Memory::cycles) the log starts at
+    pub fn new(writer: W, cycle: u64) -> Result<Self> {
+        let mut vgm = VgmWriter {
+            writer,
+            start: cycle,
+            samples: 0,
+            length: 0,
+        };
+        vgm.write_header()?;
+        Ok(vgm)
+    }
+
+    pub fn samples(&self) -> u64 {
+        self.samples
+    }
+
+    // Logs a write to 0xFF10-0xFF3F that happened at `cycle`
+    pub fn write(&mut self, cycle: u64, address: u16, data: u8) -> Result<()> {
+        self.wait_until(cycle)?;
+        self.write_bytes(&[WRITE, (address - 0xFF10) as u8, data])
+    }
+
+    // Waits up to the sample `cycle` falls in
+    pub fn wait_until(&mut self, cycle: u64) -> Result<()> {
+        let sample = cycle.saturating_sub(self.start) * Self::SAMPLE_RATE / CLOCK_RATE;
+        let mut wait = sample.saturating_sub(self.samples);
+        self.samples += wait;
+        while wait > 0 {
+            let (command, samples): (&[u8], u64) = match wait {
+                1..=16 => (&[WAIT_SHORT + wait as u8 - 1], wait),
+                735 => (&[WAIT_60HZ], wait),
+                882 => (&[WAIT_50HZ], wait),
+                _ => {
+                    let samples = wait.min(0xFFFF);
+                    let [low, high] = (samples as u16).to_le_bytes();
+                    (&[WAIT, low, high], samples)
+                }
+            };
+            self.write_bytes(command)?;
+            wait -= samples;
+        }
+        Ok(())
+    }
+
+    // Ends the log at `cycle`
+    pub fn finish(mut self, cycle: u64) -> Result<W> {
+        self.wait_until(cycle)?;
+        self.write_bytes(&[END])?;
+        self.writer.seek(SeekFrom::Start(0))?;
+        self.write_header()?;
+        self.writer.seek(SeekFrom::End(0))?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<()> {
+        self.length += bytes.len() as u32;
+        self.writer.write_all(bytes)
+    }
+
+    fn write_header(&mut self) -> Result<()> {
+        let mut header = [0u8; HEADER_SIZE];
+        let mut put = |offset: usize, value: u32| {
+            header[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+        };
+        put(0x04, HEADER_SIZE as u32 + self.length - 4);
+        put(0x08, VERSION);
+        put(0x18, self.samples as u32);
+        // Offsets count from their own field
+        put(0x34, HEADER_SIZE as u32 - 0x34);
+        put(0x80, CLOCK_RATE as u32);
+        header[0..4].copy_from_slice(b"Vgm ");
+        self.writer.write_all(&header)
+    }
+}
@@ -0,0 +1,43 @@
+// Ways of turning CGB RGB555 colours into RGB888. The CGB LCD is washed out and mixes the
+// channels a bit, so showing the raw values makes CGB games look oversaturated
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ColorCorrection {
+    // Plain 5 to 8 bit expansion
+    Raw,
+    // The channel mix from Gambatte
+    Gambatte,
+    // The mix from higan, a bit darker and less saturated
+    Higan,
+}
+
+impl ColorCorrection {
+    pub fn from_name(name: &str) -> Option<ColorCorrection> {
+        match name.to_ascii_lowercase().as_str() {
+            "raw" | "none" => Some(ColorCorrection::Raw),
+            "gambatte" => Some(ColorCorrection::Gambatte),
+            "higan" => Some(ColorCorrection::Higan),
+            _ => None,
+        }
+    }
+
+    // Returns the colour as 0xRRGGBB
+    pub fn apply(&self, color: u16) -> u32 {
+        let r = (color & 0x1F) as u32;
+        let g = ((color >> 5) & 0x1F) as u32;
+        let b = ((color >> 10) & 0x1F) as u32;
+        let (r, g, b) = match self {
+            ColorCorrection::Raw => (r << 3 | r >> 2, g << 3 | g >> 2, b << 3 | b >> 2),
+            ColorCorrection::Gambatte => (
+                (r * 13 + g * 2 + b) >> 1,
+                (g * 3 + b) << 1,
+                (r * 3 + g * 2 + b * 11) >> 1,
+            ),
+            ColorCorrection::Higan => (
+                (r * 26 + g * 4 + b * 2).min(960) >> 2,
+                (g * 24 + b * 8).min(960) >> 2,
+                (r * 6 + g * 4 + b * 22).min(960) >> 2,
+            ),
+        };
+        r << 16 | g << 8 | b
+    }
+}
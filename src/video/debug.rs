@@ -0,0 +1,263 @@
+use std::fmt::Write;
+
+use super::screenshot::write_png;
+use super::{VideoError, VideoOutput};
+use crate::memory::Memory;
+use crate::ppu::cgb::BgAttributes;
+use crate::ppu::scanline::{shade, tile_address, tile_color};
+use crate::ppu::{Sprite, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+// What the tile sheet gets coloured with
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum DebugPalette {
+    // Colour indexes straight to the DMG shades, ignoring BGP/OBP
+    Shades,
+    Bgp,
+    Obp0,
+    Obp1,
+    // One of the 8 CGB palettes
+    CgbBackground(u8),
+    CgbObject(u8),
+}
+
+// Drawn over the tile maps where the screen is
+pub const VIEWPORT_COLOR: u32 = 0xFF0000;
+// Behind transparent sprite pixels in the OAM view
+pub const BACKDROP_COLOR: u32 = 0x808080;
+
+// 0xRRGGBB pixels, row by row
+pub struct DebugImage {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u32>,
+}
+
+impl DebugImage {
+    pub fn new(width: usize, height: usize, color: u32) -> DebugImage {
+        DebugImage {
+            width,
+            height,
+            pixels: vec![color; width * height],
+        }
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> u32 {
+        self.pixels[y * self.width + x]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, color: u32) {
+        self.pixels[y * self.width + x] = color;
+    }
+
+    pub fn rgba(&self) -> Vec<u8> {
+        let mut rgba = Vec::with_capacity(self.pixels.len() * 4);
+        for color in &self.pixels {
+            rgba.extend_from_slice(&[(color >> 16) as u8, (color >> 8) as u8, *color as u8, 0xFF]);
+        }
+        rgba
+    }
+
+    pub fn save(&self, filename: &str) -> Result<(), VideoError> {
+        write_png(
+            filename,
+            self.width as u32,
+            self.height as u32,
+            &self.rgba(),
+        )
+    }
+}
+
+impl VideoOutput {
+    fn debug_color(&self, memory: &Memory, palette: DebugPalette, color: u8) -> u32 {
+        let dmg = |register: u16| {
+            let shade = shade(memory.io.get(register), color);
+            self.dmg_palette.colors()[shade as usize]
+        };
+        let palettes = &memory.ppu.palettes;
+        match palette {
+            DebugPalette::Shades => self.dmg_palette.colors()[color as usize],
+            DebugPalette::Bgp => dmg(0xFF47),
+            DebugPalette::Obp0 => dmg(0xFF48),
+            DebugPalette::Obp1 => dmg(0xFF49),
+            DebugPalette::CgbBackground(p) => self
+                .correction
+                .apply(palettes.background_color(p & 0x7, color)),
+            DebugPalette::CgbObject(p) => {
+                self.correction.apply(palettes.object_color(p & 0x7, color))
+            }
+        }
+    }
+
+    // All 384 tiles, 16 per row. On the CGB bank 1 goes to the right of bank 0
+    pub fn tile_sheet(&self, memory: &Memory, palette: DebugPalette) -> DebugImage {
+        let banks = if memory.cgb_mode { 2 } else { 1 };
+        let mut image = DebugImage::new(128 * banks, 192, 0);
+        for bank in 0..banks {
+            for tile in 0..384 {
+                let (left, top) = (bank * 128 + tile % 16 * 8, tile / 16 * 8);
+                for y in 0..8 {
+                    for x in 0..8 {
+                        let color = tile_color(&memory.vram[bank], tile * 16, x as u8, y as u8);
+                        let rgb = self.debug_color(memory, palette, color);
+                        image.set(left + x, top + y, rgb);
+                    }
+                }
+            }
+        }
+        image
+    }
+
+    // The whole 256x256 tile map at 0x9800 or 0x9C00, using the current LCDC tile data
+    // addressing and CGB attributes, with the SCX/SCY viewport outlined
+    pub fn tile_map(&self, memory: &Memory, map: u16) -> DebugImage {
+        let lcdc = memory.io.lcdc();
+        let mut image = DebugImage::new(256, 256, 0);
+        for row in 0..32 {
+            for column in 0..32 {
+                let offset = (map - 0x8000) as usize + row * 32 + column;
+                let tile = memory.vram[0][offset];
+                let attributes = if memory.cgb_mode {
+                    BgAttributes(memory.vram[1][offset])
+                } else {
+                    BgAttributes::default()
+                };
+                let palette = if memory.cgb_mode {
+                    DebugPalette::CgbBackground(attributes.palette())
+                } else {
+                    DebugPalette::Bgp
+                };
+                for y in 0..8 {
+                    for x in 0..8 {
+                        let px = if attributes.x_flip() { 7 - x } else { x };
+                        let py = if attributes.y_flip() { 7 - y } else { y };
+                        let bank = &memory.vram[attributes.bank()];
+                        let color = tile_color(bank, tile_address(lcdc, tile), px, py);
+                        let rgb = self.debug_color(memory, palette, color);
+                        image.set(column * 8 + x as usize, row * 8 + y as usize, rgb);
+                    }
+                }
+            }
+        }
+
+        // The screen wraps around the edges of the map
+        let (scx, scy) = (
+            memory.io.get(0xFF43) as usize,
+            memory.io.get(0xFF42) as usize,
+        );
+        for x in 0..SCREEN_WIDTH {
+            image.set((scx + x) % 256, scy, VIEWPORT_COLOR);
+            image.set(
+                (scx + x) % 256,
+                (scy + SCREEN_HEIGHT - 1) % 256,
+                VIEWPORT_COLOR,
+            );
+        }
+        for y in 0..SCREEN_HEIGHT {
+            image.set(scx, (scy + y) % 256, VIEWPORT_COLOR);
+            image.set(
+                (scx + SCREEN_WIDTH - 1) % 256,
+                (scy + y) % 256,
+                VIEWPORT_COLOR,
+            );
+        }
+        image
+    }
+
+    // Thumbnails of the 40 OAM entries, 8 per row, in cells of 10x18 pixels.
+    // Sprites are drawn at the current LCDC sprite height, with their flips and palette
+    pub fn oam_sheet(&self, memory: &Memory) -> DebugImage {
+        let height = memory.io.lcdc().sprite_height() as usize;
+        let mut image = DebugImage::new(8 * 10, 5 * 18, 0);
+        for sprite in oam_entries(memory) {
+            let (left, top) = (
+                sprite.index as usize % 8 * 10,
+                sprite.index as usize / 8 * 18,
+            );
+            for y in 0..16 {
+                for x in 0..8 {
+                    image.set(left + 1 + x, top + 1 + y, BACKDROP_COLOR);
+                }
+            }
+            let tile = if height == 16 {
+                sprite.tile & 0xFE
+            } else {
+                sprite.tile
+            } as usize;
+            let (bank, palette) = if memory.cgb_mode {
+                let bank = (sprite.flags >> 3) as usize & 1;
+                (bank, DebugPalette::CgbObject(sprite.flags & 0x7))
+            } else if sprite.flags & 0x10 != 0 {
+                (0, DebugPalette::Obp1)
+            } else {
+                (0, DebugPalette::Obp0)
+            };
+            for y in 0..height {
+                for x in 0..8 {
+                    let px = if sprite.flags & 0x20 != 0 { 7 - x } else { x };
+                    let py = if sprite.flags & 0x40 != 0 {
+                        height - 1 - y
+                    } else {
+                        y
+                    };
+                    // The bottom half of a 8x16 sprite is the tile right after it
+                    let color = tile_color(&memory.vram[bank], tile * 16, px as u8, py as u8);
+                    if color != 0 {
+                        let rgb = self.debug_color(memory, palette, color);
+                        image.set(left + 1 + x, top + 1 + y, rgb);
+                    }
+                }
+            }
+        }
+        image
+    }
+}
+
+pub fn oam_entries(memory: &Memory) -> Vec<Sprite> {
+    let height = memory.io.lcdc().sprite_height();
+    memory.data[0xFE00..0xFEA0]
+        .chunks(4)
+        .enumerate()
+        .map(|(index, entry)| Sprite {
+            y: entry[0],
+            x: entry[1],
+            tile: entry[2],
+            flags: entry[3],
+            index: index as u8,
+            height,
+        })
+        .collect()
+}
+
+// One line per OAM entry, with the position on screen and the decoded flags
+pub fn oam_table(memory: &Memory) -> String {
+    let mut table = String::from(" #   Y   X  screen     tile flags pal bank xflip yflip behind\n");
+    for sprite in oam_entries(memory) {
+        let palette = if memory.cgb_mode {
+            sprite.flags & 0x7
+        } else {
+            (sprite.flags >> 4) & 1
+        };
+        let bank = if memory.cgb_mode {
+            (sprite.flags >> 3) & 1
+        } else {
+            0
+        };
+        let _ = writeln!(
+            table,
+            "{:2} {:3} {:3} {:4},{:<4}  0x{:02X} 0x{:02X}  {}   {}    {:5} {:5} {}",
+            sprite.index,
+            sprite.y,
+            sprite.x,
+            sprite.x as i16 - 8,
+            sprite.y as i16 - 16,
+            sprite.tile,
+            sprite.flags,
+            palette,
+            bank,
+            sprite.flags & 0x20 != 0,
+            sprite.flags & 0x40 != 0,
+            sprite.flags & 0x80 != 0,
+        );
+    }
+    table
+}
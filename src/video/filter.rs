@@ -0,0 +1,111 @@
+use std::collections::VecDeque;
+
+// Blends the last frames together like the slow DMG LCD does, so sprites flickered on
+// alternate frames show up as see-through instead of blinking
+#[derive(Clone, Debug)]
+pub struct Ghosting {
+    // Newest frame first
+    weights: Vec<f32>,
+    history: VecDeque<Vec<u32>>,
+    last_frame: Option<u64>,
+}
+
+impl Ghosting {
+    pub fn new(weights: &[f32]) -> Ghosting {
+        Ghosting {
+            weights: weights.to_vec(),
+            history: VecDeque::with_capacity(weights.len()),
+            last_frame: None,
+        }
+    }
+
+    // Every older frame weighs `persistence` times the one after it
+    pub fn persistence(frames: usize, persistence: f32) -> Ghosting {
+        let weights: Vec<f32> = (0..frames.max(1))
+            .map(|i| persistence.powi(i as i32))
+            .collect();
+        Ghosting::new(&weights)
+    }
+
+    pub fn weights(&self) -> &[f32] {
+        &self.weights
+    }
+
+    pub fn clear(&mut self) {
+        self.history.clear();
+        self.last_frame = None;
+    }
+
+    // `frame_number` identifies the PPU frame, asking again for the same frame
+    // replaces it instead of pushing the history further
+    pub fn apply(&mut self, frame_number: u64, frame: Vec<u32>) -> Vec<u32> {
+        if self.last_frame == Some(frame_number) {
+            self.history.pop_front();
+        }
+        self.last_frame = Some(frame_number);
+        self.history.push_front(frame);
+        self.history.truncate(self.weights.len().max(1));
+
+        let total: f32 = self.weights.iter().take(self.history.len()).sum();
+        if self.history.len() == 1 || total <= 0.0 {
+            return self.history[0].clone();
+        }
+        let mut blended = vec![0; self.history[0].len()];
+        for (i, pixel) in blended.iter_mut().enumerate() {
+            let mut channels = [0.0f32; 3];
+            for (frame, weight) in self.history.iter().zip(&self.weights) {
+                for (c, channel) in channels.iter_mut().enumerate() {
+                    *channel += ((frame[i] >> (16 - c * 8)) & 0xFF) as f32 * weight;
+                }
+            }
+            *pixel = channels.iter().fold(0, |color, channel| {
+                color << 8 | (channel / total).round().clamp(0.0, 255.0) as u32
+            });
+        }
+        blended
+    }
+}
+
+// Scales every pixel up to a `scale` x `scale` block with the last row and column
+// blended towards the gap colour, like the lines between the DMG LCD's pixels
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct LcdGrid {
+    pub scale: usize,
+    // 0.0 hides the grid, 1.0 draws the gaps in the gap colour
+    pub strength: f32,
+}
+
+impl LcdGrid {
+    pub fn new(scale: usize) -> LcdGrid {
+        LcdGrid {
+            scale: scale.max(1),
+            strength: 0.5,
+        }
+    }
+
+    pub fn apply(&self, frame: &[u32], width: usize, gap: u32) -> Vec<u32> {
+        let scale = self.scale.max(1);
+        let height = frame.len() / width;
+        let mut output = vec![0; frame.len() * scale * scale];
+        for y in 0..height * scale {
+            for x in 0..width * scale {
+                let color = frame[y / scale * width + x / scale];
+                let edge = scale > 1 && (x % scale == scale - 1 || y % scale == scale - 1);
+                output[y * width * scale + x] = if edge {
+                    mix(color, gap, self.strength)
+                } else {
+                    color
+                };
+            }
+        }
+        output
+    }
+}
+
+fn mix(a: u32, b: u32, amount: f32) -> u32 {
+    (0..3).fold(0, |color, c| {
+        let shift = 16 - c * 8;
+        let (a, b) = (((a >> shift) & 0xFF) as f32, ((b >> shift) & 0xFF) as f32);
+        color << 8 | (a + (b - a) * amount.clamp(0.0, 1.0)).round() as u32
+    })
+}
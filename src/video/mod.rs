@@ -0,0 +1,145 @@
+pub mod correction;
+pub mod debug;
+pub mod filter;
+pub mod palette;
+pub mod recorder;
+pub mod scaler;
+pub mod screenshot;
+pub mod terminal;
+
+use crate::ppu::{Ppu, SCREEN_HEIGHT, SCREEN_WIDTH};
+use crate::sgb::border::{BORDER_HEIGHT, BORDER_WIDTH, SCREEN_X, SCREEN_Y};
+use correction::ColorCorrection;
+use filter::{Ghosting, LcdGrid};
+use palette::DmgPalette;
+use scaler::Scaler;
+
+#[derive(Debug)]
+pub enum VideoError {
+    Load,
+    Save,
+    Palette,
+}
+
+// Turns the PPU framebuffers into RGBA. DMG games go through a DMG palette preset,
+// CGB games (and DMG games using the CGB compatibility palettes) through a colour correction.
+// Super Game Boy games show their own colours as they are, optionally inside the border.
+// After that come the optional ghosting filter, upscaler and LCD grid, in that order
+pub struct VideoOutput {
+    pub dmg_palette: DmgPalette,
+    pub correction: ColorCorrection,
+    // Frames are 256x224 with the SGB border around the game, a black one outside SGB mode
+    pub sgb_border: bool,
+    pub ghosting: Option<Ghosting>,
+    pub scaler: Option<Scaler>,
+    pub grid: Option<LcdGrid>,
+}
+
+impl VideoOutput {
+    pub const FRAME_SIZE: usize = SCREEN_WIDTH * SCREEN_HEIGHT * 4;
+
+    pub fn new() -> VideoOutput {
+        VideoOutput {
+            dmg_palette: DmgPalette::Grey,
+            correction: ColorCorrection::Gambatte,
+            sgb_border: false,
+            ghosting: None,
+            scaler: None,
+            grid: None,
+        }
+    }
+
+    // Size of the frames coming out of rgb, before any filter
+    pub fn base_size(&self) -> (usize, usize) {
+        if self.sgb_border {
+            (BORDER_WIDTH, BORDER_HEIGHT)
+        } else {
+            (SCREEN_WIDTH, SCREEN_HEIGHT)
+        }
+    }
+
+    // Size of the frames coming out of render and rgba
+    pub fn size(&self) -> (usize, usize) {
+        let scale = self.scaler.map_or(1, |scaler| scaler.factor())
+            * self.grid.map_or(1, |grid| grid.scale.max(1));
+        let (width, height) = self.base_size();
+        (width * scale, height * scale)
+    }
+
+    // Returns 0xRRGGBB for every pixel on screen, and the border in SGB mode with sgb_border set
+    pub fn rgb(&self, ppu: &Ppu) -> Vec<u32> {
+        if let Some(sgb) = &ppu.sgb {
+            let raw = |&color: &u16| ColorCorrection::Raw.apply(color);
+            return if self.sgb_border {
+                let backdrop = sgb.palettes[0][0];
+                let border = sgb.border.compose(&ppu.color_framebuffer, backdrop);
+                border.iter().map(raw).collect()
+            } else {
+                ppu.color_framebuffer.iter().map(raw).collect()
+            };
+        }
+        if self.sgb_border {
+            // Without an SGB the game sits on a black border
+            let mut frame = vec![0x000000; BORDER_WIDTH * BORDER_HEIGHT];
+            for (y, row) in self.screen_rgb(ppu).chunks(SCREEN_WIDTH).enumerate() {
+                let start = (SCREEN_Y + y) * BORDER_WIDTH + SCREEN_X;
+                frame[start..start + SCREEN_WIDTH].copy_from_slice(row);
+            }
+            return frame;
+        }
+        self.screen_rgb(ppu)
+    }
+
+    fn screen_rgb(&self, ppu: &Ppu) -> Vec<u32> {
+        if ppu.cgb_mode || ppu.compat_palette {
+            ppu.color_framebuffer
+                .iter()
+                .map(|&color| self.correction.apply(color))
+                .collect()
+        } else {
+            let colors = self.dmg_palette.colors();
+            ppu.framebuffer
+                .iter()
+                .map(|&shade| colors[shade as usize & 0x3])
+                .collect()
+        }
+    }
+
+    // The RGB frame through the filters, at size()
+    pub fn render(&mut self, ppu: &Ppu) -> Vec<u32> {
+        let mut frame = self.rgb(ppu);
+        if let Some(ghosting) = &mut self.ghosting {
+            frame = ghosting.apply(ppu.frames, frame);
+        }
+        let (mut width, height) = self.base_size();
+        if let Some(scaler) = self.scaler {
+            frame = scaler.apply(&frame, width, height);
+            width *= scaler.factor();
+        }
+        if let Some(grid) = self.grid {
+            // The gaps between the DMG's pixels show the unlit LCD, the CGB's are dark
+            let gap = if ppu.cgb_mode || ppu.compat_palette || ppu.sgb.is_some() {
+                0x000000
+            } else {
+                self.dmg_palette.colors()[0]
+            };
+            frame = grid.apply(&frame, width, gap);
+        }
+        frame
+    }
+
+    pub fn rgba(&mut self, ppu: &Ppu) -> Vec<u8> {
+        let (width, height) = self.size();
+        let mut frame = Vec::with_capacity(width * height * 4);
+        for color in self.render(ppu) {
+            frame.extend_from_slice(&[(color >> 16) as u8, (color >> 8) as u8, color as u8, 0xFF]);
+        }
+        frame
+    }
+}
+
+impl Default for VideoOutput {
+    fn default() -> Self {
+        Self::new()
+    }
+}
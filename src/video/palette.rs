@@ -0,0 +1,336 @@
+use super::VideoError;
+use crate::ppu::Ppu;
+use crate::rom::Catridge;
+
+// Colours are stored as 0xRRGGBB
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum DmgPalette {
+    Grey,
+    // The original DMG pea-green LCD
+    PeaGreen,
+    Pocket,
+    Light,
+    Custom([u32; 4]),
+}
+
+impl DmgPalette {
+    pub const PRESETS: [DmgPalette; 4] = [
+        DmgPalette::Grey,
+        DmgPalette::PeaGreen,
+        DmgPalette::Pocket,
+        DmgPalette::Light,
+    ];
+
+    // From the lightest shade (0) to the darkest (3)
+    pub fn colors(&self) -> [u32; 4] {
+        match self {
+            DmgPalette::Grey => [0xFFFFFF, 0xAAAAAA, 0x555555, 0x000000],
+            DmgPalette::PeaGreen => [0x9BBC0F, 0x8BAC0F, 0x306230, 0x0F380F],
+            DmgPalette::Pocket => [0xC4CFA1, 0x8B956D, 0x4D533C, 0x1F1F1F],
+            DmgPalette::Light => [0x00B581, 0x009A71, 0x00694A, 0x004F3B],
+            DmgPalette::Custom(colors) => *colors,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<DmgPalette> {
+        match name.to_ascii_lowercase().as_str() {
+            "grey" | "gray" => Some(DmgPalette::Grey),
+            "green" | "pea-green" | "dmg" => Some(DmgPalette::PeaGreen),
+            "pocket" => Some(DmgPalette::Pocket),
+            "light" => Some(DmgPalette::Light),
+            _ => None,
+        }
+    }
+
+    // A palette file holds 4 hex colours (RRGGBB, optionally starting with #) from lightest
+    // to darkest, separated by whitespace. Anything after a ; is a comment
+    pub fn load(filename: &str) -> Result<DmgPalette, VideoError> {
+        let text = std::fs::read_to_string(filename).map_err(|_| VideoError::Load)?;
+        Self::parse(&text)
+    }
+
+    pub fn parse(text: &str) -> Result<DmgPalette, VideoError> {
+        let mut colors = Vec::with_capacity(4);
+        for line in text.lines() {
+            let line = line.split(';').next().unwrap_or("");
+            for word in line.split_whitespace() {
+                let hex = word.trim_start_matches('#');
+                if hex.len() != 6 {
+                    return Err(VideoError::Palette);
+                }
+                let color = u32::from_str_radix(hex, 16).map_err(|_| VideoError::Palette)?;
+                colors.push(color);
+            }
+        }
+        match colors.try_into() {
+            Ok(colors) => Ok(DmgPalette::Custom(colors)),
+            Err(_) => Err(VideoError::Palette),
+        }
+    }
+}
+
+// The BG, OBJ0 and OBJ1 palettes the CGB boot ROM sets up when it runs a DMG game
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct CompatPalette {
+    pub background: [u32; 4],
+    pub object0: [u32; 4],
+    pub object1: [u32; 4],
+}
+
+// The boot ROM's colour table, 30 palettes of 4 RGB555 colours
+const BOOT_COLORS: [u16; 120] = [
+    0x7FFF, 0x32BF, 0x00D0, 0x0000, 0x639F, 0x4279, 0x15B0, 0x04CB, 0x7FFF, 0x6E31, 0x454A, 0x0000,
+    0x7FFF, 0x1BEF, 0x0200, 0x0000, 0x7FFF, 0x421F, 0x1CF2, 0x0000, 0x7FFF, 0x5294, 0x294A, 0x0000,
+    0x7FFF, 0x03FF, 0x012F, 0x0000, 0x7FFF, 0x03EF, 0x01D6, 0x0000, 0x7FFF, 0x42B5, 0x3DC8, 0x0000,
+    0x7E74, 0x03FF, 0x0180, 0x0000, 0x67FF, 0x77AC, 0x1A13, 0x2D6B, 0x7ED6, 0x4BFF, 0x2175, 0x0000,
+    0x53FF, 0x4A5F, 0x7E52, 0x0000, 0x4FFF, 0x7ED2, 0x3A4C, 0x1CE0, 0x03ED, 0x7FFF, 0x255F, 0x0000,
+    0x036A, 0x021F, 0x03FF, 0x7FFF, 0x7FFF, 0x01DF, 0x0112, 0x0000, 0x231F, 0x035F, 0x00F2, 0x0009,
+    0x7FFF, 0x03EA, 0x011F, 0x0000, 0x299F, 0x001A, 0x000C, 0x0000, 0x7FFF, 0x027F, 0x001F, 0x0000,
+    0x7FFF, 0x03E0, 0x0206, 0x0120, 0x7FFF, 0x7EEB, 0x001F, 0x7C00, 0x7FFF, 0x3FFF, 0x7E00, 0x001F,
+    0x7FFF, 0x03FF, 0x001F, 0x0000, 0x03FF, 0x001F, 0x000C, 0x0000, 0x7FFF, 0x033F, 0x0193, 0x0000,
+    0x0000, 0x4200, 0x037F, 0x7FFF, 0x7FFF, 0x7E8C, 0x7C00, 0x0000, 0x7FFF, 0x1BEF, 0x6180, 0x0000,
+];
+
+// OBJ0, OBJ1 and BG of every palette index, as the index of their first colour in
+// BOOT_COLORS. Most start on a palette, a few shuffled ones start on the last colour of
+// the one before
+const COMBINATIONS: [(u8, u8, u8); 51] = [
+    (16, 16, 116),   // 0, Right + A
+    (72, 72, 72),    // 1, Right
+    (80, 80, 80),    // 2
+    (96, 96, 96),    // 3, Down + A
+    (36, 36, 36),    // 4
+    (0, 0, 0),       // 5, Up
+    (108, 108, 108), // 6, Right + B
+    (20, 20, 20),    // 7, Left + B
+    (48, 48, 48),    // 8, Down
+    (104, 104, 104), // 9
+    (64, 32, 32),    // 10
+    (16, 112, 112),  // 11
+    (16, 8, 8),      // 12
+    (12, 16, 16),    // 13
+    (16, 116, 116),  // 14
+    (112, 16, 112),  // 15
+    (8, 68, 8),      // 16
+    (64, 64, 32),    // 17
+    (16, 16, 28),    // 18
+    (16, 16, 72),    // 19
+    (16, 16, 80),    // 20
+    (76, 76, 36),    // 21
+    (15, 15, 44),    // 22
+    (68, 68, 8),     // 23
+    (16, 16, 8),     // 24
+    (16, 16, 12),    // 25
+    (112, 112, 0),   // 26
+    (12, 12, 0),     // 27
+    (0, 0, 4),       // 28, Up + B
+    (72, 88, 72),    // 29
+    (80, 88, 80),    // 30
+    (96, 88, 96),    // 31
+    (64, 88, 32),    // 32
+    (68, 16, 52),    // 33
+    (111, 0, 56),    // 34
+    (111, 16, 60),   // 35
+    (76, 88, 36),    // 36
+    (64, 112, 40),   // 37
+    (16, 92, 112),   // 38
+    (68, 88, 8),     // 39
+    (16, 0, 8),      // 40, Left + A
+    (16, 112, 12),   // 41
+    (112, 12, 0),    // 42
+    (12, 112, 16),   // 43, Up + A
+    (84, 112, 16),   // 44
+    (12, 112, 0),    // 45
+    (100, 12, 112),  // 46
+    (0, 112, 32),    // 47
+    (16, 12, 112),   // 48, Left
+    (112, 12, 24),   // 49, Down + B
+    (16, 112, 116),  // 50
+];
+
+// Title checksum and palette index of the games the boot ROM knows
+const TITLES: [(u8, u8); 65] = [
+    (0x00, 0),  // Default
+    (0x88, 4),  // ALLEY WAY
+    (0x16, 5),  // YAKUMAN
+    (0x36, 35), // BASEBALL, GAME&WATCH 2
+    (0xD1, 34), // TENNIS
+    (0xDB, 3),  // TETRIS
+    (0xF2, 31), // QIX
+    (0x3C, 15), // DR.MARIO
+    (0x8C, 10), // RADARMISSION
+    (0x92, 5),  // F1RACE
+    (0x3D, 19), // YOSSY NO TAMAGO
+    (0x5C, 36),
+    (0x58, 7),  // X
+    (0xC9, 37), // MARIOLAND2
+    (0x3E, 30), // YOSSY NO COOKIE
+    (0x70, 44), // ZELDA
+    (0x1D, 21),
+    (0x59, 32),
+    (0x69, 31), // TETRIS FLASH
+    (0x19, 20), // DONKEY KONG
+    (0x35, 5),  // MARIO'S PICROSS
+    (0xA8, 33),
+    (0x14, 13), // POKEMON RED, GAMEBOYCAMERA G
+    (0xAA, 14), // POKEMON GREEN
+    (0x75, 5),  // PICROSS 2
+    (0x95, 29), // YOSSY NO PANEPON
+    (0x99, 5),  // KIRAKIRA KIDS
+    (0x34, 18), // GAMEBOY GALLERY
+    (0x6F, 9),  // POCKETCAMERA
+    (0x15, 3),
+    (0xFF, 2),  // BALLOON KID
+    (0x97, 26), // KINGOFTHEZOO
+    (0x4B, 25), // DMG FOOTBALL
+    (0x90, 25), // WORLD CUP
+    (0x17, 41), // OTHELLO
+    (0x10, 42), // SUPER RC PRO-AM
+    (0x39, 26), // DYNABLASTER
+    (0xF7, 45), // BOY AND BLOB GB2
+    (0xF6, 42), // MEGAMAN
+    (0xA2, 45), // STAR WARS-NOA
+    (0x49, 36),
+    (0x4E, 38), // WAVERACE
+    (0x43, 26),
+    (0x68, 42), // LOLO2
+    (0xE0, 30), // YOSHI'S COOKIE
+    (0x8B, 41), // MYSTIC QUEST
+    (0xF0, 34),
+    (0xCE, 34), // TOPRANKINGTENNIS
+    (0x0C, 5),  // MANSELL
+    (0x29, 42), // MEGAMAN3
+    (0xE8, 6),  // SPACE INVADERS
+    (0xB7, 5),  // GAME&WATCH
+    (0x86, 33), // DONKEYKONGLAND95
+    (0x9A, 25), // ASTEROIDS/MISCMD
+    (0x52, 42), // STREET FIGHTER 2
+    (0x01, 42), // DEFENDER/JOUST
+    (0x9D, 40), // KILLERINSTINCT95
+    (0x71, 2),  // TETRIS BLAST
+    (0x9C, 16), // PINOCCHIO
+    (0xBD, 25),
+    (0x5D, 42), // BA.TOSHINDEN
+    (0x6D, 42), // NETTOU KOF 95
+    (0x67, 5),
+    (0x3F, 0),  // TETRIS PLUS
+    (0x6B, 39), // DONKEYKONGLAND 3
+];
+
+// Checksums shared by more than one game, told apart by the 4th letter of the title.
+// The first match wins, like in the boot ROM
+const DUPLICATES: [(u8, u8, u8); 30] = [
+    (0xB3, b'B', 36),
+    (0x46, b'E', 22), // SUPERMARIOLAND3
+    (0x28, b'F', 25), // GOLF
+    (0xA5, b'A', 6),  // SOLARSTRIKER
+    (0xC6, b'A', 32), // GBWARS
+    (0xD3, b'R', 12), // KAERUNOTAMENI
+    (0x27, b'B', 36),
+    (0x61, b'E', 11), // POKEMON BLUE
+    (0x18, b'K', 39), // DONKEYKONGLAND
+    (0x66, b'E', 18), // GAMEBOY GALLERY2
+    (0x6A, b'K', 39), // DONKEYKONGLAND 2
+    (0xBF, b' ', 24), // KID ICARUS
+    (0x0D, b'R', 31), // TETRIS2
+    (0xF4, b'-', 50),
+    (0xB3, b'U', 17), // MOGURANYA
+    (0x46, b'R', 46),
+    (0x28, b'A', 6),  // GALAGA&GALAXIAN
+    (0xA5, b'R', 27), // BT2RAGNAROKWORLD
+    (0xC6, b' ', 0),  // KEN GRIFFEY JR
+    (0xD3, b'I', 47),
+    (0x27, b'N', 41), // MAGNETIC SOCCER
+    (0x61, b'A', 41), // VEGAS STAKES
+    (0x18, b'I', 0),
+    (0x66, b'L', 0), // MILLI/CENTI/PEDE
+    (0x6A, b' ', 19),
+    (0xBF, b'I', 34), // MARIO & YOSHI
+    (0x0D, b'C', 23), // SOCCER
+    (0xF4, b'E', 18), // POKEBOM
+    (0xB3, b' ', 29), // G&W GALLERY
+    (0x46, b'R', 28), // TETRIS ATTACK
+];
+
+impl CompatPalette {
+    // The palette used for games that aren't in the boot ROM's table
+    pub const DEFAULT: CompatPalette = Self::combination(0);
+
+    // Palettes that can be picked by holding a button combination during the boot animation
+    pub const BROWN: CompatPalette = Self::combination(5);
+    pub const RED: CompatPalette = Self::combination(43);
+    pub const DARK_BROWN: CompatPalette = Self::combination(28);
+    pub const PASTEL: CompatPalette = Self::combination(8);
+    pub const ORANGE: CompatPalette = Self::combination(3);
+    pub const YELLOW: CompatPalette = Self::combination(49);
+    pub const BLUE: CompatPalette = Self::combination(48);
+    pub const DARK_BLUE: CompatPalette = Self::combination(40);
+    pub const GREYSCALE: CompatPalette = Self::combination(7);
+    pub const GREEN: CompatPalette = Self::combination(1);
+    pub const INVERTED: CompatPalette = Self::combination(6);
+
+    const fn combination(index: usize) -> CompatPalette {
+        let (object0, object1, background) = COMBINATIONS[index];
+        CompatPalette {
+            background: boot_colors(background),
+            object0: boot_colors(object0),
+            object1: boot_colors(object1),
+        }
+    }
+
+    // Title checksum -> palette, only for Nintendo games like the boot ROM does
+    pub fn for_catridge(catridge: &Catridge) -> CompatPalette {
+        if !catridge.nintendo_licensed() {
+            return Self::DEFAULT;
+        }
+        let checksum = catridge.title_checksum();
+        let letter = catridge.data.get(0x137).copied().unwrap_or(0);
+        let index = TITLES
+            .iter()
+            .find(|&&(sum, _)| sum == checksum)
+            .map(|&(_, index)| index)
+            .or_else(|| {
+                DUPLICATES
+                    .iter()
+                    .find(|&&(sum, fourth, _)| sum == checksum && fourth == letter)
+                    .map(|&(_, _, index)| index)
+            });
+        Self::combination(index.unwrap_or(0) as usize)
+    }
+
+    // Loads the palettes into CGB palette RAM and makes the PPU use them for DMG rendering
+    pub fn apply(&self, ppu: &mut Ppu) {
+        Self::write_palette(&mut ppu.palettes.background, 0, self.background);
+        Self::write_palette(&mut ppu.palettes.objects, 0, self.object0);
+        Self::write_palette(&mut ppu.palettes.objects, 1, self.object1);
+        ppu.compat_palette = true;
+    }
+
+    fn write_palette(ram: &mut [u8; 64], palette: usize, colors: [u32; 4]) {
+        for (i, color) in colors.into_iter().enumerate() {
+            let bytes = rgb_to_rgb555(color).to_le_bytes();
+            ram[palette * 8 + i * 2] = bytes[0];
+            ram[palette * 8 + i * 2 + 1] = bytes[1];
+        }
+    }
+}
+
+// 4 colours from BOOT_COLORS as 0xRRGGBB
+const fn boot_colors(first: u8) -> [u32; 4] {
+    let mut colors = [0; 4];
+    let mut i = 0;
+    while i < 4 {
+        let color = BOOT_COLORS[first as usize + i] as u32;
+        let (r, g, b) = (color & 0x1F, (color >> 5) & 0x1F, (color >> 10) & 0x1F);
+        colors[i] = (r << 3 | r >> 2) << 16 | (g << 3 | g >> 2) << 8 | (b << 3 | b >> 2);
+        i += 1;
+    }
+    colors
+}
+
+pub fn rgb_to_rgb555(color: u32) -> u16 {
+    let r = (color >> 19) & 0x1F;
+    let g = (color >> 11) & 0x1F;
+    let b = (color >> 3) & 0x1F;
+    (b << 10 | g << 5 | r) as u16
+}
@@ -0,0 +1,230 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use super::VideoError;
+use crate::audio::Resampler;
+use crate::wav::WavWriter;
+
+// Only APNG keeps every pixel as it was, Y4M and GIF are lossy
+#[derive(Clone, Copy, PartialEq, Default, Debug)]
+pub enum RecordFormat {
+    // 8 bit BT.601 YUV 4:4:4 video, audio goes next to it in a WAV file. Lossy, the
+    // YUV conversion rounds so the colours don't come back exactly
+    Y4m,
+    // Animated PNG, frames are kept in memory until the end so keep clips short
+    #[default]
+    Apng,
+    // Resampled to 50 fps, GIF delays are in centiseconds and players clamp anything
+    // under 2. Lossy, every frame is quantized to a palette of 256 colours
+    Gif,
+}
+
+impl RecordFormat {
+    // Picks the format from the extension, APNG when there is none
+    pub fn from_filename(filename: &str) -> Option<RecordFormat> {
+        let Some(extension) = Path::new(filename).extension() else {
+            return Some(RecordFormat::default());
+        };
+        match extension.to_str()?.to_ascii_lowercase().as_str() {
+            "y4m" => Some(RecordFormat::Y4m),
+            "png" | "apng" => Some(RecordFormat::Apng),
+            "gif" => Some(RecordFormat::Gif),
+            _ => None,
+        }
+    }
+}
+
+enum Encoder {
+    Y4m(BufWriter<File>),
+    Apng(BufWriter<File>, Vec<Vec<u8>>),
+    Gif(gif::Encoder<BufWriter<File>>),
+}
+
+// Records every frame at the native 4194304 / 70224 (~59.73) Hz. Frames are RGBA
+// at the size given when the recording starts, which is the output size after scaling
+pub struct Recorder {
+    encoder: Encoder,
+    audio: Option<(WavWriter<BufWriter<File>>, Resampler)>,
+    width: usize,
+    height: usize,
+    pub frames: u64,
+    gif_frames: u64,
+    // The first write error sticks around until finish
+    failed: bool,
+}
+
+impl Recorder {
+    pub const FRAME_RATE: (u32, u32) = (4194304, 70224);
+    // 70224 / 4194304 seconds as the closest fraction that fits APNG's 16 bit delays
+    pub const APNG_DELAY: (u16, u16) = (400, 23891);
+    pub const GIF_DELAY: u16 = 2;
+    // The sound gets its own resampler at a fixed rate, whatever the audio device does,
+    // so it always lines up with the frames
+    pub const AUDIO_RATE: u32 = 48000;
+
+    pub fn create(
+        filename: &str,
+        format: RecordFormat,
+        width: usize,
+        height: usize,
+    ) -> Result<Recorder, VideoError> {
+        let file = BufWriter::new(File::create(filename).map_err(|_| VideoError::Save)?);
+        let mut audio = None;
+        let encoder = match format {
+            RecordFormat::Y4m => {
+                let mut file = file;
+                let (num, den) = Self::FRAME_RATE;
+                writeln!(
+                    file,
+                    "YUV4MPEG2 W{} H{} F{}:{} Ip A1:1 C444 XCOLORRANGE=FULL",
+                    width, height, num, den
+                )
+                .map_err(|_| VideoError::Save)?;
+                let audio_filename = Path::new(filename).with_extension("wav");
+                let wav = WavWriter::create(&audio_filename.to_string_lossy(), 2, Self::AUDIO_RATE)
+                    .map_err(|_| VideoError::Save)?;
+                audio = Some((wav, Resampler::new(Self::AUDIO_RATE)));
+                Encoder::Y4m(file)
+            }
+            RecordFormat::Apng => Encoder::Apng(file, Vec::new()),
+            RecordFormat::Gif => {
+                let (w, h) = (width as u16, height as u16);
+                let mut encoder =
+                    gif::Encoder::new(file, w, h, &[]).map_err(|_| VideoError::Save)?;
+                encoder
+                    .set_repeat(gif::Repeat::Infinite)
+                    .map_err(|_| VideoError::Save)?;
+                Encoder::Gif(encoder)
+            }
+        };
+        Ok(Recorder {
+            encoder,
+            audio,
+            width,
+            height,
+            frames: 0,
+            gif_frames: 0,
+            failed: false,
+        })
+    }
+
+    pub fn write_frame(&mut self, rgba: &[u8]) -> Result<(), VideoError> {
+        let result = self.encode_frame(rgba);
+        self.frames += 1;
+        self.failed |= result.is_err();
+        result
+    }
+
+    // Only Y4M recordings keep audio, in a WAV file with the same name
+    pub fn has_audio(&self) -> bool {
+        self.audio.is_some()
+    }
+
+    // Samples from Apu::take_samples, resampled to AUDIO_RATE. Dropped without audio
+    pub fn write_audio(&mut self, samples: &[[i16; 2]]) -> Result<(), VideoError> {
+        let Some((wav, resampler)) = &mut self.audio else {
+            return Ok(());
+        };
+        let output = resampler.process(samples);
+        let result = wav.write_samples(&output).map_err(|_| VideoError::Save);
+        self.failed |= result.is_err();
+        result
+    }
+
+    pub fn finish(self) -> Result<(), VideoError> {
+        if let Some((audio, _)) = self.audio {
+            audio.finish().map_err(|_| VideoError::Save)?;
+        }
+        match self.encoder {
+            Encoder::Y4m(mut file) => file.flush().map_err(|_| VideoError::Save)?,
+            Encoder::Apng(file, frames) => {
+                Self::write_apng(file, self.width, self.height, &frames)?
+            }
+            Encoder::Gif(encoder) => {
+                encoder
+                    .into_inner()
+                    .and_then(|mut file| file.flush())
+                    .map_err(|_| VideoError::Save)?;
+            }
+        }
+        if self.failed {
+            return Err(VideoError::Save);
+        }
+        Ok(())
+    }
+
+    fn encode_frame(&mut self, rgba: &[u8]) -> Result<(), VideoError> {
+        if rgba.len() != self.width * self.height * 4 {
+            return Err(VideoError::Save);
+        }
+        match &mut self.encoder {
+            Encoder::Y4m(file) => {
+                file.write_all(b"FRAME\n").map_err(|_| VideoError::Save)?;
+                file.write_all(&rgba_to_yuv444(rgba))
+                    .map_err(|_| VideoError::Save)
+            }
+            Encoder::Apng(_, frames) => {
+                frames.push(rgba.to_vec());
+                Ok(())
+            }
+            Encoder::Gif(encoder) => {
+                // Only the first frame starting in every 2 centisecond slot is kept
+                let (num, den) = Self::FRAME_RATE;
+                let start = self.frames * den as u64 * 100 / num as u64;
+                if start < self.gif_frames * Self::GIF_DELAY as u64 {
+                    return Ok(());
+                }
+                self.gif_frames += 1;
+                let mut pixels = rgba.to_vec();
+                let (w, h) = (self.width as u16, self.height as u16);
+                let mut frame = gif::Frame::from_rgba_speed(w, h, &mut pixels, 10);
+                frame.delay = Self::GIF_DELAY;
+                encoder.write_frame(&frame).map_err(|_| VideoError::Save)
+            }
+        }
+    }
+
+    fn write_apng(
+        file: BufWriter<File>,
+        width: usize,
+        height: usize,
+        frames: &[Vec<u8>],
+    ) -> Result<(), VideoError> {
+        if frames.is_empty() {
+            return Err(VideoError::Save);
+        }
+        let mut encoder = png::Encoder::new(file, width as u32, height as u32);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let (num, den) = Self::APNG_DELAY;
+        encoder
+            .set_animated(frames.len() as u32, 0)
+            .and_then(|_| encoder.set_frame_delay(num, den))
+            .map_err(|_| VideoError::Save)?;
+        let mut writer = encoder.write_header().map_err(|_| VideoError::Save)?;
+        for frame in frames {
+            writer
+                .write_image_data(frame)
+                .map_err(|_| VideoError::Save)?;
+        }
+        writer.finish().map_err(|_| VideoError::Save)
+    }
+}
+
+// Full range BT.601, as planar Y, Cb, Cr. Lossy, rounding to 8 bits means the colours
+// don't convert back exactly
+fn rgba_to_yuv444(rgba: &[u8]) -> Vec<u8> {
+    let pixels = rgba.len() / 4;
+    let mut yuv = vec![0; pixels * 3];
+    for (i, pixel) in rgba.chunks(4).enumerate() {
+        let (r, g, b) = (pixel[0] as i32, pixel[1] as i32, pixel[2] as i32);
+        let y = (77 * r + 150 * g + 29 * b + 128) >> 8;
+        let cb = ((-43 * r - 85 * g + 128 * b + 128) >> 8) + 128;
+        let cr = ((128 * r - 107 * g - 21 * b + 128) >> 8) + 128;
+        yuv[i] = y.clamp(0, 255) as u8;
+        yuv[pixels + i] = cb.clamp(0, 255) as u8;
+        yuv[pixels * 2 + i] = cr.clamp(0, 255) as u8;
+    }
+    yuv
+}
@@ -0,0 +1,318 @@
+// Pixel art upscalers, working on 0xRRGGBB frames
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Scaler {
+    Nearest(usize),
+    // AdvMAME2x/3x, Scale4x is Scale2x applied twice
+    Scale2x,
+    Scale3x,
+    Scale4x,
+    // Corner smoothing after hq2x: its YUV thresholds and corner blends, picked by a few
+    // rules instead of its 256 pattern table, so the output isn't hq2x's
+    HqLike,
+    // Level 1 2xBR
+    Xbr2x,
+}
+
+// A pixel and its neighbours, clamped at the edges of the frame
+//   A B C
+//   D E F
+//   G H I
+struct Neighbours {
+    a: u32,
+    b: u32,
+    c: u32,
+    d: u32,
+    e: u32,
+    f: u32,
+    g: u32,
+    h: u32,
+    i: u32,
+}
+
+impl Neighbours {
+    fn new(frame: &[u32], width: usize, height: usize, x: usize, y: usize) -> Neighbours {
+        let at = |dx: isize, dy: isize| {
+            let x = (x as isize + dx).clamp(0, width as isize - 1) as usize;
+            let y = (y as isize + dy).clamp(0, height as isize - 1) as usize;
+            frame[y * width + x]
+        };
+        Neighbours {
+            a: at(-1, -1),
+            b: at(0, -1),
+            c: at(1, -1),
+            d: at(-1, 0),
+            e: at(0, 0),
+            f: at(1, 0),
+            g: at(-1, 1),
+            h: at(0, 1),
+            i: at(1, 1),
+        }
+    }
+}
+
+impl Scaler {
+    pub const ALL: [Scaler; 7] = [
+        Scaler::Nearest(2),
+        Scaler::Nearest(4),
+        Scaler::Scale2x,
+        Scaler::Scale3x,
+        Scaler::Scale4x,
+        Scaler::HqLike,
+        Scaler::Xbr2x,
+    ];
+
+    pub fn from_name(name: &str) -> Option<Scaler> {
+        match name.to_ascii_lowercase().as_str() {
+            "nearest" | "1x" => Some(Scaler::Nearest(1)),
+            "2x" => Some(Scaler::Nearest(2)),
+            "3x" => Some(Scaler::Nearest(3)),
+            "4x" => Some(Scaler::Nearest(4)),
+            "scale2x" => Some(Scaler::Scale2x),
+            "scale3x" => Some(Scaler::Scale3x),
+            "scale4x" => Some(Scaler::Scale4x),
+            "hqlike" => Some(Scaler::HqLike),
+            "xbr" | "2xbr" | "xbr2x" => Some(Scaler::Xbr2x),
+            _ => None,
+        }
+    }
+
+    pub fn factor(&self) -> usize {
+        match self {
+            Scaler::Nearest(factor) => (*factor).max(1),
+            Scaler::Scale2x | Scaler::HqLike | Scaler::Xbr2x => 2,
+            Scaler::Scale3x => 3,
+            Scaler::Scale4x => 4,
+        }
+    }
+
+    pub fn apply(&self, frame: &[u32], width: usize, height: usize) -> Vec<u32> {
+        match self {
+            Scaler::Nearest(factor) => nearest(frame, width, height, (*factor).max(1)),
+            Scaler::Scale2x => scale2x(frame, width, height),
+            Scaler::Scale3x => scale3x(frame, width, height),
+            Scaler::Scale4x => scale2x(&scale2x(frame, width, height), width * 2, height * 2),
+            Scaler::HqLike => hq_like(frame, width, height),
+            Scaler::Xbr2x => xbr2x(frame, width, height),
+        }
+    }
+
+    // Same as apply, on RGBA bytes. Alpha comes out opaque
+    pub fn apply_rgba(&self, rgba: &[u8], width: usize, height: usize) -> Vec<u8> {
+        let frame: Vec<u32> = rgba
+            .chunks(4)
+            .map(|pixel| (pixel[0] as u32) << 16 | (pixel[1] as u32) << 8 | pixel[2] as u32)
+            .collect();
+        let mut output = Vec::with_capacity(rgba.len() * self.factor() * self.factor());
+        for color in self.apply(&frame, width, height) {
+            output.extend_from_slice(&[(color >> 16) as u8, (color >> 8) as u8, color as u8, 0xFF]);
+        }
+        output
+    }
+}
+
+fn nearest(frame: &[u32], width: usize, height: usize, factor: usize) -> Vec<u32> {
+    let mut output = Vec::with_capacity(frame.len() * factor * factor);
+    for y in 0..height {
+        let row = &frame[y * width..(y + 1) * width];
+        let start = output.len();
+        for &color in row {
+            output.extend(std::iter::repeat_n(color, factor));
+        }
+        for _ in 1..factor {
+            output.extend_from_within(start..start + width * factor);
+        }
+    }
+    output
+}
+
+fn scale2x(frame: &[u32], width: usize, height: usize) -> Vec<u32> {
+    let mut output = vec![0; frame.len() * 4];
+    let stride = width * 2;
+    for y in 0..height {
+        for x in 0..width {
+            let Neighbours { b, d, e, f, h, .. } = Neighbours::new(frame, width, height, x, y);
+            let mut block = [e; 4];
+            if b != h && d != f {
+                block = [
+                    if d == b { d } else { e },
+                    if b == f { f } else { e },
+                    if d == h { d } else { e },
+                    if h == f { f } else { e },
+                ];
+            }
+            let top = y * 2 * stride + x * 2;
+            output[top..top + 2].copy_from_slice(&block[0..2]);
+            output[top + stride..top + stride + 2].copy_from_slice(&block[2..4]);
+        }
+    }
+    output
+}
+
+fn scale3x(frame: &[u32], width: usize, height: usize) -> Vec<u32> {
+    let mut output = vec![0; frame.len() * 9];
+    let stride = width * 3;
+    for y in 0..height {
+        for x in 0..width {
+            let Neighbours {
+                a,
+                b,
+                c,
+                d,
+                e,
+                f,
+                g,
+                h,
+                i,
+            } = Neighbours::new(frame, width, height, x, y);
+            let mut block = [e; 9];
+            if b != h && d != f {
+                block = [
+                    if d == b { d } else { e },
+                    if (d == b && e != c) || (b == f && e != a) {
+                        b
+                    } else {
+                        e
+                    },
+                    if b == f { f } else { e },
+                    if (d == b && e != g) || (d == h && e != a) {
+                        d
+                    } else {
+                        e
+                    },
+                    e,
+                    if (b == f && e != i) || (h == f && e != c) {
+                        f
+                    } else {
+                        e
+                    },
+                    if d == h { d } else { e },
+                    if (d == h && e != i) || (h == f && e != g) {
+                        h
+                    } else {
+                        e
+                    },
+                    if h == f { f } else { e },
+                ];
+            }
+            for row in 0..3 {
+                let start = (y * 3 + row) * stride + x * 3;
+                output[start..start + 3].copy_from_slice(&block[row * 3..row * 3 + 3]);
+            }
+        }
+    }
+    output
+}
+
+fn yuv(color: u32) -> (i32, i32, i32) {
+    let (r, g, b) = (
+        (color >> 16 & 0xFF) as i32,
+        (color >> 8 & 0xFF) as i32,
+        (color & 0xFF) as i32,
+    );
+    let y = (r * 299 + g * 587 + b * 114) / 1000;
+    let u = (-r * 169 - g * 331 + b * 500) / 1000 + 128;
+    let v = (r * 500 - g * 419 - b * 81) / 1000 + 128;
+    (y, u, v)
+}
+
+// hqx's "different enough" test
+fn hq_differs(a: u32, b: u32) -> bool {
+    if a == b {
+        return false;
+    }
+    let ((y1, u1, v1), (y2, u2, v2)) = (yuv(a), yuv(b));
+    (y1 - y2).abs() > 0x30 || (u1 - u2).abs() > 0x07 || (v1 - v2).abs() > 0x06
+}
+
+// Weighted average of the colours, per channel
+fn blend(colors: &[(u32, u32)]) -> u32 {
+    let total: u32 = colors.iter().map(|(_, weight)| weight).sum();
+    (0..3).fold(0, |output, c| {
+        let shift = 16 - c * 8;
+        let sum: u32 = colors
+            .iter()
+            .map(|(color, weight)| (color >> shift & 0xFF) * weight)
+            .sum();
+        (output << 8) | ((sum + total / 2) / total)
+    })
+}
+
+// One output corner of E, from the two pixels next to that corner and the diagonal one
+fn hq_corner(e: u32, side1: u32, side2: u32, diagonal: u32) -> u32 {
+    let edge = !hq_differs(side1, side2) && hq_differs(e, side1);
+    if edge {
+        if hq_differs(e, diagonal) {
+            blend(&[(e, 2), (side1, 1), (side2, 1)])
+        } else {
+            blend(&[(e, 6), (side1, 1), (side2, 1)])
+        }
+    } else if hq_differs(e, diagonal) && !hq_differs(e, side1) && !hq_differs(e, side2) {
+        blend(&[(e, 3), (diagonal, 1)])
+    } else if hq_differs(e, side1) && hq_differs(e, side2) {
+        blend(&[(e, 2), (side1, 1), (side2, 1)])
+    } else {
+        e
+    }
+}
+
+fn hq_like(frame: &[u32], width: usize, height: usize) -> Vec<u32> {
+    let mut output = vec![0; frame.len() * 4];
+    let stride = width * 2;
+    for y in 0..height {
+        for x in 0..width {
+            let n = Neighbours::new(frame, width, height, x, y);
+            let top = y * 2 * stride + x * 2;
+            output[top] = hq_corner(n.e, n.b, n.d, n.a);
+            output[top + 1] = hq_corner(n.e, n.b, n.f, n.c);
+            output[top + stride] = hq_corner(n.e, n.h, n.d, n.g);
+            output[top + stride + 1] = hq_corner(n.e, n.h, n.f, n.i);
+        }
+    }
+    output
+}
+
+fn xbr_distance(a: (i32, i32, i32), b: (i32, i32, i32)) -> i32 {
+    (a.0 - b.0).abs() * 48 + (a.1 - b.1).abs() * 7 + (a.2 - b.2).abs() * 6
+}
+
+fn xbr2x(frame: &[u32], width: usize, height: usize) -> Vec<u32> {
+    let yuvs: Vec<(i32, i32, i32)> = frame.iter().map(|&color| yuv(color)).collect();
+    let mut output = vec![0; frame.len() * 4];
+    let stride = width * 2;
+    for y in 0..height {
+        for x in 0..width {
+            // Pixels 2 away are needed for the edge weights
+            let at = |dx: isize, dy: isize| {
+                let x = (x as isize + dx).clamp(0, width as isize - 1) as usize;
+                let y = (y as isize + dy).clamp(0, height as isize - 1) as usize;
+                y * width + x
+            };
+            let e = at(0, 0);
+            let top = y * 2 * stride + x * 2;
+            // Each corner is the bottom right one of the neighbourhood rotated by (rx, ry)
+            for (offset, (rx, ry)) in [
+                (stride + 1, (1, 1)),
+                (stride, (-1, 1)),
+                (1, (1, -1)),
+                (0, (-1, -1)),
+            ] {
+                // Rotated coordinates: u runs towards the corner's side, v towards its bottom
+                let p = |u: isize, v: isize| at(u * rx, v * ry);
+                let (c, g) = (p(1, -1), p(-1, 1));
+                let (f, h, i) = (p(1, 0), p(0, 1), p(1, 1));
+                let (b, d) = (p(0, -1), p(-1, 0));
+                let (f4, h5, i4, i5) = (p(2, 0), p(0, 2), p(2, 1), p(1, 2));
+                let dist = |a: usize, b: usize| xbr_distance(yuvs[a], yuvs[b]);
+                let weight_e = dist(e, c) + dist(e, g) + dist(i, f4) + dist(i, h5) + 4 * dist(h, f);
+                let weight_i = dist(h, d) + dist(h, i5) + dist(f, i4) + dist(f, b) + 4 * dist(e, i);
+                output[top + offset] = if weight_e < weight_i {
+                    let closer = if dist(e, f) <= dist(e, h) { f } else { h };
+                    blend(&[(frame[e], 1), (frame[closer], 1)])
+                } else {
+                    frame[e]
+                };
+            }
+        }
+    }
+    output
+}
@@ -0,0 +1,27 @@
+use std::fs::File;
+use std::io::BufWriter;
+
+use super::VideoError;
+use crate::ppu::Ppu;
+
+pub fn write_png(filename: &str, width: u32, height: u32, rgba: &[u8]) -> Result<(), VideoError> {
+    let file = File::create(filename).map_err(|_| VideoError::Save)?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(|_| VideoError::Save)?;
+    writer.write_image_data(rgba).map_err(|_| VideoError::Save)
+}
+
+// 64 bit FNV-1a over the RGB555 framebuffer. It doesn't depend on the output palette or
+// colour correction, so the same frame always hashes to the same value
+pub fn frame_hash(ppu: &Ppu) -> u64 {
+    let mut hash: u64 = 0xCBF29CE484222325;
+    for color in ppu.color_framebuffer {
+        for byte in color.to_le_bytes() {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001B3);
+        }
+    }
+    hash
+}
@@ -0,0 +1,142 @@
+use std::fmt::Write;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TerminalColors {
+    // 24 bit colour escapes
+    TrueColor,
+    // xterm's 6x6x6 cube and grey ramp
+    Ansi256,
+}
+
+impl TerminalColors {
+    // COLORTERM is how terminals advertise truecolor support
+    pub fn detect() -> TerminalColors {
+        match std::env::var("COLORTERM") {
+            Ok(value) if value == "truecolor" || value == "24bit" => TerminalColors::TrueColor,
+            _ => TerminalColors::Ansi256,
+        }
+    }
+
+    fn escape(&self, output: &mut String, background: bool, color: u32) {
+        let layer = if background { 48 } else { 38 };
+        match self {
+            TerminalColors::TrueColor => {
+                let (r, g, b) = (color >> 16 & 0xFF, color >> 8 & 0xFF, color & 0xFF);
+                let _ = write!(output, "\x1b[{};2;{};{};{}m", layer, r, g, b);
+            }
+            TerminalColors::Ansi256 => {
+                let _ = write!(output, "\x1b[{};5;{}m", layer, ansi256(color));
+            }
+        }
+    }
+}
+
+// Closest xterm 256 colour index, either from the colour cube or the grey ramp
+pub fn ansi256(color: u32) -> u8 {
+    const LEVELS: [u32; 6] = [0x00, 0x5F, 0x87, 0xAF, 0xD7, 0xFF];
+    let channels = [color >> 16 & 0xFF, color >> 8 & 0xFF, color & 0xFF];
+    let level = |value: u32| {
+        (0..6)
+            .min_by_key(|&i| LEVELS[i].abs_diff(value))
+            .unwrap_or(0)
+    };
+    let cube = channels.map(level);
+    let cube_color = cube.map(|i| LEVELS[i]);
+    let grey = channels.iter().sum::<u32>() / 3;
+    let grey_index = (grey.saturating_sub(8) / 10).min(23);
+    let grey_color = 8 + grey_index * 10;
+    let distance = |target: [u32; 3]| -> u32 {
+        channels
+            .iter()
+            .zip(target)
+            .map(|(&a, b)| a.abs_diff(b).pow(2))
+            .sum()
+    };
+    if distance([grey_color; 3]) < distance(cube_color) {
+        232 + grey_index as u8
+    } else {
+        16 + (cube[0] * 36 + cube[1] * 6 + cube[2]) as u8
+    }
+}
+
+// Draws frames with one `▀` per two pixels stacked vertically: the top one is the
+// foreground colour and the bottom one the background. Only cells that changed since the
+// previous frame are sent, which keeps it usable over SSH
+pub struct TerminalRenderer {
+    pub colors: TerminalColors,
+    // Top left corner of the picture on the terminal, 0 based
+    pub column: u16,
+    pub row: u16,
+    previous: Option<(usize, Vec<u32>)>,
+}
+
+impl TerminalRenderer {
+    pub fn new(colors: TerminalColors) -> TerminalRenderer {
+        TerminalRenderer {
+            colors,
+            column: 0,
+            row: 0,
+            previous: None,
+        }
+    }
+
+    // Forces the next frame to be drawn in full, after a resize or a cleared screen
+    pub fn invalidate(&mut self) {
+        self.previous = None;
+    }
+
+    // Escape sequences that bring the terminal from the previous frame to this one.
+    // `frame` is 0xRRGGBB, an odd last row is paired with black
+    pub fn draw(&mut self, frame: &[u32], width: usize) -> String {
+        let height = frame.len() / width;
+        let previous = match &self.previous {
+            Some((previous_width, previous)) if *previous_width == width => {
+                Some(previous.as_slice())
+            }
+            _ => None,
+        };
+        let pixel = |frame: &[u32], x: usize, y: usize| {
+            if y < height {
+                frame[y * width + x]
+            } else {
+                0
+            }
+        };
+
+        let mut output = String::new();
+        // Colours currently set on the terminal, and where the cursor is
+        let (mut foreground, mut background) = (None, None);
+        let mut cursor = None;
+        for row in 0..height.div_ceil(2) {
+            for x in 0..width {
+                let (top, bottom) = (pixel(frame, x, row * 2), pixel(frame, x, row * 2 + 1));
+                if let Some(previous) = previous {
+                    if (pixel(previous, x, row * 2), pixel(previous, x, row * 2 + 1))
+                        == (top, bottom)
+                    {
+                        continue;
+                    }
+                }
+                if cursor != Some((x, row)) {
+                    let (column, line) = (self.column as usize + x, self.row as usize + row);
+                    let _ = write!(output, "\x1b[{};{}H", line + 1, column + 1);
+                }
+                if foreground != Some(top) {
+                    self.colors.escape(&mut output, false, top);
+                    foreground = Some(top);
+                }
+                if background != Some(bottom) {
+                    self.colors.escape(&mut output, true, bottom);
+                    background = Some(bottom);
+                }
+                output.push('▀');
+                cursor = Some((x + 1, row));
+            }
+        }
+        if !output.is_empty() {
+            output.push_str("\x1b[0m");
+        }
+        self.previous = Some((width, frame.to_vec()));
+        output
+    }
+}
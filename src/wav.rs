@@ -0,0 +1,80 @@
+use std::fs::File;
+use std::io::{BufWriter, Result, Seek, SeekFrom, Write};
+
+// 16 bit PCM WAV. The RIFF and data chunk sizes are only known at the end,
+// they get patched in when the file is finished
+pub struct WavWriter<W: Write + Seek> {
+    writer: W,
+    channels: u16,
+    sample_rate: u32,
+    samples: u32,
+}
+
+impl WavWriter<BufWriter<File>> {
+    pub fn create(filename: &str, channels: u16, sample_rate: u32) -> Result<Self> {
+        WavWriter::new(
+            BufWriter::new(File::create(filename)?),
+            channels,
+            sample_rate,
+        )
+    }
+}
+
+impl<W: Write + Seek> WavWriter<W> {
+    const HEADER_SIZE: u32 = 44;
+
+    pub fn new(writer: W, channels: u16, sample_rate: u32) -> Result<Self> {
+        let mut wav = WavWriter {
+            writer,
+            channels,
+            sample_rate,
+            samples: 0,
+        };
+        wav.write_header()?;
+        Ok(wav)
+    }
+
+    pub fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    // Samples are interleaved when there's more than one channel
+    pub fn write_samples(&mut self, samples: &[i16]) -> Result<()> {
+        for sample in samples {
+            self.writer.write_all(&sample.to_le_bytes())?;
+        }
+        self.samples += samples.len() as u32;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<W> {
+        self.writer.seek(SeekFrom::Start(0))?;
+        self.write_header()?;
+        self.writer.seek(SeekFrom::End(0))?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    fn write_header(&mut self) -> Result<()> {
+        let data_size = self.samples * 2;
+        let block_align = self.channels * 2;
+        let header = &mut self.writer;
+        header.write_all(b"RIFF")?;
+        header.write_all(&(Self::HEADER_SIZE - 8 + data_size).to_le_bytes())?;
+        header.write_all(b"WAVEfmt ")?;
+        header.write_all(&16u32.to_le_bytes())?;
+        // PCM
+        header.write_all(&1u16.to_le_bytes())?;
+        header.write_all(&self.channels.to_le_bytes())?;
+        header.write_all(&self.sample_rate.to_le_bytes())?;
+        header.write_all(&(self.sample_rate * block_align as u32).to_le_bytes())?;
+        header.write_all(&block_align.to_le_bytes())?;
+        header.write_all(&16u16.to_le_bytes())?;
+        header.write_all(b"data")?;
+        header.write_all(&data_size.to_le_bytes())
+    }
+}
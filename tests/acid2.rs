@@ -0,0 +1,154 @@
+#[cfg(test)]
+mod acid2_test {
+
+    use std::fs::File;
+    use std::path::{Path, PathBuf};
+
+    use blazeboy::{
+        write_png, Catridge, ColorCorrection, DmgPalette, GameBoy, SCREEN_HEIGHT, SCREEN_WIDTH,
+    };
+
+    // The ROMs and reference images aren't shipped with the repo, so the acid2 tests are
+    // ignored. Point BLAZEBOY_TEST_ROMS at a directory holding dmg-acid2.gb/.png and
+    // cgb-acid2.gbc/.png and run them with `cargo test --test acid2 -- --ignored`
+    fn rom_dir() -> PathBuf {
+        let dir = std::env::var("BLAZEBOY_TEST_ROMS")
+            .expect("BLAZEBOY_TEST_ROMS should point at the directory with the acid2 ROMs");
+        PathBuf::from(dir)
+    }
+
+    // Reference image as 0xRRGGBB pixels
+    fn load_reference(path: &Path) -> Vec<u32> {
+        let decoder = png::Decoder::new(File::open(path).unwrap());
+        let mut reader = decoder.read_info().unwrap();
+        let mut image = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut image).unwrap();
+        assert_eq!(
+            (info.width as usize, info.height as usize),
+            (SCREEN_WIDTH, SCREEN_HEIGHT)
+        );
+        assert_eq!(info.bit_depth, png::BitDepth::Eight);
+        let channels = match info.color_type {
+            png::ColorType::Rgb => 3,
+            png::ColorType::Rgba => 4,
+            color_type => panic!("unsupported reference format {:?}", color_type),
+        };
+        image[..info.buffer_size()]
+            .chunks(channels)
+            .map(|pixel| (pixel[0] as u32) << 16 | (pixel[1] as u32) << 8 | pixel[2] as u32)
+            .collect()
+    }
+
+    // Mismatched pixels in red, everything else dimmed so the picture is still recognisable
+    fn write_diff(filename: &Path, frame: &[u32], reference: &[u32]) {
+        let mut rgba = Vec::with_capacity(frame.len() * 4);
+        for (&actual, &expected) in frame.iter().zip(reference) {
+            if actual == expected {
+                let [_, r, g, b] = (actual >> 2 & 0x3F3F3F).to_be_bytes();
+                rgba.extend_from_slice(&[r, g, b, 0xFF]);
+            } else {
+                rgba.extend_from_slice(&[0xFF, 0x00, 0x00, 0xFF]);
+            }
+        }
+        let filename = filename.to_str().unwrap();
+        write_png(filename, SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32, &rgba).unwrap();
+    }
+
+    fn run_acid2(name: &str, extension: &str, setup: fn(&mut GameBoy)) {
+        let dir = rom_dir();
+        let rom = dir.join(format!("{}.{}", name, extension));
+        let reference = dir.join(format!("{}.png", name));
+        assert!(rom.exists(), "{:?} is missing", rom);
+        assert!(reference.exists(), "{:?} is missing", reference);
+
+        let catridge = Catridge::from_bytes(std::fs::read(&rom).unwrap()).unwrap();
+        let mut gameboy = GameBoy::from_catridge(catridge);
+        setup(&mut gameboy);
+        assert!(
+            gameboy.run_until_breakpoint(60),
+            "{} never hit LD B,B",
+            name
+        );
+        // The breakpoint is hit mid-frame, let the last frame finish drawing
+        gameboy.run_frame();
+
+        let frame = gameboy.video.rgb(&gameboy.memory.ppu);
+        let reference = load_reference(&reference);
+        let mismatches = frame.iter().zip(&reference).filter(|(a, b)| a != b).count();
+        if mismatches > 0 {
+            let out = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("acid2");
+            std::fs::create_dir_all(&out).unwrap();
+            let diff = out.join(format!("{}-diff.png", name));
+            write_diff(&diff, &frame, &reference);
+            panic!(
+                "{}: {} pixels differ, diff written to {:?}",
+                name, mismatches, diff
+            );
+        }
+    }
+
+    #[test]
+    #[ignore = "needs the acid2 ROMs, see rom_dir"]
+    fn test_dmg_acid2() {
+        run_acid2("dmg-acid2", "gb", |gameboy| {
+            gameboy.video.dmg_palette = DmgPalette::Grey;
+        });
+    }
+
+    #[test]
+    #[ignore = "needs the acid2 ROMs, see rom_dir"]
+    fn test_cgb_acid2() {
+        run_acid2("cgb-acid2", "gbc", |gameboy| {
+            gameboy.video.correction = ColorCorrection::Raw;
+        });
+    }
+
+    fn build_rom(program: &[u8]) -> Catridge {
+        let mut rom = vec![0u8; 0x8000];
+        rom[0x100..0x100 + program.len()].copy_from_slice(program);
+        rom[0x134..0x138].copy_from_slice(b"TEST");
+        Catridge::from_bytes(rom).unwrap()
+    }
+
+    #[test]
+    fn test_breakpoint() {
+        // NOP, NOP, LD B,B
+        let mut gameboy = GameBoy::from_catridge(build_rom(&[0x00, 0x00, 0x40]));
+        assert!(gameboy.run_until_breakpoint(1));
+        assert_eq!(gameboy.cpu.registers.pc, 0x102);
+
+        // JP 0x0100 never gets there
+        let mut gameboy = GameBoy::from_catridge(build_rom(&[0xC3, 0x00, 0x01]));
+        assert!(!gameboy.run_until_breakpoint(2));
+    }
+
+    #[test]
+    fn test_vblank_interrupt() {
+        // LD A,0x01; LDH (0xFF),A; EI; HALT; JP 0x0105 with RETI at the V-blank vector
+        let mut rom = vec![0u8; 0x8000];
+        rom[0x40] = 0xD9;
+        rom[0x100..0x10A]
+            .copy_from_slice(&[0x3E, 0x01, 0xE0, 0xFF, 0xFB, 0x76, 0xC3, 0x05, 0x01, 0x00]);
+        rom[0x134..0x138].copy_from_slice(b"TEST");
+        let mut gameboy = GameBoy::from_catridge(Catridge::from_bytes(rom).unwrap());
+        gameboy.memory.io.set(0xFF0F, 0);
+
+        for _ in 0..4 {
+            gameboy.step();
+        }
+        assert!(gameboy.cpu.halted);
+        assert!(gameboy.cpu.ime);
+
+        // Halted until V-blank wakes it and jumps to the handler, which returns behind the HALT
+        while gameboy.cpu.halted {
+            gameboy.step();
+        }
+        assert_eq!(gameboy.memory.ppu.ly, 144);
+        assert_eq!(gameboy.cpu.registers.pc, 0x40);
+        assert!(!gameboy.cpu.ime);
+        assert_eq!(gameboy.memory.io.interrupt_flags() & 0x01, 0);
+        gameboy.step();
+        assert_eq!(gameboy.cpu.registers.pc, 0x106);
+        assert!(gameboy.cpu.ime);
+    }
+}
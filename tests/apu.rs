@@ -0,0 +1,156 @@
+#[cfg(test)]
+mod apu_test {
+
+    use blazeboy::{bus_read, bus_tick, bus_write, Apu, Memory};
+
+    fn read(memory: &Memory, address: u16) -> u8 {
+        bus_read(memory, address).unwrap()
+    }
+
+    // One frame sequencer step, 1/512 s
+    const STEP: u32 = 8192;
+
+    fn play_square2(memory: &mut Memory, length: u8) {
+        bus_write(memory, 0xFF16, 0x80 | length);
+        bus_write(memory, 0xFF17, 0xF0);
+        bus_write(memory, 0xFF18, 0x00);
+        bus_write(memory, 0xFF19, 0xC7);
+    }
+
+    #[test]
+    fn test_power() {
+        let mut memory = Memory::new();
+        assert!(memory.apu.power());
+        bus_write(&mut memory, 0xFF24, 0x77);
+        bus_write(&mut memory, 0xFF12, 0xF0);
+        bus_write(&mut memory, 0xFF14, 0x80);
+        assert_eq!(read(&memory, 0xFF26), 0xF1);
+
+        bus_write(&mut memory, 0xFF30, 0x12);
+        bus_write(&mut memory, 0xFF26, 0x00);
+        assert_eq!(read(&memory, 0xFF26), 0x70);
+        assert_eq!(read(&memory, 0xFF24), 0x00);
+        assert_eq!(read(&memory, 0xFF12), 0x00);
+        // Registers ignore writes while the power is off, wave RAM doesn't
+        bus_write(&mut memory, 0xFF24, 0x77);
+        assert_eq!(read(&memory, 0xFF24), 0x00);
+        bus_write(&mut memory, 0xFF31, 0x34);
+        assert_eq!((read(&memory, 0xFF30), read(&memory, 0xFF31)), (0x12, 0x34));
+
+        bus_write(&mut memory, 0xFF26, 0x80);
+        bus_write(&mut memory, 0xFF24, 0x77);
+        assert_eq!(read(&memory, 0xFF24), 0x77);
+    }
+
+    #[test]
+    fn test_length_counter() {
+        let mut memory = Memory::new();
+        // 64 - 60 = 4 length clocks at 256 Hz
+        play_square2(&mut memory, 60);
+        assert!(memory.apu.channel_on(1));
+        assert_eq!(read(&memory, 0xFF26) & 0x02, 0x02);
+        bus_tick(&mut memory, STEP * 6);
+        assert!(memory.apu.channel_on(1));
+        bus_tick(&mut memory, STEP * 2);
+        assert!(!memory.apu.channel_on(1));
+        assert_eq!(read(&memory, 0xFF26) & 0x02, 0x00);
+
+        // Without NR24 bit 6 the channel keeps playing
+        bus_write(&mut memory, 0xFF16, 0x80 | 63);
+        bus_write(&mut memory, 0xFF19, 0x80);
+        bus_tick(&mut memory, STEP * 16);
+        assert!(memory.apu.channel_on(1));
+    }
+
+    #[test]
+    fn test_dac() {
+        let mut memory = Memory::new();
+        play_square2(&mut memory, 0);
+        // Volume 0 and decreasing turns the DAC off, and the channel with it
+        bus_write(&mut memory, 0xFF17, 0x00);
+        assert!(!memory.apu.channel_on(1));
+        bus_write(&mut memory, 0xFF19, 0x80);
+        assert!(!memory.apu.channel_on(1));
+        // A DAC that is on but silent still outputs its lowest level
+        bus_write(&mut memory, 0xFF17, 0x08);
+        assert_eq!(memory.apu.dac_output(1), -15);
+    }
+
+    #[test]
+    fn test_envelope() {
+        let mut memory = Memory::new();
+        bus_write(&mut memory, 0xFF21, 0xF1);
+        bus_write(&mut memory, 0xFF23, 0x80);
+        assert_eq!(memory.apu.noise.envelope.volume, 15);
+        // One step at 64 Hz
+        bus_tick(&mut memory, STEP * 8);
+        assert_eq!(memory.apu.noise.envelope.volume, 14);
+        bus_tick(&mut memory, STEP * 8 * 20);
+        assert_eq!(memory.apu.noise.envelope.volume, 0);
+        // The channel stays on at volume 0
+        assert!(memory.apu.channel_on(3));
+    }
+
+    #[test]
+    fn test_sweep() {
+        let mut memory = Memory::new();
+        // Overflowing right away disables the channel on trigger
+        bus_write(&mut memory, 0xFF10, 0x11);
+        bus_write(&mut memory, 0xFF12, 0xF0);
+        bus_write(&mut memory, 0xFF13, 0xFF);
+        bus_write(&mut memory, 0xFF14, 0x87);
+        assert!(!memory.apu.channel_on(0));
+
+        // 0x100 + 0x100 >> 1, every 1/128 s, written back to NR13/NR14
+        bus_write(&mut memory, 0xFF13, 0x00);
+        bus_write(&mut memory, 0xFF14, 0x81);
+        bus_tick(&mut memory, STEP * 4);
+        assert_eq!(memory.apu.square1.frequency, 0x180);
+        assert_eq!(
+            (memory.io.get(0xFF13), memory.io.get(0xFF14) & 0x7),
+            (0x80, 0x1)
+        );
+        bus_tick(&mut memory, STEP * 4);
+        assert_eq!(memory.apu.square1.frequency, 0x240);
+        assert!(memory.apu.channel_on(0));
+    }
+
+    #[test]
+    fn test_noise_lfsr() {
+        let mut memory = Memory::new();
+        bus_write(&mut memory, 0xFF21, 0xF0);
+        // 7 bit mode, shortest period: the output repeats every 127 steps
+        bus_write(&mut memory, 0xFF22, 0x08);
+        bus_write(&mut memory, 0xFF23, 0x80);
+        let mut states = vec![];
+        for _ in 0..254 {
+            bus_tick(&mut memory, 8);
+            states.push(memory.apu.noise.lfsr & 0x7F);
+        }
+        assert_eq!(states[..127], states[127..]);
+        assert!(states[..127].iter().any(|&state| state != states[0]));
+    }
+
+    #[test]
+    fn test_mix() {
+        let mut memory = Memory::new();
+        bus_write(&mut memory, 0xFF30, 0xFF);
+        bus_write(&mut memory, 0xFF1A, 0x80);
+        bus_write(&mut memory, 0xFF1C, 0x20);
+        bus_write(&mut memory, 0xFF1D, 0xFF);
+        bus_write(&mut memory, 0xFF1E, 0x87);
+        // Channel 3 only on the left, at full volume there
+        bus_write(&mut memory, 0xFF24, 0x70);
+        bus_write(&mut memory, 0xFF25, 0x40);
+        memory.apu.set_capture(true);
+        bus_tick(&mut memory, 4 * 64);
+        let samples = memory.apu.take_samples();
+        assert_eq!(samples.len(), 64);
+        assert!(samples.iter().all(|sample| sample[1] == 0));
+        let (low, high) = (-15 * 8 * 68, 15 * 8 * 68);
+        assert!(samples.contains(&[high, 0]));
+        assert!(samples.contains(&[low, 0]));
+        assert!(memory.apu.take_samples().is_empty());
+        assert_eq!(Apu::SAMPLE_RATE, 4194304 / 4);
+    }
+}
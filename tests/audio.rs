@@ -0,0 +1,101 @@
+mod common;
+
+#[cfg(test)]
+mod audio_test {
+
+    use blazeboy::{bus_write, AudioDevice, BlipBuffer, GameBoy, HighPass, NullAudio, RateControl};
+
+    use crate::common::loop_rom;
+
+    const CLOCK: u32 = 1048576;
+
+    #[test]
+    fn test_blip_step() {
+        let mut blip = BlipBuffer::new(CLOCK, 48000);
+        blip.push(std::iter::repeat_n(1000, CLOCK as usize / 10));
+        let output = blip.read();
+        // A tenth of a second, give or take the sample still in progress
+        assert!((4799..=4800).contains(&output.len()), "{}", output.len());
+        assert!(output[0].abs() < 1.0);
+        assert!(output[100..]
+            .iter()
+            .all(|&sample| (sample - 1000.0).abs() < 1.0));
+        // Reading again only gives what's new
+        assert!(blip.read().is_empty());
+        blip.push(std::iter::repeat_n(1000, 22));
+        assert_eq!(blip.read().len(), 1);
+    }
+
+    #[test]
+    fn test_blip_band_limited() {
+        // Half the input rate, far above what 48 kHz can hold: comes out as its average
+        let mut blip = BlipBuffer::new(CLOCK, 48000);
+        blip.push((0..CLOCK / 10).map(|i| if i % 2 == 0 { 2000 } else { 0 }));
+        let output = blip.read();
+        assert!(output[100..]
+            .iter()
+            .all(|&sample| (sample - 1000.0).abs() < 50.0));
+    }
+
+    #[test]
+    fn test_high_pass() {
+        let mut high_pass = HighPass::new(48000);
+        let first = high_pass.apply(1000.0);
+        assert_eq!(first, 1000.0);
+        let mut last = first;
+        for _ in 0..48000 {
+            last = high_pass.apply(1000.0);
+        }
+        assert!(last.abs() < 1.0, "{}", last);
+    }
+
+    #[test]
+    fn test_rate_control() {
+        let rate_control = RateControl::new(48000);
+        assert_eq!(rate_control.target, 2400);
+        assert_eq!(rate_control.adjust(0), 1.005);
+        assert_eq!(rate_control.adjust(2400), 1.0);
+        assert_eq!(rate_control.adjust(100000), 0.995);
+    }
+
+    #[test]
+    fn test_null_device() {
+        let mut device = NullAudio::new(44100);
+        device.playback = 10;
+        device.queue(&[0; 30]).unwrap();
+        assert_eq!((device.frames_written, device.queued()), (15, 5));
+        device.queue(&[0; 4]).unwrap();
+        assert_eq!(device.queued(), 0);
+    }
+
+    #[test]
+    fn test_gameboy_audio() {
+        let mut gameboy = GameBoy::from_catridge(loop_rom());
+        let mut device = NullAudio::new(48000);
+        // Plays exactly in real time
+        device.playback = 800;
+        gameboy.start_audio(Box::new(device));
+        bus_write(&mut gameboy.memory, 0xFF17, 0xF0);
+        bus_write(&mut gameboy.memory, 0xFF19, 0x87);
+
+        for _ in 0..60 {
+            gameboy.run_frame();
+        }
+        let queued = gameboy.audio.as_ref().unwrap().device.queued();
+        // Rate control keeps the queue from growing or running dry
+        assert!(queued > 0 && queued < 2400, "{}", queued);
+
+        let mut audio = gameboy.audio.take().unwrap();
+        gameboy.run_frame();
+        let samples = audio.update(&gameboy.memory.apu.take_samples()).unwrap();
+        // ~804 stereo frames per emulated frame at 48 kHz
+        assert!((1600..1620).contains(&samples.len()), "{}", samples.len());
+        assert!(samples.iter().any(|&sample| sample > 1000));
+        assert!(samples.iter().any(|&sample| sample < -1000));
+        gameboy.audio = Some(audio);
+
+        gameboy.stop_audio();
+        assert!(gameboy.audio.is_none());
+        assert!(gameboy.memory.apu.take_samples().is_empty());
+    }
+}
@@ -0,0 +1,133 @@
+#[cfg(test)]
+mod cgb_memory_test {
+
+    use blazeboy::{bus_hblank, bus_read, bus_write, Memory};
+
+    fn cgb_memory() -> Memory {
+        let mut memory = Memory::new();
+        memory.cgb_mode = true;
+        memory
+    }
+
+    fn setup_hdma(memory: &mut Memory, source: u16, destination: u16) {
+        for i in 0..0x80u16 {
+            bus_write(memory, source + i, i as u8);
+        }
+        bus_write(memory, 0xFF51, (source >> 8) as u8);
+        bus_write(memory, 0xFF52, source as u8);
+        bus_write(memory, 0xFF53, (destination >> 8) as u8);
+        bus_write(memory, 0xFF54, destination as u8);
+    }
+
+    #[test]
+    fn test_vram_banks() {
+        let mut memory = cgb_memory();
+        bus_write(&mut memory, 0x8000, 0x11);
+        bus_write(&mut memory, 0xFF4F, 1);
+        assert_eq!(bus_read(&memory, 0xFF4F).unwrap(), 0xFF);
+        bus_write(&mut memory, 0x8000, 0x22);
+        assert_eq!(memory.vram[0][0], 0x11);
+        assert_eq!(memory.vram[1][0], 0x22);
+        bus_write(&mut memory, 0xFF4F, 0);
+        assert_eq!(bus_read(&memory, 0xFF4F).unwrap(), 0xFE);
+        assert_eq!(bus_read(&memory, 0x8000).unwrap(), 0x11);
+    }
+
+    #[test]
+    fn test_wram_banks() {
+        let mut memory = cgb_memory();
+        for bank in 0..8u8 {
+            bus_write(&mut memory, 0xFF70, bank);
+            bus_write(&mut memory, 0xD000, 0x10 + bank);
+        }
+        // Bank 0 selects bank 1
+        bus_write(&mut memory, 0xFF70, 0);
+        assert_eq!(bus_read(&memory, 0xFF70).unwrap(), 0xF8);
+        assert_eq!(bus_read(&memory, 0xD000).unwrap(), 0x11);
+        bus_write(&mut memory, 0xFF70, 5);
+        assert_eq!(bus_read(&memory, 0xD000).unwrap(), 0x15);
+        assert_eq!(bus_read(&memory, 0xF000).unwrap(), 0x15);
+        bus_write(&mut memory, 0xC123, 0x77);
+        assert_eq!(bus_read(&memory, 0xE123).unwrap(), 0x77);
+    }
+
+    #[test]
+    fn test_dmg_ignores_banking() {
+        let mut memory = Memory::new();
+        bus_write(&mut memory, 0xD000, 0x33);
+        bus_write(&mut memory, 0xFF70, 3);
+        bus_write(&mut memory, 0xFF4F, 1);
+        assert_eq!(bus_read(&memory, 0xD000).unwrap(), 0x33);
+        bus_write(&mut memory, 0x9000, 0x44);
+        assert_eq!(memory.vram[0][0x1000], 0x44);
+    }
+
+    #[test]
+    fn test_general_dma() {
+        let mut memory = cgb_memory();
+        setup_hdma(&mut memory, 0xC000, 0x8800);
+        bus_write(&mut memory, 0xFF55, 0x03);
+        for i in 0..0x40usize {
+            assert_eq!(memory.vram[0][0x800 + i], i as u8);
+        }
+        assert_eq!(bus_read(&memory, 0xFF55).unwrap(), 0xFF);
+        assert_eq!(memory.take_stall(), 4 * 32);
+        assert_eq!(memory.take_stall(), 0);
+    }
+
+    #[test]
+    fn test_hblank_dma() {
+        let mut memory = cgb_memory();
+        setup_hdma(&mut memory, 0xC000, 0x9000);
+        bus_write(&mut memory, 0xFF55, 0x81);
+        assert_eq!(bus_read(&memory, 0xFF55).unwrap(), 0x01);
+        assert_eq!(memory.vram[0][0x1000], 0x00);
+        assert_eq!(memory.vram[0][0x1001], 0x00);
+
+        bus_hblank(&mut memory);
+        assert_eq!(memory.vram[0][0x100F], 0x0F);
+        assert_eq!(memory.vram[0][0x1010], 0x00);
+        assert_eq!(bus_read(&memory, 0xFF55).unwrap(), 0x00);
+        assert_eq!(memory.take_stall(), 32);
+
+        bus_hblank(&mut memory);
+        assert_eq!(memory.vram[0][0x101F], 0x1F);
+        assert_eq!(bus_read(&memory, 0xFF55).unwrap(), 0xFF);
+
+        bus_hblank(&mut memory);
+        assert_eq!(memory.vram[0][0x1020], 0x00);
+    }
+
+    #[test]
+    fn test_hblank_dma_cancel() {
+        let mut memory = cgb_memory();
+        setup_hdma(&mut memory, 0xC000, 0x8000);
+        bus_write(&mut memory, 0xFF55, 0x83);
+        bus_hblank(&mut memory);
+        bus_write(&mut memory, 0xFF55, 0x00);
+        assert_eq!(bus_read(&memory, 0xFF55).unwrap(), 0x82);
+
+        bus_hblank(&mut memory);
+        assert_eq!(memory.vram[0][0x10], 0x00);
+        assert_eq!(memory.vram[0][0x0F], 0x0F);
+    }
+
+    #[test]
+    fn test_random_values() {
+        // 0x1000 random bytes all coming out 0 won't happen
+        let memory = Memory::new_random_values();
+        assert!(memory.vram[1].iter().any(|&value| value != 0));
+        assert!(memory.wram[7].iter().any(|&value| value != 0));
+
+        let mut memory = cgb_memory();
+        bus_write(&mut memory, 0xFF70, 3);
+        for _ in 0..16 {
+            memory.set_random_number_at_addr(0xD123);
+            if memory.wram[3][0x123] != 0 {
+                break;
+            }
+        }
+        assert_ne!(memory.wram[3][0x123], 0);
+        assert_eq!(bus_read(&memory, 0xD123), Some(memory.wram[3][0x123]));
+    }
+}
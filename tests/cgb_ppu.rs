@@ -0,0 +1,172 @@
+#[cfg(test)]
+mod cgb_ppu_test {
+
+    use blazeboy::{bus_read, bus_tick, bus_write, Memory, Renderer, SCREEN_WIDTH};
+
+    const LINE: u32 = 456;
+    const RED: u16 = 0x001F;
+    const GREEN: u16 = 0x03E0;
+    const BLUE: u16 = 0x7C00;
+
+    fn cgb_memory() -> Memory {
+        let mut memory = Memory::new();
+        memory.cgb_mode = true;
+        // Tile 1 in bank 0 is solid colour 3, tile 1 in bank 1 is solid colour 1
+        for row in 0..8 {
+            memory.vram[0][0x10 + row * 2] = 0xFF;
+            memory.vram[0][0x10 + row * 2 + 1] = 0xFF;
+            memory.vram[1][0x10 + row * 2] = 0xFF;
+        }
+        memory
+    }
+
+    fn set_palette(memory: &mut Memory, spec: u16, palette: u8, colors: [u16; 4]) {
+        bus_write(memory, spec, 0x80 | (palette * 8));
+        for color in colors {
+            bus_write(memory, spec + 1, color as u8);
+            bus_write(memory, spec + 1, (color >> 8) as u8);
+        }
+    }
+
+    fn pixel(memory: &Memory, x: usize, y: usize) -> u16 {
+        memory.ppu.color_framebuffer[y * SCREEN_WIDTH + x]
+    }
+
+    fn run_frame(memory: &mut Memory) {
+        bus_tick(memory, LINE * 154);
+    }
+
+    #[test]
+    fn test_palette_auto_increment() {
+        let mut memory = cgb_memory();
+        bus_write(&mut memory, 0xFF68, 0x82);
+        bus_write(&mut memory, 0xFF69, 0x12);
+        bus_write(&mut memory, 0xFF69, 0x34);
+        assert_eq!(bus_read(&memory, 0xFF68).unwrap(), 0xC4);
+
+        bus_write(&mut memory, 0xFF68, 0x03);
+        assert_eq!(bus_read(&memory, 0xFF69).unwrap(), 0x34);
+        bus_write(&mut memory, 0xFF69, 0x56);
+        assert_eq!(bus_read(&memory, 0xFF68).unwrap(), 0x43);
+        assert_eq!(bus_read(&memory, 0xFF69).unwrap(), 0x56);
+
+        // The index wraps around inside the 64 bytes
+        bus_write(&mut memory, 0xFF6A, 0xBF);
+        bus_write(&mut memory, 0xFF6B, 0x99);
+        assert_eq!(bus_read(&memory, 0xFF6A).unwrap(), 0xC0);
+        assert_eq!(memory.ppu.palettes.objects[0x3F], 0x99);
+    }
+
+    #[test]
+    fn test_palette_blocked_in_mode3() {
+        let mut memory = cgb_memory();
+        bus_write(&mut memory, 0xFF40, 0x80);
+        bus_tick(&mut memory, 4 + 80);
+        bus_write(&mut memory, 0xFF68, 0x80);
+        bus_write(&mut memory, 0xFF69, 0x00);
+        assert_eq!(bus_read(&memory, 0xFF69).unwrap(), 0xFF);
+        assert_eq!(bus_read(&memory, 0xFF68).unwrap(), 0xC1);
+        assert_eq!(memory.ppu.palettes.background[0], 0xFF);
+    }
+
+    #[test]
+    fn test_bg_attributes() {
+        let mut memory = cgb_memory();
+        set_palette(&mut memory, 0xFF68, 2, [0, GREEN, 0, RED]);
+        memory.vram[0][0x1800] = 1;
+        memory.vram[1][0x1800] = 0x02;
+        // Same tile from bank 1
+        memory.vram[0][0x1801] = 1;
+        memory.vram[1][0x1801] = 0x0A;
+        // Left half of the tile is colour 1, flipped horizontally
+        memory.vram[0][0x1802] = 2;
+        memory.vram[1][0x1802] = 0x22;
+        for row in 0..8 {
+            memory.vram[0][0x20 + row * 2] = 0xF0;
+        }
+        bus_write(&mut memory, 0xFF40, 0x91);
+        run_frame(&mut memory);
+        assert_eq!(pixel(&memory, 0, 0), RED);
+        assert_eq!(pixel(&memory, 8, 0), GREEN);
+        assert_eq!(pixel(&memory, 16, 0), 0);
+        assert_eq!(pixel(&memory, 20, 0), GREEN);
+    }
+
+    #[test]
+    fn test_priority() {
+        let mut memory = cgb_memory();
+        set_palette(&mut memory, 0xFF68, 0, [0, 0, 0, RED]);
+        set_palette(&mut memory, 0xFF6A, 1, [0, 0, 0, BLUE]);
+        // BG priority attribute on the first tile only
+        memory.vram[0][0x1800] = 1;
+        memory.vram[1][0x1800] = 0x80;
+        memory.vram[0][0x1801] = 1;
+        memory.data[0xFE00..0xFE08].copy_from_slice(&[16, 8, 1, 0x01, 16, 16, 1, 0x01]);
+        bus_write(&mut memory, 0xFF40, 0x93);
+        run_frame(&mut memory);
+        assert_eq!(pixel(&memory, 0, 0), RED);
+        assert_eq!(pixel(&memory, 8, 0), BLUE);
+
+        // Clearing LCDC bit 0 puts every sprite above the background
+        let mut memory = cgb_memory();
+        set_palette(&mut memory, 0xFF68, 0, [0, 0, 0, RED]);
+        set_palette(&mut memory, 0xFF6A, 1, [0, 0, 0, BLUE]);
+        memory.vram[0][0x1800] = 1;
+        memory.vram[1][0x1800] = 0x80;
+        memory.data[0xFE00..0xFE04].copy_from_slice(&[16, 8, 1, 0x81]);
+        bus_write(&mut memory, 0xFF40, 0x92);
+        run_frame(&mut memory);
+        assert_eq!(pixel(&memory, 0, 0), BLUE);
+        assert_eq!(pixel(&memory, 8, 0), 0);
+    }
+
+    #[test]
+    fn test_sprite_oam_priority() {
+        for renderer in [Renderer::Scanline, Renderer::Fifo] {
+            let mut memory = cgb_memory();
+            memory.ppu.renderer = renderer;
+            set_palette(&mut memory, 0xFF6A, 1, [0, 0, 0, BLUE]);
+            set_palette(&mut memory, 0xFF6A, 2, [0, GREEN, 0, 0]);
+            // Sprite 0 is further right but wins over sprite 1, which uses VRAM bank 1
+            memory.data[0xFE00..0xFE08].copy_from_slice(&[16, 12, 1, 0x01, 16, 8, 1, 0x0A]);
+            bus_write(&mut memory, 0xFF40, 0x82);
+            run_frame(&mut memory);
+            assert_eq!(pixel(&memory, 3, 0), GREEN, "{:?}", renderer);
+            assert_eq!(pixel(&memory, 4, 0), BLUE, "{:?}", renderer);
+            assert_eq!(pixel(&memory, 11, 0), BLUE, "{:?}", renderer);
+
+            // OPRI switches back to the DMG X priority
+            bus_write(&mut memory, 0xFF6C, 0x01);
+            run_frame(&mut memory);
+            assert_eq!(pixel(&memory, 4, 0), GREEN, "{:?}", renderer);
+        }
+    }
+
+    #[test]
+    fn test_fifo_matches_scanline() {
+        let mut frames = Vec::new();
+        for renderer in [Renderer::Scanline, Renderer::Fifo] {
+            let mut memory = cgb_memory();
+            memory.ppu.renderer = renderer;
+            for palette in 0..8 {
+                let colors = [0, 0x1111 * palette as u16, 0x0842, 0x7FFF - palette as u16];
+                set_palette(&mut memory, 0xFF68, palette, colors);
+                set_palette(&mut memory, 0xFF6A, palette, colors);
+            }
+            for offset in 0..0x800 {
+                memory.vram[0][0x1800 + offset] = (offset % 2) as u8;
+                memory.vram[1][0x1800 + offset] = (offset * 37 % 256) as u8;
+            }
+            memory.data[0xFE00..0xFE0C]
+                .copy_from_slice(&[20, 4, 1, 0x2B, 30, 50, 1, 0x95, 30, 52, 1, 0x03]);
+            bus_write(&mut memory, 0xFF42, 3);
+            bus_write(&mut memory, 0xFF43, 5);
+            bus_write(&mut memory, 0xFF4A, 60);
+            bus_write(&mut memory, 0xFF4B, 40);
+            bus_write(&mut memory, 0xFF40, 0xF3);
+            run_frame(&mut memory);
+            frames.push(memory.ppu.color_framebuffer);
+        }
+        assert!(frames[0] == frames[1]);
+    }
+}
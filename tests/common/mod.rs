@@ -0,0 +1,18 @@
+// Helpers shared by the test files, each one only uses some of them
+#![allow(dead_code)]
+
+use blazeboy::Catridge;
+
+// A file name in the temp dir that won't clash with another test run
+pub fn temp_file(name: &str) -> String {
+    let path = std::env::temp_dir().join(format!("blazeboy-{}-{}", std::process::id(), name));
+    path.to_string_lossy().into_owned()
+}
+
+pub fn loop_rom() -> Catridge {
+    // JP 0x0100 at the entry point
+    let mut rom = vec![0u8; 0x8000];
+    rom[0x100..0x103].copy_from_slice(&[0xC3, 0x00, 0x01]);
+    rom[0x134..0x138].copy_from_slice(b"LOOP");
+    Catridge::from_bytes(rom).unwrap()
+}
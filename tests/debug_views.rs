@@ -0,0 +1,120 @@
+#[cfg(test)]
+mod debug_views_test {
+
+    use blazeboy::{
+        bus_write, oam_entries, oam_table, DebugPalette, DmgPalette, GameBoy, Memory, VideoOutput,
+        BACKDROP_COLOR, VIEWPORT_COLOR,
+    };
+
+    // Tile 1 solid colour 3, tile 2 colour 1 on its top row only
+    fn draw_tiles(memory: &mut Memory) {
+        memory.vram[0][0x10..0x20].fill(0xFF);
+        memory.vram[0][0x20] = 0xFF;
+        bus_write(memory, 0xFF47, 0xE4);
+        bus_write(memory, 0xFF48, 0xE4);
+        bus_write(memory, 0xFF49, 0x1B);
+    }
+
+    #[test]
+    fn test_tile_sheet() {
+        let mut memory = Memory::new();
+        draw_tiles(&mut memory);
+        let output = VideoOutput::new();
+        let colors = DmgPalette::Grey.colors();
+
+        let sheet = output.tile_sheet(&memory, DebugPalette::Bgp);
+        assert_eq!((sheet.width, sheet.height), (128, 192));
+        assert_eq!(sheet.get(0, 0), colors[0]);
+        assert_eq!(sheet.get(8, 0), colors[3]);
+        assert_eq!(sheet.get(15, 7), colors[3]);
+        assert_eq!(sheet.get(16, 0), colors[1]);
+        assert_eq!(sheet.get(16, 1), colors[0]);
+
+        // OBP1 is inverted
+        let sheet = output.tile_sheet(&memory, DebugPalette::Obp1);
+        assert_eq!(sheet.get(0, 0), colors[3]);
+        assert_eq!(sheet.get(8, 0), colors[0]);
+
+        memory.cgb_mode = true;
+        memory.vram[1][0x10..0x20].fill(0xFF);
+        let sheet = output.tile_sheet(&memory, DebugPalette::Shades);
+        assert_eq!((sheet.width, sheet.height), (256, 192));
+        assert_eq!(sheet.get(128 + 8, 0), colors[3]);
+        assert_eq!(sheet.get(128 + 16, 0), colors[0]);
+        assert_eq!(sheet.rgba().len(), 256 * 192 * 4);
+    }
+
+    #[test]
+    fn test_tile_map_viewport() {
+        let mut memory = Memory::new();
+        draw_tiles(&mut memory);
+        memory.vram[0][0x1800 + 33] = 1;
+        memory.vram[0][0x1C00] = 1;
+        bus_write(&mut memory, 0xFF40, 0x91);
+        bus_write(&mut memory, 0xFF42, 200);
+        bus_write(&mut memory, 0xFF43, 120);
+        let output = VideoOutput::new();
+        let colors = DmgPalette::Grey.colors();
+
+        let map = output.tile_map(&memory, 0x9800);
+        assert_eq!((map.width, map.height), (256, 256));
+        assert_eq!(map.get(8, 8), colors[3]);
+        assert_eq!(map.get(0, 8), colors[0]);
+        let map = output.tile_map(&memory, 0x9C00);
+        assert_eq!(map.get(1, 1), colors[3]);
+
+        // The viewport wraps past the right and bottom edges
+        assert_eq!(map.get(120, 200), VIEWPORT_COLOR);
+        assert_eq!(map.get(255, 200), VIEWPORT_COLOR);
+        assert_eq!(map.get(23, 200), VIEWPORT_COLOR);
+        assert_eq!(map.get(24, 200), colors[0]);
+        assert_eq!(map.get(120, 87), VIEWPORT_COLOR);
+        assert_eq!(map.get(279 % 256, 50), VIEWPORT_COLOR);
+        assert_eq!(map.get(121, 201), colors[0]);
+    }
+
+    #[test]
+    fn test_oam_views() {
+        let mut memory = Memory::new();
+        draw_tiles(&mut memory);
+        bus_write(&mut memory, 0xFF40, 0x93);
+        // Sprite 1: tile 2 flipped vertically through OBP1, sprite 9: tile 1
+        memory.data[0xFE04..0xFE08].copy_from_slice(&[16, 8, 2, 0x50]);
+        memory.data[0xFE24..0xFE28].copy_from_slice(&[40, 30, 1, 0x80]);
+
+        let entries = oam_entries(&memory);
+        assert_eq!(entries.len(), 40);
+        assert_eq!((entries[9].y, entries[9].x, entries[9].tile), (40, 30, 1));
+
+        let output = VideoOutput::new();
+        let colors = DmgPalette::Grey.colors();
+        let sheet = output.oam_sheet(&memory);
+        assert_eq!((sheet.width, sheet.height), (80, 90));
+        assert_eq!(sheet.get(11, 8), colors[2]);
+        assert_eq!(sheet.get(11, 1), BACKDROP_COLOR);
+        assert_eq!(sheet.get(11, 18), 0);
+        assert_eq!(sheet.get(11, 19), colors[3]);
+
+        let table = oam_table(&memory);
+        assert_eq!(table.lines().count(), 41);
+        let line = table.lines().nth(10).unwrap();
+        assert!(line.starts_with(" 9  40  30"), "{}", line);
+        assert!(line.contains("0x01 0x80"), "{}", line);
+        assert!(line.ends_with("true"), "{}", line);
+    }
+
+    #[test]
+    fn test_dump_vram() {
+        let mut gameboy = GameBoy::new();
+        draw_tiles(&mut gameboy.memory);
+        let dir = std::env::temp_dir().join(format!("blazeboy-vram-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let table = gameboy.dump_vram(dir.to_str().unwrap()).unwrap();
+        assert_eq!(table.lines().count(), 41);
+        for name in ["tiles.png", "map_9800.png", "map_9C00.png", "oam.png"] {
+            assert!(dir.join(name).exists(), "{}", name);
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(gameboy.dump_vram("/nonexistent/dir").is_err());
+    }
+}
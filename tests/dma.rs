@@ -0,0 +1,81 @@
+#[cfg(test)]
+mod dma_test {
+
+    use blazeboy::{bus_read, bus_tick, bus_write, Memory};
+
+    fn fill_source(memory: &mut Memory, base: u16) {
+        for i in 0..0xA0u16 {
+            bus_write(memory, base + i, i as u8 ^ 0x5A);
+        }
+    }
+
+    #[test]
+    fn test_dma_copies_after_160_cycles() {
+        let mut memory = Memory::new();
+        fill_source(&mut memory, 0xC100);
+        bus_write(&mut memory, 0xFF46, 0xC1);
+
+        // One M-cycle of setup, then 160 M-cycles of copying
+        bus_tick(&mut memory, 4);
+        assert!(memory.oam_dma.is_active());
+        for _ in 0..0xA0 {
+            assert_eq!(bus_read(&memory, 0xFE00).unwrap(), 0xFF);
+            bus_tick(&mut memory, 4);
+        }
+        assert!(!memory.oam_dma.is_active());
+        for i in 0..0xA0u16 {
+            assert_eq!(bus_read(&memory, 0xFE00 + i).unwrap(), i as u8 ^ 0x5A);
+        }
+    }
+
+    #[test]
+    fn test_dma_bus_conflicts() {
+        let mut memory = Memory::new();
+        fill_source(&mut memory, 0xC000);
+        memory.data[0xFF80] = 0x42;
+        bus_write(&mut memory, 0xD000, 0x99);
+        bus_write(&mut memory, 0xFF46, 0xC0);
+        bus_tick(&mut memory, 8);
+
+        // The last byte read by the DMA shows up everywhere outside of HRAM
+        assert_eq!(bus_read(&memory, 0xD000).unwrap(), 0x5A);
+        assert_eq!(bus_read(&memory, 0x0150).unwrap(), 0x5A);
+        assert_eq!(bus_read(&memory, 0xFF80).unwrap(), 0x42);
+
+        bus_write(&mut memory, 0xD000, 0x11);
+        bus_write(&mut memory, 0xFF81, 0x22);
+        bus_tick(&mut memory, 0xA0 * 4);
+        assert_eq!(bus_read(&memory, 0xD000).unwrap(), 0x99);
+        assert_eq!(bus_read(&memory, 0xFF81).unwrap(), 0x22);
+    }
+
+    #[test]
+    fn test_dma_restart() {
+        let mut memory = Memory::new();
+        fill_source(&mut memory, 0xC000);
+        for i in 0..0xA0u16 {
+            bus_write(&mut memory, 0xC200 + i, 0xEE);
+        }
+        bus_write(&mut memory, 0xFF46, 0xC0);
+        bus_tick(&mut memory, 4 * 11);
+
+        // The old transfer keeps OAM locked while the new one is being set up
+        bus_write(&mut memory, 0xFF46, 0xC2);
+        bus_tick(&mut memory, 4);
+        assert!(memory.oam_dma.is_active());
+        assert_eq!(memory.data[0xFE0A], 0x0A ^ 0x5A);
+        bus_tick(&mut memory, 0xA0 * 4);
+        assert!(!memory.oam_dma.is_active());
+        assert_eq!(bus_read(&memory, 0xFE00).unwrap(), 0xEE);
+        assert_eq!(bus_read(&memory, 0xFE9F).unwrap(), 0xEE);
+    }
+
+    #[test]
+    fn test_dma_from_echo_ram() {
+        let mut memory = Memory::new();
+        fill_source(&mut memory, 0xDE00);
+        bus_write(&mut memory, 0xFF46, 0xFE);
+        bus_tick(&mut memory, 4 * 0xA1);
+        assert_eq!(bus_read(&memory, 0xFE10).unwrap(), 0x10 ^ 0x5A);
+    }
+}
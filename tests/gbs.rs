@@ -0,0 +1,153 @@
+#[cfg(test)]
+mod gbs_test {
+
+    use blazeboy::{bus_read, Gbs, GbsPlayer};
+
+    // Init stores the track at 0xC000, play counts its calls at 0xC001
+    const INIT: [u8; 4] = [0xEA, 0x00, 0xC0, 0xC9];
+    const PLAY: [u8; 5] = [0x21, 0x01, 0xC0, 0x34, 0xC9];
+
+    fn gbs_file(init: &[u8], play: &[u8], timer: (u8, u8), size: usize) -> Vec<u8> {
+        let mut file = vec![0; 0x70];
+        file[0..3].copy_from_slice(b"GBS");
+        file[0x03] = 1;
+        // 3 songs starting at the second one
+        (file[0x04], file[0x05]) = (3, 2);
+        file[0x06..0x08].copy_from_slice(&0x0400u16.to_le_bytes());
+        file[0x08..0x0A].copy_from_slice(&0x0400u16.to_le_bytes());
+        file[0x0A..0x0C].copy_from_slice(&0x0480u16.to_le_bytes());
+        file[0x0C..0x0E].copy_from_slice(&0xDFFEu16.to_le_bytes());
+        (file[0x0E], file[0x0F]) = timer;
+        file[0x10..0x15].copy_from_slice(b"Title");
+        file[0x30..0x36].copy_from_slice(b"Author");
+        file[0x50..0x54].copy_from_slice(b"2024");
+        let mut data = vec![0; size];
+        data[..init.len()].copy_from_slice(init);
+        data[0x80..0x80 + play.len()].copy_from_slice(play);
+        file.extend(data);
+        file
+    }
+
+    fn wram(player: &GbsPlayer, address: u16) -> u8 {
+        bus_read(&player.gameboy.memory, address).unwrap()
+    }
+
+    #[test]
+    fn test_header() {
+        let gbs = Gbs::from_bytes(&gbs_file(&INIT, &PLAY, (0x00, 0x04), 0x100)).unwrap();
+        assert_eq!((gbs.version, gbs.songs, gbs.first_song), (1, 3, 2));
+        assert_eq!(gbs.load_address, 0x400);
+        assert_eq!((gbs.init_address, gbs.play_address), (0x400, 0x480));
+        assert_eq!(gbs.stack_pointer, 0xDFFE);
+        assert_eq!(
+            (gbs.title.as_str(), gbs.author.as_str()),
+            ("Title", "Author")
+        );
+        assert_eq!(gbs.copyright, "2024");
+        assert_eq!(gbs.data.len(), 0x100);
+        // 4096 Hz timer overflowing every 256 ticks
+        assert!(gbs.uses_timer());
+        assert_eq!(gbs.play_rate(), 16.0);
+
+        let mut file = gbs_file(&INIT, &PLAY, (0, 0), 0x100);
+        file[0] = b'X';
+        assert!(Gbs::from_bytes(&file).is_err());
+        let mut file = gbs_file(&INIT, &PLAY, (0, 0), 0x100);
+        file[0x06..0x08].copy_from_slice(&0x0100u16.to_le_bytes());
+        assert!(Gbs::from_bytes(&file).is_err());
+        assert!(Gbs::from_bytes(b"GBS").is_err());
+    }
+
+    #[test]
+    fn test_image() {
+        let gbs = Gbs::from_bytes(&gbs_file(&INIT, &PLAY, (0, 0), 0x100)).unwrap();
+        assert!((gbs.play_rate() - 59.73).abs() < 0.01);
+        let image = gbs.image();
+        assert_eq!(image.data.len(), 0x8000);
+        assert_eq!(image.data[0x400..0x404], INIT);
+        // RST 0x28 jumps to load address + 0x28
+        assert_eq!(image.data[0x28..0x2B], [0xC3, 0x28, 0x04]);
+        assert_eq!(image.data[0x50], 0xD9);
+    }
+
+    #[test]
+    fn test_vblank_play() {
+        let gbs = Gbs::from_bytes(&gbs_file(&INIT, &PLAY, (0, 0), 0x100)).unwrap();
+        let mut player = GbsPlayer::new(gbs);
+        assert_eq!(player.track(), 1);
+        for _ in 0..60 {
+            player.run_frame();
+        }
+        assert_eq!(wram(&player, 0xC000), 1);
+        assert!((59..=60).contains(&wram(&player, 0xC001)));
+
+        // Starting another track runs init again from a clean state
+        player.start(2);
+        assert_eq!(player.track(), 2);
+        player.run_frame();
+        assert_eq!(wram(&player, 0xC000), 2);
+        assert!(wram(&player, 0xC001) <= 1);
+        // Out of range tracks play the last one
+        player.start(7);
+        assert_eq!(player.track(), 2);
+    }
+
+    #[test]
+    fn test_timer_play() {
+        let gbs = Gbs::from_bytes(&gbs_file(&INIT, &PLAY, (0x00, 0x04), 0x100)).unwrap();
+        let mut player = GbsPlayer::new(gbs);
+        for _ in 0..60 {
+            player.run_frame();
+        }
+        // A second at 16 Hz
+        let calls = wram(&player, 0xC001);
+        assert!((15..=17).contains(&calls), "{}", calls);
+    }
+
+    #[test]
+    fn test_banked() {
+        // Play switches to bank 2 and copies its first byte to 0xC002
+        let play = [
+            0x3E, 0x02, 0xEA, 0x00, 0x20, 0xFA, 0x00, 0x40, 0xEA, 0x02, 0xC0, 0xC9,
+        ];
+        let mut file = gbs_file(&INIT, &play, (0, 0), 0xBC00);
+        file[0x70 + 0x8000 - 0x400] = 0x5A;
+        let gbs = Gbs::from_bytes(&file).unwrap();
+        assert_eq!(gbs.image().data.len(), 0xC000);
+        let mut player = GbsPlayer::new(gbs);
+        player.run(100000);
+        assert_eq!(wram(&player, 0xC002), 0x5A);
+    }
+
+    #[test]
+    fn test_render_wav() {
+        // Init starts a tone on square 2
+        let init = [0x3E, 0xF0, 0xE0, 0x17, 0x3E, 0x87, 0xE0, 0x19, 0xC9];
+        let file = gbs_file(&init, &PLAY, (0, 0), 0x100);
+        let filename = std::env::temp_dir()
+            .join(format!("blazeboy-{}-gbs.wav", std::process::id()))
+            .to_string_lossy()
+            .into_owned();
+        let render = || {
+            let mut player = GbsPlayer::new(Gbs::from_bytes(&file).unwrap());
+            let hash = player.render_wav(&filename, 44100, false, 1).unwrap();
+            assert!(player.gameboy.wav.is_none());
+            hash
+        };
+        let hash = render();
+        let bytes = std::fs::read(&filename).unwrap();
+        // A second of 16 bit stereo
+        assert!(
+            (176000..=176500).contains(&(bytes.len() - 44)),
+            "{}",
+            bytes.len()
+        );
+        let samples: Vec<i16> = bytes[44..]
+            .chunks_exact(2)
+            .map(|pair| i16::from_le_bytes([pair[0], pair[1]]))
+            .collect();
+        assert!(samples.iter().any(|&sample| sample > 1000));
+        assert_eq!(hash, render());
+        std::fs::remove_file(&filename).unwrap();
+    }
+}
@@ -0,0 +1,120 @@
+#[cfg(test)]
+mod input_test {
+
+    use blazeboy::{Action, Buttons, Device, Hotkey, Input, InputMap, InputState};
+
+    const PAD: Device = Device::Controller(3);
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Input::parse("key:X"), Some(Input::key("x")));
+        assert_eq!(Input::parse("button:DpUp"), Some(Input::button("dpup")));
+        assert_eq!(
+            Input::parse("axis:leftx-"),
+            Some(Input::Axis("leftx".to_string(), false))
+        );
+        assert_eq!(Input::parse("axis:leftx"), None);
+        assert_eq!(Input::parse("key:"), None);
+        assert_eq!(Input::parse("mouse:left"), None);
+    }
+
+    #[test]
+    fn test_default_map() {
+        let map = InputMap::default();
+        let actions: Vec<Action> = map.actions(&Input::key("Return")).collect();
+        assert_eq!(actions, vec![Action::Press(Buttons::START)]);
+        let actions: Vec<Action> = map.actions(&Input::key("tab")).collect();
+        assert_eq!(actions, vec![Action::Hotkey(Hotkey::FastForward)]);
+        let actions: Vec<Action> = map.actions(&Input::key("F7")).collect();
+        assert_eq!(actions, vec![Action::Hotkey(Hotkey::LoadState)]);
+        assert_eq!(map.deadzone, 8000);
+    }
+
+    #[test]
+    fn test_map_file() {
+        let json = r#"{
+            "deadzone": 16000,
+            "buttons": { "a": ["key:Space", "button:b"], "start": ["key:Return"] }
+        }"#;
+        let map = InputMap::from_json(json).unwrap();
+        assert_eq!(map.deadzone, 16000);
+        assert_eq!(map.turbo_rate, 3);
+        let actions: Vec<Action> = map.actions(&Input::button("b")).collect();
+        assert_eq!(actions, vec![Action::Press(Buttons::A)]);
+        // The buttons section is replaced as a whole, the other sections stay
+        assert_eq!(map.actions(&Input::key("x")).count(), 0);
+        assert_eq!(map.actions(&Input::key("Escape")).count(), 1);
+        let json = r#"{ "hotkeys": { "save_state": ["key:F1"] } }"#;
+        let map = InputMap::from_json(json).unwrap();
+        let actions: Vec<Action> = map.actions(&Input::key("f1")).collect();
+        assert_eq!(actions, vec![Action::Hotkey(Hotkey::SaveState)]);
+        assert_eq!(map.actions(&Input::key("F5")).count(), 0);
+
+        assert!(InputMap::from_json(r#"{ "buttons": { "c": ["key:C"] } }"#).is_err());
+        assert!(InputMap::from_json(r#"{ "hotkeys": { "quit": ["esc"] } }"#).is_err());
+        assert!(InputMap::from_json(r#"{ "deadzone": -1 }"#).is_err());
+        assert!(InputMap::from_json("[]").is_err());
+    }
+
+    #[test]
+    fn test_buttons() {
+        let mut state = InputState::new(InputMap::default());
+        state.press(Device::Keyboard, Input::key("x"));
+        state.press(PAD, Input::button("dpup"));
+        assert_eq!(state.buttons(), Buttons::A | Buttons::UP);
+        state.release(Device::Keyboard, &Input::key("x"));
+        assert_eq!(state.buttons(), Buttons::UP);
+    }
+
+    #[test]
+    fn test_deadzone() {
+        let mut state = InputState::new(InputMap::default());
+        state.axis(PAD, "leftx", 5000);
+        assert_eq!(state.buttons(), Buttons::NONE);
+        state.axis(PAD, "leftx", 20000);
+        assert_eq!(state.buttons(), Buttons::RIGHT);
+        state.axis(PAD, "leftx", -20000);
+        assert_eq!(state.buttons(), Buttons::LEFT);
+        state.axis(PAD, "leftx", -100);
+        assert_eq!(state.buttons(), Buttons::NONE);
+    }
+
+    #[test]
+    fn test_turbo() {
+        let mut state = InputState::new(InputMap::default());
+        state.press(Device::Keyboard, Input::key("s"));
+        let mut pressed = Vec::new();
+        for _ in 0..8 {
+            pressed.push(state.buttons() == Buttons::A);
+            state.next_frame();
+        }
+        let expected = [true, true, true, false, false, false, true, true];
+        assert_eq!(pressed, expected);
+    }
+
+    #[test]
+    fn test_hotkeys() {
+        let mut state = InputState::new(InputMap::default());
+        state.press(Device::Keyboard, Input::key("p"));
+        // Held keys don't repeat the hotkey
+        state.press(Device::Keyboard, Input::key("p"));
+        state.press(Device::Keyboard, Input::key("tab"));
+        let hotkeys = state.take_hotkeys();
+        assert_eq!(hotkeys, vec![Hotkey::Pause, Hotkey::FastForward]);
+        assert!(state.take_hotkeys().is_empty());
+        assert!(state.hotkey_held(Hotkey::FastForward));
+        state.release(Device::Keyboard, &Input::key("tab"));
+        assert!(!state.hotkey_held(Hotkey::FastForward));
+        assert_eq!(state.buttons(), Buttons::NONE);
+    }
+
+    #[test]
+    fn test_remove_device() {
+        let mut state = InputState::new(InputMap::default());
+        state.press(Device::Keyboard, Input::key("down"));
+        state.press(PAD, Input::button("a"));
+        state.press(Device::Controller(4), Input::button("b"));
+        state.remove_device(PAD);
+        assert_eq!(state.buttons(), Buttons::DOWN | Buttons::B);
+    }
+}
@@ -2,8 +2,8 @@
 mod instruction_test {
 
     use blazeboy::{
-        bus_read, get_bit, BitwiseOperator, CpuRegisters, Flag, Instruction, Memory, Register16Bit,
-        Register8Bit,
+        bus_read, bus_write, get_bit, BitwiseOperator, CpuRegisters, Flag, Instruction, Memory,
+        Register16Bit, Register8Bit,
     };
     use rand::Rng;
 
@@ -50,6 +50,14 @@ mod instruction_test {
         }
     }
 
+    // What reads back after writing `value` to `addr` on a fresh bus. IO registers keep
+    // only their writable bits and read the rest as 1
+    fn written(addr: u16, value: u8) -> u8 {
+        let mut memory = Memory::new();
+        bus_write(&mut memory, addr, value);
+        bus_read(&memory, addr).unwrap()
+    }
+
     fn check_instruction_props(instruction: &Instruction, length: u8, cycle: u8) {
         assert_eq!(
             instruction.length, length,
@@ -204,7 +212,7 @@ mod instruction_test {
         memory.set_random_number_at_addr(addr);
         instruction.ld_a_c(&mut registers, &mut memory);
         assert_eq!(
-            registers.get_8bit_reg_value(Register8Bit::A),
+            written(addr, registers.get_8bit_reg_value(Register8Bit::A)),
             bus_read(&memory, addr).unwrap()
         );
 
@@ -279,9 +287,10 @@ mod instruction_test {
         let random_address = thread_rng.gen::<u8>();
 
         instruction.ld_reg_8bit_to_addr_8bit(&mut registers, &mut memory, random_address);
+        let addr = 0xFF00 + random_address as u16;
         assert_eq!(
-            registers.get_8bit_reg_value(Register8Bit::A),
-            bus_read(&memory, 0xFF00 + random_address as u16).unwrap()
+            written(addr, registers.get_8bit_reg_value(Register8Bit::A)),
+            bus_read(&memory, addr).unwrap()
         );
 
         check_instruction_props(&instruction, 2, 12);
@@ -0,0 +1,91 @@
+#[cfg(test)]
+mod io_test {
+
+    use blazeboy::{bus_read, bus_write, Interrupt, IoRegister, Memory};
+
+    // Bits that read back as 1 after writing 0x00, taken from mooneye's unused_hwio-GS
+    const DMG_MASKS: [(u16, u8); 14] = [
+        (0xFF00, 0xCF),
+        (0xFF02, 0x7E),
+        (0xFF03, 0xFF),
+        (0xFF07, 0xF8),
+        (0xFF0F, 0xE0),
+        (0xFF10, 0x80),
+        (0xFF1A, 0x7F),
+        (0xFF1C, 0x9F),
+        (0xFF20, 0xFF),
+        (0xFF23, 0xBF),
+        (0xFF26, 0x70),
+        (0xFF41, 0x80),
+        (0xFF4F, 0xFF),
+        (0xFF70, 0xFF),
+    ];
+
+    #[test]
+    fn test_dmg_read_masks() {
+        let mut memory = Memory::new();
+        for (address, mask) in DMG_MASKS {
+            bus_write(&mut memory, address, 0x00);
+            assert_eq!(
+                bus_read(&memory, address).unwrap(),
+                mask,
+                "Reading {} after writing 0x00",
+                IoRegister::get(address, false).name
+            );
+            let read_only = IoRegister::get(address, false).read_only;
+            bus_write(&mut memory, address, 0xFF);
+            assert_eq!(bus_read(&memory, address).unwrap() | read_only, 0xFF);
+        }
+    }
+
+    #[test]
+    fn test_unmapped_registers() {
+        let mut memory = Memory::new();
+        for address in [0xFF03, 0xFF08, 0xFF0E, 0xFF15, 0xFF1F, 0xFF27, 0xFF4C, 0xFF7F] {
+            bus_write(&mut memory, address, 0x00);
+            assert_eq!(bus_read(&memory, address).unwrap(), 0xFF);
+        }
+    }
+
+    #[test]
+    fn test_write_only_registers() {
+        let mut memory = Memory::new();
+        bus_write(&mut memory, 0xFF13, 0x12);
+        assert_eq!(bus_read(&memory, 0xFF13).unwrap(), 0xFF);
+        assert_eq!(memory.io.get(0xFF13), 0x12);
+
+        bus_write(&mut memory, 0xFF11, 0x81);
+        assert_eq!(bus_read(&memory, 0xFF11).unwrap(), 0xBF);
+    }
+
+    #[test]
+    fn test_read_only_bits() {
+        let mut memory = Memory::new();
+        memory.io.set(0xFF41, 0x02);
+        bus_write(&mut memory, 0xFF41, 0x7D);
+        assert_eq!(bus_read(&memory, 0xFF41).unwrap(), 0xFA);
+
+        memory.io.set(0xFF44, 0x90);
+        bus_write(&mut memory, 0xFF44, 0x00);
+        assert_eq!(bus_read(&memory, 0xFF44).unwrap(), 0x90);
+    }
+
+    #[test]
+    fn test_cgb_registers() {
+        let mut memory = Memory::new();
+        memory.cgb_mode = true;
+        bus_write(&mut memory, 0xFF68, 0x00);
+        assert_eq!(bus_read(&memory, 0xFF68).unwrap(), 0x40);
+        bus_write(&mut memory, 0xFF02, 0x00);
+        assert_eq!(bus_read(&memory, 0xFF02).unwrap(), 0x7C);
+    }
+
+    #[test]
+    fn test_request_interrupt() {
+        let mut memory = Memory::new();
+        memory.io.request_interrupt(Interrupt::Timer);
+        memory.io.request_interrupt(Interrupt::Joypad);
+        assert_eq!(bus_read(&memory, 0xFF0F).unwrap(), 0xF4);
+        assert_eq!(memory.io.interrupt_flags(), 0x14);
+    }
+}
@@ -0,0 +1,116 @@
+#[cfg(test)]
+mod joypad_test {
+
+    use blazeboy::{bus_peek, bus_read, bus_write, Buttons, Catridge, GameBoy, Interrupt, Memory};
+
+    fn press(memory: &mut Memory, buttons: Buttons) {
+        memory.joypad.set_buttons(buttons, &mut memory.io);
+    }
+
+    fn joypad_interrupt(memory: &mut Memory) -> bool {
+        let flags = memory.io.get(0xFF0F);
+        memory.io.set(0xFF0F, flags & !Interrupt::Joypad.mask());
+        flags & Interrupt::Joypad.mask() != 0
+    }
+
+    #[test]
+    fn test_buttons() {
+        let mut buttons = Buttons::A | Buttons::UP;
+        assert!(buttons.contains(Buttons::A));
+        assert!(!buttons.contains(Buttons::A | Buttons::B));
+        buttons.set(Buttons::A, false);
+        buttons.set(Buttons::START, true);
+        assert_eq!(buttons, Buttons::START | Buttons::UP);
+    }
+
+    #[test]
+    fn test_matrix() {
+        let mut memory = Memory::new();
+        press(&mut memory, Buttons::A | Buttons::DOWN);
+        // Bit 5 low picks the buttons, bit 4 low the directions
+        bus_write(&mut memory, 0xFF00, 0x10);
+        assert_eq!(bus_read(&memory, 0xFF00), Some(0xDE));
+        bus_write(&mut memory, 0xFF00, 0x20);
+        assert_eq!(bus_read(&memory, 0xFF00), Some(0xE7));
+        bus_write(&mut memory, 0xFF00, 0x30);
+        assert_eq!(bus_read(&memory, 0xFF00), Some(0xFF));
+        // Both at once read as the AND of the two groups
+        bus_write(&mut memory, 0xFF00, 0x00);
+        assert_eq!(bus_read(&memory, 0xFF00), Some(0xC6));
+        // The lines can't be written
+        bus_write(&mut memory, 0xFF00, 0x2F);
+        assert_eq!(bus_read(&memory, 0xFF00), Some(0xE7));
+    }
+
+    #[test]
+    fn test_polled() {
+        let mut memory = Memory::new();
+        memory.cgb_mode = true;
+        bus_write(&mut memory, 0xFF00, 0x10);
+        assert_eq!(bus_peek(&memory, 0xFF00), Some(0xDF));
+        // A DMA copying from P1 isn't the game reading it either
+        for (address, value) in [
+            (0xFF51, 0xFF),
+            (0xFF52, 0x00),
+            (0xFF53, 0x00),
+            (0xFF54, 0x00),
+        ] {
+            bus_write(&mut memory, address, value);
+        }
+        bus_write(&mut memory, 0xFF55, 0x00);
+        assert_eq!(memory.vram[0][0], 0xDF);
+        assert!(!memory.joypad.take_polled());
+        bus_read(&memory, 0xFF00);
+        assert!(memory.joypad.take_polled());
+        assert!(!memory.joypad.take_polled());
+    }
+
+    #[test]
+    fn test_interrupt() {
+        let mut memory = Memory::new();
+        bus_write(&mut memory, 0xFF00, 0x10);
+        joypad_interrupt(&mut memory);
+        press(&mut memory, Buttons::B);
+        assert!(joypad_interrupt(&mut memory));
+        // Holding or letting go doesn't
+        press(&mut memory, Buttons::B);
+        assert!(!joypad_interrupt(&mut memory));
+        press(&mut memory, Buttons::NONE);
+        assert!(!joypad_interrupt(&mut memory));
+        // A group that isn't selected doesn't, until it gets selected
+        press(&mut memory, Buttons::LEFT);
+        assert!(!joypad_interrupt(&mut memory));
+        bus_write(&mut memory, 0xFF00, 0x20);
+        assert!(joypad_interrupt(&mut memory));
+    }
+
+    #[test]
+    fn test_stop() {
+        // Select the buttons, STOP, then count up in B
+        let mut rom = vec![0u8; 0x8000];
+        rom[0x100..0x109].copy_from_slice(&[0x3E, 0x10, 0xE0, 0x00, 0x10, 0x00, 0x04, 0x18, 0xFD]);
+        rom[0x134..0x138].copy_from_slice(b"STOP");
+        let mut gameboy = GameBoy::from_catridge(Catridge::from_bytes(rom).unwrap());
+        for _ in 0..3 {
+            gameboy.step();
+        }
+        assert!(gameboy.cpu.stopped);
+        assert_eq!(gameboy.cpu.registers.pc, 0x106);
+        assert_eq!(bus_read(&gameboy.memory, 0xFF04), Some(0));
+        let b = gameboy.cpu.registers.b;
+        for _ in 0..100 {
+            gameboy.step();
+        }
+        assert!(gameboy.cpu.stopped);
+        assert_eq!(gameboy.cpu.registers.b, b);
+
+        // Directions aren't selected
+        gameboy.set_buttons(Buttons::UP);
+        gameboy.step();
+        assert!(gameboy.cpu.stopped);
+        gameboy.set_buttons(Buttons::START);
+        gameboy.step();
+        assert!(!gameboy.cpu.stopped);
+        assert_eq!(gameboy.cpu.registers.b, b.wrapping_add(1));
+    }
+}
@@ -0,0 +1,181 @@
+#[cfg(test)]
+mod mbc_test {
+
+    use blazeboy::{bus_read, bus_tick, bus_write, Catridge, Memory, RtcClock};
+
+    fn build_rom(catridge_type: u8, ram_size: u8, banks: usize) -> Vec<u8> {
+        let mut data = vec![0u8; banks * 0x4000];
+        for (bank, chunk) in data.chunks_mut(0x2000).enumerate() {
+            chunk.fill(bank as u8);
+        }
+        data[0x134..0x138].copy_from_slice(b"TEST");
+        data[0x138..=0x14C].fill(0);
+        data[0x147] = catridge_type;
+        data[0x148] = 0x1;
+        data[0x149] = ram_size;
+        data[0x14B] = 0x01;
+        data
+    }
+
+    fn load(catridge_type: u8, ram_size: u8, banks: usize) -> Memory {
+        let catridge = Catridge::from_bytes(build_rom(catridge_type, ram_size, banks)).unwrap();
+        let mut memory = Memory::new();
+        memory.load_catridge(catridge);
+        memory
+    }
+
+    fn tama5_write(memory: &mut Memory, register: u8, value: u8) {
+        bus_write(memory, 0xA001, register);
+        bus_write(memory, 0xA000, value);
+    }
+
+    fn tama5_read(memory: &mut Memory, register: u8) -> u8 {
+        bus_write(memory, 0xA001, register);
+        bus_read(memory, 0xA000).unwrap()
+    }
+
+    #[test]
+    fn test_tama5_handshake_and_banking() {
+        let mut memory = load(0xFD, 0x0, 4);
+        assert_eq!(tama5_read(&mut memory, 0xA), 0xF1);
+
+        tama5_write(&mut memory, 0x0, 0x3);
+        tama5_write(&mut memory, 0x1, 0x0);
+        assert_eq!(bus_read(&memory, 0x4000).unwrap(), 6);
+        assert_eq!(bus_read(&memory, 0x0000).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_tama5_ram() {
+        let mut memory = load(0xFD, 0x0, 2);
+        // Write 0x5A to address 0x13
+        tama5_write(&mut memory, 0x4, 0xA);
+        tama5_write(&mut memory, 0x5, 0x5);
+        tama5_write(&mut memory, 0x6, 0x1);
+        tama5_write(&mut memory, 0x7, 0x3);
+
+        // Read it back
+        tama5_write(&mut memory, 0x6, 0x3);
+        tama5_write(&mut memory, 0x7, 0x3);
+        assert_eq!(tama5_read(&mut memory, 0xC), 0xFA);
+        assert_eq!(tama5_read(&mut memory, 0xD), 0xF5);
+    }
+
+    #[test]
+    fn test_tama5_save_roundtrip() {
+        let mut memory = load(0xFD, 0x0, 2);
+        tama5_write(&mut memory, 0x4, 0x7);
+        tama5_write(&mut memory, 0x5, 0x0);
+        tama5_write(&mut memory, 0x6, 0x0);
+        tama5_write(&mut memory, 0x7, 0x2);
+
+        let path = std::env::temp_dir().join("blazeboy_tama5.sav");
+        let path = path.to_str().unwrap();
+        memory.save_ram(path).unwrap();
+
+        let mut restored = load(0xFD, 0x0, 2);
+        restored.load_ram(path).unwrap();
+        tama5_write(&mut restored, 0x6, 0x2);
+        tama5_write(&mut restored, 0x7, 0x2);
+        assert_eq!(tama5_read(&mut restored, 0xC), 0xF7);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_tama5_emulated_clock() {
+        let mut memory = load(0xFD, 0x0, 2);
+        memory.set_rtc_clock(RtcClock::Emulated);
+        let seconds = |memory: &mut Memory| {
+            // Latch, then read the seconds digit
+            tama5_write(memory, 0x6, 0x8);
+            tama5_write(memory, 0x7, 0x0);
+            tama5_write(memory, 0x6, 0x6);
+            tama5_write(memory, 0x7, 0x0);
+            tama5_read(memory, 0xC) & 0xF
+        };
+        bus_tick(&mut memory, 4194304 * 3 / 2);
+        assert_eq!(seconds(&mut memory), 1);
+        bus_tick(&mut memory, 4194304 / 2);
+        assert_eq!(seconds(&mut memory), 2);
+    }
+
+    #[test]
+    fn test_mbc6_windows() {
+        let mut memory = load(0x20, 0x3, 8);
+        bus_write(&mut memory, 0x2000, 5);
+        bus_write(&mut memory, 0x3000, 9);
+        assert_eq!(bus_read(&memory, 0x4000).unwrap(), 5);
+        assert_eq!(bus_read(&memory, 0x6000).unwrap(), 9);
+
+        bus_write(&mut memory, 0x0000, 0xA);
+        bus_write(&mut memory, 0x0400, 1);
+        bus_write(&mut memory, 0x0800, 2);
+        bus_write(&mut memory, 0xA010, 0x11);
+        bus_write(&mut memory, 0xB010, 0x22);
+        bus_write(&mut memory, 0x0800, 1);
+        assert_eq!(bus_read(&memory, 0xB010).unwrap(), 0x11);
+    }
+
+    fn flash_command(memory: &mut Memory, command: u8) {
+        bus_write(memory, 0x2000, 2);
+        bus_write(memory, 0x5555, 0xAA);
+        bus_write(memory, 0x2000, 1);
+        bus_write(memory, 0x4AAA, 0x55);
+        bus_write(memory, 0x2000, 2);
+        bus_write(memory, 0x5555, command);
+    }
+
+    #[test]
+    fn test_mbc6_flash() {
+        let mut memory = load(0x20, 0x3, 8);
+        bus_write(&mut memory, 0x0C00, 1);
+        bus_write(&mut memory, 0x1000, 1);
+        bus_write(&mut memory, 0x2800, 0x08);
+        bus_write(&mut memory, 0x3800, 0x08);
+
+        flash_command(&mut memory, 0x90);
+        assert_eq!(bus_read(&memory, 0x4000).unwrap(), 0xC2);
+        bus_write(&mut memory, 0x4000, 0xF0);
+
+        flash_command(&mut memory, 0xA0);
+        bus_write(&mut memory, 0x3000, 0x10);
+        bus_write(&mut memory, 0x6123, 0x3C);
+        assert_eq!(bus_read(&memory, 0x6123).unwrap(), 0x3C);
+        // 0xF0 is data while programming, not the reset command
+        flash_command(&mut memory, 0xA0);
+        bus_write(&mut memory, 0x3000, 0x10);
+        bus_write(&mut memory, 0x6124, 0xF0);
+        assert_eq!(bus_read(&memory, 0x6124).unwrap(), 0xF0);
+
+        flash_command(&mut memory, 0x80);
+        bus_write(&mut memory, 0x5555, 0xAA);
+        bus_write(&mut memory, 0x2000, 1);
+        bus_write(&mut memory, 0x4AAA, 0x55);
+        bus_write(&mut memory, 0x6000, 0x30);
+        assert_eq!(bus_read(&memory, 0x6123).unwrap(), 0xFF);
+    }
+
+    #[test]
+    fn test_mbc6_flash_needs_write_enable() {
+        let mut memory = load(0x20, 0x3, 8);
+        bus_write(&mut memory, 0x0C00, 1);
+        bus_write(&mut memory, 0x2800, 0x08);
+        bus_write(&mut memory, 0x3800, 0x08);
+
+        flash_command(&mut memory, 0xA0);
+        bus_write(&mut memory, 0x6000, 0x00);
+        assert_eq!(bus_read(&memory, 0x6000).unwrap(), 0xFF);
+    }
+
+    #[test]
+    fn test_rom_only() {
+        let mut memory = load(0x08, 0x2, 2);
+        assert_eq!(bus_read(&memory, 0x0000).unwrap(), 0);
+        assert_eq!(bus_read(&memory, 0x3000).unwrap(), 1);
+        assert_eq!(bus_read(&memory, 0x7FFF).unwrap(), 3);
+        bus_write(&mut memory, 0x2000, 0x02);
+        assert_eq!(bus_read(&memory, 0x7FFF).unwrap(), 3);
+        bus_write(&mut memory, 0xA123, 0x42);
+        assert_eq!(bus_read(&memory, 0xA123).unwrap(), 0x42);
+    }
+}
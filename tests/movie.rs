@@ -0,0 +1,183 @@
+#[cfg(test)]
+mod movie_test {
+
+    use blazeboy::{
+        Buttons, Catridge, GameBoy, Mbc, Model, Movie, MovieError, MoviePlayer, MovieRecorder,
+        Tama5,
+    };
+
+    // Selects the buttons, then once per frame adds P1 to B and puts B into BGP, so
+    // every frame's buttons change the colour of the screen from then on
+    const POLL: [u8; 27] = [
+        0x3E, 0x10, 0xE0, 0x00, // LD A,0x10 / LDH (P1),A
+        0xF0, 0x44, 0xFE, 0x90, 0xC2, 0x54, 0x01, // wait for LY 144
+        0xF0, 0x00, 0x80, 0x47, 0xE0, 0x47, // LDH A,(P1) / ADD A,B / LD B,A / LDH (BGP),A
+        0xF0, 0x44, 0xFE, 0x90, 0xCA, 0x61, 0x01, // wait for LY to move on
+        0xC3, 0x54, 0x01,
+    ];
+
+    fn catridge(code: &[u8]) -> Catridge {
+        let mut rom = vec![0u8; 0x8000];
+        rom[0x100..0x103].copy_from_slice(&[0xC3, 0x50, 0x01]);
+        rom[0x134..0x139].copy_from_slice(b"MOVIE");
+        rom[0x150..0x150 + code.len()].copy_from_slice(code);
+        Catridge::from_bytes(rom).unwrap()
+    }
+
+    fn buttons(frame: usize) -> Buttons {
+        if frame % 7 < 3 {
+            Buttons::A
+        } else if frame.is_multiple_of(11) {
+            Buttons::START | Buttons::B
+        } else {
+            Buttons::NONE
+        }
+    }
+
+    fn record(frames: usize) -> (Movie, u64) {
+        let movie = Movie::new(&catridge(&POLL), Model::Dmg, Vec::new());
+        let mut gameboy = movie.boot(catridge(&POLL)).unwrap();
+        let mut recorder = MovieRecorder::new(movie);
+        for frame in 0..frames {
+            recorder.run_frame(&mut gameboy, buttons(frame));
+        }
+        (recorder.movie, gameboy.frame_hash())
+    }
+
+    #[test]
+    fn test_file() {
+        let (mut movie, _) = record(130);
+        movie.save_data = vec![1, 2, 3];
+        let loaded = Movie::from_bytes(&movie.to_bytes()).unwrap();
+        assert_eq!(loaded.model, Model::Dmg);
+        assert_eq!(loaded.rom_hash, movie.rom_hash);
+        assert_eq!(loaded.save_data, vec![1, 2, 3]);
+        assert!(loaded.state.is_empty());
+        assert_eq!(loaded.hash_interval, 60);
+        assert_eq!(loaded.frames, movie.frames);
+        assert_eq!(loaded.hashes.len(), 2);
+        assert_eq!(loaded.hashes, movie.hashes);
+
+        let mut data = movie.to_bytes();
+        data.pop();
+        assert!(Movie::from_bytes(&data).is_err());
+        assert!(Movie::from_bytes(b"BBMV").is_err());
+
+        // Version 1 files have no state
+        let mut data = movie.to_bytes();
+        data[4] = 1;
+        assert_eq!(Movie::from_bytes(&data).unwrap().frames, movie.frames);
+    }
+
+    // Starts halfway through another run, with the SGB on
+    fn record_from_state(frames: usize) -> (Movie, Vec<u8>) {
+        let mut gameboy = GameBoy::from_catridge_sgb(catridge(&POLL));
+        for frame in 0..50 {
+            gameboy.set_buttons(buttons(frame + 3));
+            gameboy.run_frame();
+        }
+        let movie = Movie::from_state(&gameboy);
+        let mut gameboy = movie.boot(catridge(&POLL)).unwrap();
+        let mut recorder = MovieRecorder::new(movie);
+        for frame in 0..frames {
+            recorder.run_frame(&mut gameboy, buttons(frame));
+        }
+        (recorder.movie, gameboy.state_data())
+    }
+
+    #[test]
+    fn test_state() {
+        let (movie, state) = record_from_state(150);
+        assert_eq!(movie.model, Model::Sgb);
+        assert!(!movie.state.is_empty());
+        let movie = Movie::from_bytes(&movie.to_bytes()).unwrap();
+        let mut gameboy = movie.boot(catridge(&POLL)).unwrap();
+        let mut player = MoviePlayer::new(movie);
+        assert_eq!(player.run(&mut gameboy), Ok(()));
+        assert_eq!(gameboy.state_data(), state);
+    }
+
+    #[test]
+    fn test_bad_state() {
+        let (movie, _) = record_from_state(0);
+        let mut other = Movie::new(&catridge(&POLL), Model::Dmg, Vec::new());
+        other.state = movie.state.clone();
+        assert_eq!(other.boot(catridge(&POLL)).err(), Some(MovieError::Model));
+        other.state.pop();
+        assert_eq!(other.boot(catridge(&POLL)).err(), Some(MovieError::State));
+    }
+
+    #[test]
+    fn test_playback() {
+        let (movie, hash) = record(200);
+        let movie = Movie::from_bytes(&movie.to_bytes()).unwrap();
+        let mut gameboy = movie.boot(catridge(&POLL)).unwrap();
+        let mut player = MoviePlayer::new(movie);
+        assert_eq!(player.run(&mut gameboy), Ok(()));
+        assert!(player.finished());
+        assert_eq!(player.frame(), 200);
+        assert_eq!(gameboy.frame_hash(), hash);
+        // The game reads P1 every frame, only the first one can miss it
+        assert!(player.lag_frames <= 1);
+    }
+
+    #[test]
+    fn test_desync() {
+        let (mut movie, _) = record(200);
+        movie.frames[70] = Buttons::SELECT;
+        let mut gameboy = movie.boot(catridge(&POLL)).unwrap();
+        let mut player = MoviePlayer::new(movie);
+        assert_eq!(player.run(&mut gameboy), Err(MovieError::Desync));
+        assert_eq!(player.frame(), 120);
+    }
+
+    #[test]
+    fn test_lag_frames() {
+        // JP to itself, P1 is never read
+        let code = [0xC3, 0x50, 0x01];
+        let movie = Movie::new(&catridge(&code), Model::Dmg, Vec::new());
+        let mut gameboy = movie.boot(catridge(&code)).unwrap();
+        let mut recorder = MovieRecorder::new(movie);
+        for _ in 0..10 {
+            assert!(recorder.run_frame(&mut gameboy, Buttons::A));
+        }
+        assert_eq!(recorder.lag_frames, 10);
+    }
+
+    #[test]
+    fn test_boot() {
+        let (mut movie, _) = record(0);
+        assert_eq!(movie.boot(catridge(&[0x00])).err(), Some(MovieError::Rom));
+        movie.model = Model::Cgb;
+        assert_eq!(movie.boot(catridge(&POLL)).err(), Some(MovieError::Model));
+        movie.model = Model::Sgb;
+        let gameboy = movie.boot(catridge(&POLL)).unwrap();
+        assert_eq!(Model::of(&gameboy), Model::Sgb);
+    }
+
+    #[test]
+    fn test_rtc() {
+        // A TAMA5 save from long ago, the host clock would move it on by years
+        let mut rom = vec![0u8; 0x8000];
+        rom[0x134..0x139].copy_from_slice(b"TAMA5");
+        rom[0x147] = 0xFD;
+        let catridge = || Catridge::from_bytes(rom.clone()).unwrap();
+        let mut save_data = vec![0u8; Tama5::SAVE_SIZE];
+        save_data[Tama5::RAM_SIZE..Tama5::RAM_SIZE + 7].copy_from_slice(&[5, 4, 3, 2, 1, 6, 90]);
+        let movie = Movie::new(&catridge(), Model::Dmg, save_data);
+        let gameboy = movie.boot(catridge()).unwrap();
+        let Mbc::Tama5(tama5) = gameboy.memory.mbc() else {
+            panic!("not a TAMA5");
+        };
+        let rtc = &tama5.rtc;
+        let time = [
+            rtc.seconds,
+            rtc.minutes,
+            rtc.hours,
+            rtc.day,
+            rtc.month,
+            rtc.year,
+        ];
+        assert_eq!(time, [5, 4, 3, 1, 6, 90]);
+    }
+}
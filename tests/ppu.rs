@@ -0,0 +1,282 @@
+#[cfg(test)]
+mod ppu_test {
+
+    use blazeboy::{bus_read, bus_tick, bus_write, Memory, PpuMode, Renderer, SCREEN_WIDTH};
+
+    const LINE: u32 = 456;
+
+    fn read(memory: &Memory, address: u16) -> u8 {
+        bus_read(memory, address).unwrap()
+    }
+
+    // Tile 1 is solid colour 3, tile 2 is solid colour 1
+    fn setup_tiles(memory: &mut Memory) {
+        for row in 0..8 {
+            memory.vram[0][0x10 + row * 2] = 0xFF;
+            memory.vram[0][0x10 + row * 2 + 1] = 0xFF;
+            memory.vram[0][0x20 + row * 2] = 0xFF;
+        }
+        bus_write(memory, 0xFF47, 0xE4);
+        bus_write(memory, 0xFF48, 0xE4);
+        bus_write(memory, 0xFF49, 0x1B);
+    }
+
+    fn pixel(memory: &Memory, x: usize, y: usize) -> u8 {
+        memory.ppu.framebuffer[y * SCREEN_WIDTH + x]
+    }
+
+    fn run_frame(memory: &mut Memory) {
+        bus_tick(memory, LINE * 154);
+    }
+
+    #[test]
+    fn test_mode_timing() {
+        let mut memory = Memory::new();
+        bus_write(&mut memory, 0xFF40, 0x80);
+        bus_tick(&mut memory, 4);
+        assert_eq!(read(&memory, 0xFF41) & 0x3, PpuMode::OamScan as u8);
+        bus_tick(&mut memory, 80);
+        assert_eq!(read(&memory, 0xFF41) & 0x3, PpuMode::Drawing as u8);
+        assert_eq!(read(&memory, 0x8000), 0xFF);
+        bus_tick(&mut memory, 172);
+        assert_eq!(read(&memory, 0xFF41) & 0x3, PpuMode::HBlank as u8);
+        bus_tick(&mut memory, 204);
+        assert_eq!(read(&memory, 0xFF44), 1);
+        assert_eq!(read(&memory, 0xFF41) & 0x3, PpuMode::OamScan as u8);
+    }
+
+    #[test]
+    fn test_vblank() {
+        let mut memory = Memory::new();
+        bus_write(&mut memory, 0xFF40, 0x80);
+        bus_tick(&mut memory, 4 + LINE * 144 - 4);
+        assert_eq!(memory.io.interrupt_flags() & 0x01, 0);
+        bus_tick(&mut memory, 4);
+        assert_eq!(read(&memory, 0xFF44), 144);
+        assert_eq!(read(&memory, 0xFF41) & 0x3, PpuMode::VBlank as u8);
+        assert_eq!(memory.io.interrupt_flags() & 0x01, 0x01);
+        assert!(memory.ppu.take_frame());
+        assert!(!memory.ppu.take_frame());
+
+        bus_tick(&mut memory, LINE * 10);
+        assert_eq!(read(&memory, 0xFF44), 0);
+    }
+
+    #[test]
+    fn test_lyc_interrupt() {
+        let mut memory = Memory::new();
+        bus_write(&mut memory, 0xFF45, 10);
+        bus_write(&mut memory, 0xFF41, 0x40);
+        bus_write(&mut memory, 0xFF40, 0x80);
+        bus_tick(&mut memory, 4 + LINE * 10 - 4);
+        assert_eq!(memory.io.interrupt_flags() & 0x02, 0);
+        bus_tick(&mut memory, 4);
+        assert_eq!(read(&memory, 0xFF41) & 0x04, 0x04);
+        assert_eq!(memory.io.interrupt_flags() & 0x02, 0x02);
+    }
+
+    #[test]
+    fn test_lcd_off() {
+        let mut memory = Memory::new();
+        bus_write(&mut memory, 0xFF40, 0x80);
+        bus_tick(&mut memory, LINE * 3);
+        bus_write(&mut memory, 0xFF40, 0x00);
+        bus_tick(&mut memory, 4);
+        assert_eq!(read(&memory, 0xFF44), 0);
+        assert_eq!(read(&memory, 0xFF41) & 0x3, 0);
+    }
+
+    #[test]
+    fn test_background_scroll() {
+        let mut memory = Memory::new();
+        setup_tiles(&mut memory);
+        // Map entry (1, 0) is tile 1, everything else tile 0
+        memory.vram[0][0x1801] = 1;
+        bus_write(&mut memory, 0xFF43, 4);
+        bus_write(&mut memory, 0xFF40, 0x91);
+        run_frame(&mut memory);
+        assert_eq!(pixel(&memory, 3, 0), 0);
+        assert_eq!(pixel(&memory, 4, 0), 3);
+        assert_eq!(pixel(&memory, 11, 7), 3);
+        assert_eq!(pixel(&memory, 12, 0), 0);
+        assert_eq!(pixel(&memory, 4, 8), 0);
+    }
+
+    #[test]
+    fn test_window() {
+        let mut memory = Memory::new();
+        setup_tiles(&mut memory);
+        for offset in 0x1C00..0x2000 {
+            memory.vram[0][offset] = 2;
+        }
+        bus_write(&mut memory, 0xFF4A, 100);
+        bus_write(&mut memory, 0xFF4B, 87);
+        bus_write(&mut memory, 0xFF40, 0xF1);
+        run_frame(&mut memory);
+        assert_eq!(pixel(&memory, 80, 99), 0);
+        assert_eq!(pixel(&memory, 79, 100), 0);
+        assert_eq!(pixel(&memory, 80, 100), 1);
+        assert_eq!(memory.ppu.window_line, 44);
+    }
+
+    #[test]
+    fn test_sprite_priority() {
+        let mut memory = Memory::new();
+        setup_tiles(&mut memory);
+        // Sprite 0 at x=20 with OBP1, sprite 1 at x=16 overlapping it
+        memory.data[0xFE00..0xFE08].copy_from_slice(&[16, 28, 1, 0x10, 16, 24, 2, 0x00]);
+        // Sprite 2 is behind a non-zero background
+        memory.data[0xFE08..0xFE0C].copy_from_slice(&[40, 8, 1, 0x80]);
+        memory.vram[0][0x1800 + 3 * 32] = 2;
+        bus_write(&mut memory, 0xFF40, 0x93);
+        run_frame(&mut memory);
+        assert_eq!(pixel(&memory, 16, 0), 1);
+        assert_eq!(pixel(&memory, 23, 0), 1);
+        // OBP1 maps colour 3 to shade 0
+        assert_eq!(pixel(&memory, 24, 0), 0);
+        assert_eq!(pixel(&memory, 0, 24), 1);
+        assert_eq!(pixel(&memory, 0, 25), 1);
+    }
+
+    #[test]
+    fn test_sprite_limit() {
+        let mut memory = Memory::new();
+        setup_tiles(&mut memory);
+        for i in 0..12 {
+            let entry = 0xFE00 + i * 4;
+            memory.data[entry..entry + 4].copy_from_slice(&[16, 8 + i as u8 * 8, 1, 0]);
+        }
+        bus_write(&mut memory, 0xFF40, 0x83);
+        run_frame(&mut memory);
+        assert_eq!(pixel(&memory, 72, 0), 3);
+        assert_eq!(pixel(&memory, 80, 0), 0);
+    }
+
+    #[test]
+    fn test_sprite_height_change() {
+        for renderer in [Renderer::Scanline, Renderer::Fifo] {
+            let mut memory = Memory::new();
+            memory.ppu.renderer = renderer;
+            setup_tiles(&mut memory);
+            // 8x16 sprites on lines 0-15, the second one flipped
+            memory.data[0xFE00..0xFE08].copy_from_slice(&[16, 8, 1, 0, 16, 40, 1, 0x40]);
+            bus_write(&mut memory, 0xFF40, 0x97);
+            // Down to 8x8 in the middle of line 10, after the sprites were picked
+            bus_tick(&mut memory, 4 + LINE * 10 + 40);
+            bus_write(&mut memory, 0xFF40, 0x93);
+            bus_tick(&mut memory, LINE);
+            // Row 10 is still in the bottom tile, and row 5 of the empty top one when flipped
+            assert_eq!(pixel(&memory, 0, 10), 3);
+            assert_eq!(pixel(&memory, 32, 10), 0);
+            assert_eq!(pixel(&memory, 0, 11), 0);
+        }
+    }
+
+    fn fifo_memory() -> Memory {
+        let mut memory = Memory::new();
+        memory.ppu.renderer = Renderer::Fifo;
+        setup_tiles(&mut memory);
+        memory
+    }
+
+    // Length of mode 3 on line 1, rounded up to whole M-cycles
+    fn mode3_length(memory: &mut Memory) -> u32 {
+        bus_tick(memory, LINE);
+        while read(memory, 0xFF41) & 0x3 != PpuMode::Drawing as u8 {
+            bus_tick(memory, 4);
+        }
+        let mut length = 0;
+        while read(memory, 0xFF41) & 0x3 == PpuMode::Drawing as u8 {
+            bus_tick(memory, 4);
+            length += 4;
+        }
+        length
+    }
+
+    #[test]
+    fn test_fifo_mode3_length() {
+        let mut memory = fifo_memory();
+        bus_write(&mut memory, 0xFF40, 0x91);
+        assert_eq!(mode3_length(&mut memory), 172);
+
+        let mut memory = fifo_memory();
+        bus_write(&mut memory, 0xFF43, 4);
+        bus_write(&mut memory, 0xFF40, 0x91);
+        assert_eq!(mode3_length(&mut memory), 176);
+
+        let mut memory = fifo_memory();
+        bus_write(&mut memory, 0xFF4B, 87);
+        bus_write(&mut memory, 0xFF40, 0xB1);
+        assert!(mode3_length(&mut memory) > 172);
+
+        let mut memory = fifo_memory();
+        memory.data[0xFE00..0xFE04].copy_from_slice(&[16, 40, 1, 0]);
+        bus_write(&mut memory, 0xFF40, 0x93);
+        assert!(mode3_length(&mut memory) > 172);
+    }
+
+    #[test]
+    fn test_fifo_sprite_penalty() {
+        let lengths = [
+            // First pixel of a tile, the whole tile fetch is waited for
+            (vec![8], 0, 184),
+            (vec![0], 0, 184),
+            (vec![13], 3, 188),
+            // Nothing to wait for over the last pixels of a tile
+            (vec![14], 0, 180),
+            (vec![11], 3, 184),
+            // Only the first sprite over a tile waits for it
+            (vec![8, 12], 0, 192),
+            (vec![8, 16], 0, 196),
+        ];
+        for (xs, scx, length) in lengths {
+            let mut memory = fifo_memory();
+            for (i, x) in xs.into_iter().enumerate() {
+                memory.data[0xFE00 + i * 4..0xFE04 + i * 4].copy_from_slice(&[16, x, 1, 0]);
+            }
+            bus_write(&mut memory, 0xFF43, scx);
+            bus_write(&mut memory, 0xFF40, 0x93);
+            assert_eq!(mode3_length(&mut memory), length);
+        }
+    }
+
+    #[test]
+    fn test_fifo_mid_scanline_palette() {
+        let mut memory = fifo_memory();
+        for offset in 0x1800..0x1C00 {
+            memory.vram[0][offset] = 1;
+        }
+        bus_write(&mut memory, 0xFF47, 0xFF);
+        bus_write(&mut memory, 0xFF40, 0x91);
+        bus_tick(&mut memory, 4 + 80 + 12 + 64);
+        bus_write(&mut memory, 0xFF47, 0x3F);
+        bus_tick(&mut memory, LINE * 2);
+        assert!((0..60).all(|x| pixel(&memory, x, 0) == 3));
+        assert!((70..SCREEN_WIDTH).all(|x| pixel(&memory, x, 0) == 0));
+        assert!((0..SCREEN_WIDTH).all(|x| pixel(&memory, x, 1) == 0));
+    }
+
+    #[test]
+    fn test_fifo_matches_scanline() {
+        let mut frames = Vec::new();
+        for renderer in [Renderer::Scanline, Renderer::Fifo] {
+            let mut memory = Memory::new();
+            memory.ppu.renderer = renderer;
+            setup_tiles(&mut memory);
+            for offset in 0..0x400 {
+                memory.vram[0][0x1800 + offset] = (offset % 3) as u8;
+                memory.vram[0][0x1C00 + offset] = 2 - (offset % 3) as u8;
+            }
+            memory.data[0xFE00..0xFE0C]
+                .copy_from_slice(&[20, 4, 1, 0x20, 30, 50, 2, 0x90, 30, 52, 1, 0]);
+            bus_write(&mut memory, 0xFF42, 3);
+            bus_write(&mut memory, 0xFF43, 5);
+            bus_write(&mut memory, 0xFF4A, 60);
+            bus_write(&mut memory, 0xFF4B, 40);
+            bus_write(&mut memory, 0xFF40, 0xF3);
+            run_frame(&mut memory);
+            frames.push(memory.ppu.framebuffer);
+        }
+        assert!(frames[0] == frames[1]);
+    }
+}
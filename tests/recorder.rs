@@ -0,0 +1,176 @@
+mod common;
+
+#[cfg(test)]
+mod recorder_test {
+
+    use std::fs::File;
+    use std::io::Cursor;
+
+    use blazeboy::{GameBoy, RecordFormat, Recorder, VideoOutput, WavWriter};
+
+    use crate::common::{loop_rom, temp_file};
+
+    fn solid_frame(rgb: [u8; 3]) -> Vec<u8> {
+        [rgb[0], rgb[1], rgb[2], 0xFF].repeat(VideoOutput::FRAME_SIZE / 4)
+    }
+
+    #[test]
+    fn test_format_from_filename() {
+        assert_eq!(
+            RecordFormat::from_filename("clip.y4m"),
+            Some(RecordFormat::Y4m)
+        );
+        assert_eq!(
+            RecordFormat::from_filename("clip.APNG"),
+            Some(RecordFormat::Apng)
+        );
+        assert_eq!(
+            RecordFormat::from_filename("dir/clip.gif"),
+            Some(RecordFormat::Gif)
+        );
+        assert_eq!(RecordFormat::from_filename("clip.mp4"), None);
+        assert_eq!(
+            RecordFormat::from_filename("clip"),
+            Some(RecordFormat::Apng)
+        );
+    }
+
+    #[test]
+    fn test_wav_writer() {
+        let mut wav = WavWriter::new(Cursor::new(Vec::new()), 2, 48000).unwrap();
+        wav.write_samples(&[1, -1, 0x1234, 0]).unwrap();
+        let data = wav.finish().unwrap().into_inner();
+        assert_eq!(data.len(), 44 + 8);
+        assert_eq!(&data[0..4], b"RIFF");
+        assert_eq!(u32::from_le_bytes(data[4..8].try_into().unwrap()), 44);
+        assert_eq!(&data[8..16], b"WAVEfmt ");
+        assert_eq!(u16::from_le_bytes([data[22], data[23]]), 2);
+        assert_eq!(u32::from_le_bytes(data[24..28].try_into().unwrap()), 48000);
+        assert_eq!(u32::from_le_bytes(data[28..32].try_into().unwrap()), 192000);
+        assert_eq!(u32::from_le_bytes(data[40..44].try_into().unwrap()), 8);
+        assert_eq!(data[44..48], [0x01, 0x00, 0xFF, 0xFF]);
+    }
+
+    #[test]
+    fn test_y4m_with_audio() {
+        let filename = temp_file("clip.y4m");
+        let mut recorder = Recorder::create(&filename, RecordFormat::Y4m, 160, 144).unwrap();
+        recorder
+            .write_frame(&solid_frame([0xFF, 0xFF, 0xFF]))
+            .unwrap();
+        recorder
+            .write_frame(&solid_frame([0xFF, 0x00, 0x00]))
+            .unwrap();
+        // A frame of silence, 17556 APU samples
+        recorder.write_audio(&[[0, 0]; 17556]).unwrap();
+        assert!(recorder.write_frame(&[0; 16]).is_err());
+        // A failed write is still reported at the end
+        assert!(recorder.finish().is_err());
+
+        let data = std::fs::read(&filename).unwrap();
+        let header = b"YUV4MPEG2 W160 H144 F4194304:70224 Ip A1:1 C444 XCOLORRANGE=FULL\n";
+        assert_eq!(&data[..header.len()], header);
+        let plane = 160 * 144;
+        let frame_size = 6 + plane * 3;
+        assert_eq!(data.len(), header.len() + frame_size * 2);
+        let white = &data[header.len()..];
+        assert_eq!(&white[..6], b"FRAME\n");
+        assert_eq!(white[6], 255);
+        assert_eq!(white[6 + plane], 128);
+        let red = &white[frame_size..];
+        assert_eq!(&red[..6], b"FRAME\n");
+        assert_eq!((red[6], red[6 + plane * 2]), (77, 255));
+
+        let wav_filename = filename.replace(".y4m", ".wav");
+        let wav = std::fs::read(&wav_filename).unwrap();
+        assert_eq!(
+            u32::from_le_bytes(wav[24..28].try_into().unwrap()),
+            Recorder::AUDIO_RATE
+        );
+        // About 800 samples at 48 kHz, less what's still in the resampler
+        let samples = (wav.len() - 44) / 4;
+        assert!((700..=804).contains(&samples), "{}", samples);
+        std::fs::remove_file(&filename).unwrap();
+        std::fs::remove_file(&wav_filename).unwrap();
+    }
+
+    #[test]
+    fn test_apng() {
+        let filename = temp_file("clip.png");
+        let mut recorder = Recorder::create(&filename, RecordFormat::Apng, 160, 144).unwrap();
+        for shade in [0x00, 0x55, 0xAA] {
+            recorder.write_frame(&solid_frame([shade; 3])).unwrap();
+        }
+        // Audio is dropped without an error
+        assert!(!recorder.has_audio());
+        recorder.write_audio(&[[0, 0]; 4]).unwrap();
+        recorder.finish().unwrap();
+
+        let decoder = png::Decoder::new(File::open(&filename).unwrap());
+        let mut reader = decoder.read_info().unwrap();
+        let control = reader.info().animation_control.unwrap();
+        assert_eq!((control.num_frames, control.num_plays), (3, 0));
+        let mut image = vec![0; reader.output_buffer_size()];
+        let mut shades = vec![];
+        for _ in 0..3 {
+            reader.next_frame(&mut image).unwrap();
+            let frame = reader.info().frame_control.unwrap();
+            assert_eq!((frame.delay_num, frame.delay_den), Recorder::APNG_DELAY);
+            shades.push(image[0]);
+        }
+        assert_eq!(shades, [0x00, 0x55, 0xAA]);
+        std::fs::remove_file(&filename).unwrap();
+    }
+
+    #[test]
+    fn test_gif_resampled() {
+        let filename = temp_file("clip.gif");
+        let mut recorder = Recorder::create(&filename, RecordFormat::Gif, 160, 144).unwrap();
+        // Two seconds of emulation
+        for i in 0..120 {
+            let shade = if i % 2 == 0 { 0x00 } else { 0xFF };
+            recorder.write_frame(&solid_frame([shade; 3])).unwrap();
+        }
+        recorder.finish().unwrap();
+
+        let mut options = gif::DecodeOptions::new();
+        options.set_color_output(gif::ColorOutput::RGBA);
+        let mut decoder = options.read_info(File::open(&filename).unwrap()).unwrap();
+        let (mut frames, mut duration) = (0, 0);
+        let mut first = None;
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            first.get_or_insert(frame.buffer[0]);
+            frames += 1;
+            duration += frame.delay as u32;
+        }
+        assert_eq!(first, Some(0x00));
+        // ~50 frames per second and the clip still lasts about as long
+        assert!((99..=101).contains(&frames), "{}", frames);
+        assert!((198..=202).contains(&duration), "{}", duration);
+        std::fs::remove_file(&filename).unwrap();
+    }
+
+    #[test]
+    fn test_gameboy_recording() {
+        let filename = temp_file("gameboy.y4m");
+        let mut gameboy = GameBoy::from_catridge(loop_rom());
+        assert!(gameboy.start_recording("clip.mp4").is_err());
+        gameboy.start_recording(&filename).unwrap();
+        for _ in 0..3 {
+            gameboy.run_frame();
+        }
+        assert_eq!(gameboy.recorder.as_ref().unwrap().frames, 3);
+        gameboy.stop_recording().unwrap();
+        assert!(gameboy.recorder.is_none());
+        gameboy.run_frame();
+
+        let size = std::fs::metadata(&filename).unwrap().len() as usize;
+        assert_eq!(size, 65 + 3 * (6 + 160 * 144 * 3));
+        // The sound is recorded without an audio device too, 3 frames are ~2411 samples
+        let wav_filename = filename.replace(".y4m", ".wav");
+        let samples = (std::fs::metadata(&wav_filename).unwrap().len() as usize - 44) / 4;
+        assert!((2300..=2412).contains(&samples), "{}", samples);
+        std::fs::remove_file(&filename).unwrap();
+        std::fs::remove_file(&wav_filename).unwrap();
+    }
+}
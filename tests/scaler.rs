@@ -0,0 +1,86 @@
+#[cfg(test)]
+mod scaler_test {
+
+    use blazeboy::{LcdGrid, Scaler, VideoOutput};
+
+    const WHITE: u32 = 0xFFFFFF;
+    const BLACK: u32 = 0x000000;
+
+    #[test]
+    fn test_flat_frames_stay_flat() {
+        let frame = vec![0x123456; 4 * 3];
+        for scaler in Scaler::ALL {
+            let factor = scaler.factor();
+            let output = scaler.apply(&frame, 4, 3);
+            assert_eq!(output.len(), 4 * 3 * factor * factor, "{:?}", scaler);
+            assert!(output.iter().all(|&c| c == 0x123456), "{:?}", scaler);
+        }
+        assert_eq!(Scaler::from_name("Scale3x"), Some(Scaler::Scale3x));
+        assert_eq!(Scaler::from_name("4x"), Some(Scaler::Nearest(4)));
+        assert_eq!(Scaler::from_name("bilinear"), None);
+        // Not the real hq2x, so it doesn't go by its name
+        assert_eq!(Scaler::from_name("hq2x"), None);
+    }
+
+    #[test]
+    fn test_nearest() {
+        let output = Scaler::Nearest(2).apply(&[1, 2, 3, 4], 2, 2);
+        assert_eq!(output, [1, 1, 2, 2, 1, 1, 2, 2, 3, 3, 4, 4, 3, 3, 4, 4]);
+        let rgba = Scaler::Nearest(3).apply_rgba(&[0x10, 0x20, 0x30, 0x00], 1, 1);
+        assert_eq!(rgba, [0x10, 0x20, 0x30, 0xFF].repeat(9));
+    }
+
+    #[test]
+    fn test_scale2x_diagonal() {
+        // The diagonal comes out as a thin continuous line instead of 2x2 steps
+        let output = Scaler::Scale2x.apply(&[BLACK, WHITE, WHITE, BLACK], 2, 2);
+        assert_eq!(output[0..4], [BLACK, BLACK, WHITE, WHITE]);
+        assert_eq!(output[4..8], [BLACK, WHITE, BLACK, WHITE]);
+        assert_eq!(output[8..12], [WHITE, BLACK, WHITE, BLACK]);
+        assert_eq!(output[12..16], [WHITE, WHITE, BLACK, BLACK]);
+
+        let scale4x = Scaler::Scale4x.apply(&[BLACK, WHITE, WHITE, BLACK], 2, 2);
+        let twice = Scaler::Scale2x.apply(&output, 4, 4);
+        assert_eq!(scale4x, twice);
+    }
+
+    #[test]
+    fn test_scale3x_diagonal() {
+        let output = Scaler::Scale3x.apply(&[BLACK, WHITE, WHITE, BLACK], 2, 2);
+        // Top right block of the white pixel: its bottom left corner follows the diagonal
+        assert_eq!(output[3..6], [WHITE, WHITE, WHITE]);
+        assert_eq!(output[6 + 3..6 + 6], [BLACK, WHITE, WHITE]);
+        assert_eq!(output[6 * 2 + 3..6 * 2 + 6], [BLACK, BLACK, WHITE]);
+    }
+
+    #[test]
+    fn test_smoothing_scalers() {
+        // Black below the diagonal, white above it
+        let mut frame = vec![WHITE; 16];
+        for y in 0..4 {
+            for x in 0..y {
+                frame[y * 4 + x] = BLACK;
+            }
+        }
+        let xbr = Scaler::Xbr2x.apply(&frame, 4, 4);
+        assert_eq!(xbr[3 * 8 + 2], 0x808080);
+        assert_eq!(xbr[2 * 8 + 3], WHITE);
+
+        // An isolated pixel gets its corners rounded off
+        let mut dot = vec![WHITE; 9];
+        dot[4] = BLACK;
+        let hq = Scaler::HqLike.apply(&dot, 3, 3);
+        let corner = hq[2 * 6 + 2];
+        assert!(corner != BLACK && corner != WHITE, "{:06X}", corner);
+        assert_eq!(hq[0], WHITE);
+    }
+
+    #[test]
+    fn test_video_output_scaling() {
+        let mut output = VideoOutput::new();
+        output.scaler = Some(Scaler::Scale2x);
+        assert_eq!(output.size(), (320, 288));
+        output.grid = Some(LcdGrid::new(2));
+        assert_eq!(output.size(), (640, 576));
+    }
+}
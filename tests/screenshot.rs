@@ -0,0 +1,76 @@
+mod common;
+
+#[cfg(test)]
+mod screenshot_test {
+
+    use std::fs::File;
+
+    use blazeboy::{bus_tick, bus_write, Catridge, DmgPalette, GameBoy, SCREEN_WIDTH};
+
+    use crate::common::temp_file;
+
+    // Solid colour 3 on the top left tile, white everywhere else
+    fn draw(gameboy: &mut GameBoy) {
+        let memory = &mut gameboy.memory;
+        memory.vram[0][0x10..0x20].fill(0xFF);
+        memory.vram[0][0x1800] = 1;
+        bus_write(memory, 0xFF47, 0xE4);
+        bus_write(memory, 0xFF40, 0x91);
+        bus_tick(memory, GameBoy::FRAME_CYCLES as u32);
+    }
+
+    #[test]
+    fn test_screenshot() {
+        let mut gameboy = GameBoy::new();
+        gameboy.video.dmg_palette = DmgPalette::PeaGreen;
+        draw(&mut gameboy);
+        let filename = temp_file("screenshot.png");
+        gameboy.screenshot(&filename).unwrap();
+
+        let decoder = png::Decoder::new(File::open(&filename).unwrap());
+        let mut reader = decoder.read_info().unwrap();
+        let mut image = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut image).unwrap();
+        std::fs::remove_file(&filename).unwrap();
+
+        assert_eq!((info.width, info.height), (160, 144));
+        assert_eq!(info.color_type, png::ColorType::Rgba);
+        assert_eq!(image[0..4], [0x0F, 0x38, 0x0F, 0xFF]);
+        let white = 8 * 4;
+        assert_eq!(image[white..white + 4], [0x9B, 0xBC, 0x0F, 0xFF]);
+
+        assert!(gameboy.screenshot("/nonexistent/dir/shot.png").is_err());
+    }
+
+    #[test]
+    fn test_frame_hash() {
+        let mut first = GameBoy::new();
+        let mut second = GameBoy::new();
+        // An all white frame always hashes to the same value
+        assert_eq!(first.frame_hash(), 0x0C4D7E02ABD38725);
+        draw(&mut first);
+        draw(&mut second);
+        assert_eq!(first.frame_hash(), second.frame_hash());
+
+        // The output palette doesn't change the hash, the frame contents do
+        second.video.dmg_palette = DmgPalette::Pocket;
+        assert_eq!(first.frame_hash(), second.frame_hash());
+        second.memory.ppu.color_framebuffer[SCREEN_WIDTH + 20] = 0;
+        assert_ne!(first.frame_hash(), second.frame_hash());
+    }
+
+    #[test]
+    fn test_run_frame_headless() {
+        // JP 0x0100 at the entry point
+        let mut rom = vec![0u8; 0x8000];
+        rom[0x100..0x103].copy_from_slice(&[0xC3, 0x00, 0x01]);
+        rom[0x134..0x138].copy_from_slice(b"LOOP");
+        rom[0x14B] = 0x01;
+        let mut gameboy = GameBoy::from_catridge(Catridge::from_bytes(rom).unwrap());
+        gameboy.run_frame();
+        assert_eq!(gameboy.memory.ppu.frames, 1);
+        assert_eq!(gameboy.cpu.registers.pc, 0x100);
+        gameboy.run_frame();
+        assert_eq!(gameboy.memory.ppu.frames, 2);
+    }
+}
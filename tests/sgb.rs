@@ -0,0 +1,244 @@
+#[cfg(test)]
+mod sgb_test {
+
+    use blazeboy::{
+        bus_read, bus_tick, bus_write, Buttons, Memory, SgbCommand, SgbMask, VideoOutput,
+        BORDER_HEIGHT, BORDER_WIDTH,
+    };
+
+    fn sgb_memory() -> Memory {
+        let mut memory = Memory::new();
+        memory.enable_sgb();
+        memory
+    }
+
+    // Pulses the packets out through P14/P15 the way games do
+    fn send(memory: &mut Memory, command: u8, data: &[u8]) {
+        let packets = data.len().div_ceil(15).max(1);
+        let mut bytes = vec![command << 3 | packets as u8];
+        bytes.extend_from_slice(data);
+        bytes.resize(packets * 16, 0);
+        for packet in bytes.chunks(16) {
+            bus_write(memory, 0xFF00, 0x00);
+            bus_write(memory, 0xFF00, 0x30);
+            for bit in 0..128 {
+                let one = packet[bit / 8] >> (bit % 8) & 1 != 0;
+                bus_write(memory, 0xFF00, if one { 0x10 } else { 0x20 });
+                bus_write(memory, 0xFF00, 0x30);
+            }
+            bus_write(memory, 0xFF00, 0x20);
+            bus_write(memory, 0xFF00, 0x30);
+        }
+    }
+
+    // Puts `data` on screen for a *_TRN command: tiles 0-255 in rows of 20 from 0x8000
+    fn show_transfer(memory: &mut Memory, data: &[u8]) {
+        bus_write(memory, 0xFF40, 0x91);
+        for tile in 0..256 {
+            memory.vram[0][0x1800 + tile / 20 * 32 + tile % 20] = tile as u8;
+        }
+        memory.vram[0][..data.len()].copy_from_slice(data);
+    }
+
+    fn attribute(memory: &Memory, x: usize, y: usize) -> u8 {
+        memory.ppu.sgb.as_ref().unwrap().attributes[y * 20 + x]
+    }
+
+    #[test]
+    fn test_palette_packets() {
+        let mut memory = sgb_memory();
+        let colors: Vec<u8> = [0x1111u16, 0x0001, 0x0002, 0x0003, 0x0011, 0x0012, 0x0013]
+            .iter()
+            .flat_map(|color| color.to_le_bytes())
+            .collect();
+        send(&mut memory, 0x00, &colors);
+        send(&mut memory, 0x03, &[0; 14]);
+        let sgb = memory.ppu.sgb.as_ref().unwrap();
+        assert_eq!(sgb.last_command, Some(SgbCommand::Pal12));
+        assert_eq!(sgb.palettes[0], [0x0000, 0x0001, 0x0002, 0x0003]);
+        // PAL12 replaced palette 1 and the shared colour 0
+        assert_eq!(sgb.palettes[1], [0x0000; 4]);
+        assert_eq!(sgb.palettes[3][0], 0x0000);
+        assert_eq!(sgb.color(0, 0, 2, 0x7FFF), 0x0002);
+    }
+
+    #[test]
+    fn test_attribute_packets() {
+        let mut memory = sgb_memory();
+        // Inside only: palette 1 inside a 2..5 x 3..6 block, the outline goes with it
+        send(&mut memory, 0x04, &[1, 0x01, 0x01, 2, 3, 5, 6]);
+        assert_eq!(attribute(&memory, 2, 3), 1);
+        assert_eq!(attribute(&memory, 4, 5), 1);
+        assert_eq!(attribute(&memory, 1, 3), 0);
+        assert_eq!(attribute(&memory, 6, 6), 0);
+
+        // Column 0 to palette 2 and row 17 to palette 3
+        send(&mut memory, 0x05, &[2, 0x40, 0x80 | 0x60 | 17]);
+        assert_eq!(attribute(&memory, 0, 5), 2);
+        assert_eq!(attribute(&memory, 10, 17), 3);
+
+        // Vertical split at column 10
+        send(&mut memory, 0x06, &[0x01 | 0x02 << 2 | 0x03 << 4, 10]);
+        assert_eq!(attribute(&memory, 9, 0), 2);
+        assert_eq!(attribute(&memory, 10, 0), 3);
+        assert_eq!(attribute(&memory, 11, 17), 1);
+
+        // Four cells from (18, 0) left to right, wrapping onto the next row
+        send(&mut memory, 0x07, &[18, 0, 4, 0, 0, 0b00_01_10_11]);
+        let cells = [(18, 0), (19, 0), (0, 1), (1, 1)];
+        let palettes: Vec<u8> = cells
+            .iter()
+            .map(|&(x, y)| attribute(&memory, x, y))
+            .collect();
+        assert_eq!(palettes, [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn test_system_palettes_and_mask() {
+        let mut memory = sgb_memory();
+        let mut data = vec![0; 0x1000];
+        // System palette 3
+        for (i, color) in [0x7C00u16, 0x03E0, 0x001F, 0x1234].iter().enumerate() {
+            data[3 * 8 + i * 2..3 * 8 + i * 2 + 2].copy_from_slice(&color.to_le_bytes());
+        }
+        // Attribute file 1: every cell palette 2
+        data[90..180].fill(0xAA);
+        show_transfer(&mut memory, &data);
+        send(&mut memory, 0x0B, &[]);
+        send(&mut memory, 0x15, &[]);
+
+        send(&mut memory, 0x17, &[2]);
+        assert_eq!(memory.ppu.sgb.as_ref().unwrap().mask, SgbMask::Black);
+        // PAL_SET with palette 3 as palette 2, applying file 1 and lifting the mask
+        send(
+            &mut memory,
+            0x0A,
+            &[0, 0, 0, 0, 3, 0, 0, 0, 0x80 | 0x40 | 1],
+        );
+        let sgb = memory.ppu.sgb.as_ref().unwrap();
+        assert_eq!(sgb.mask, SgbMask::Cancel);
+        assert_eq!(sgb.palettes[2], [0x0000, 0x03E0, 0x001F, 0x1234]);
+        assert_eq!(sgb.color(100, 100, 3, 0), 0x1234);
+
+        send(&mut memory, 0x17, &[1]);
+        let sgb = memory.ppu.sgb.as_ref().unwrap();
+        assert_eq!(sgb.color(100, 100, 3, 0x4321), 0x4321);
+    }
+
+    #[test]
+    fn test_multiplayer() {
+        let mut memory = sgb_memory();
+        bus_write(&mut memory, 0xFF00, 0x30);
+        assert_eq!(bus_read(&memory, 0xFF00), Some(0xFF));
+        send(&mut memory, 0x11, &[3]);
+        assert_eq!(memory.ppu.sgb.as_ref().unwrap().players(), 4);
+
+        let mut ids = vec![];
+        for _ in 0..5 {
+            bus_write(&mut memory, 0xFF00, 0x10);
+            bus_write(&mut memory, 0xFF00, 0x30);
+            ids.push(bus_read(&memory, 0xFF00).unwrap() & 0x0F);
+        }
+        assert_eq!(ids, [0xE, 0xD, 0xC, 0xF, 0xE]);
+
+        send(&mut memory, 0x11, &[0]);
+        assert_eq!(bus_read(&memory, 0xFF00), Some(0xFF));
+    }
+
+    #[test]
+    fn test_multiplayer_buttons() {
+        let mut memory = sgb_memory();
+        send(&mut memory, 0x11, &[3]);
+        let pressed = [
+            Buttons::NONE,
+            Buttons::A | Buttons::UP,
+            Buttons::START,
+            Buttons::B | Buttons::LEFT,
+        ];
+        for (player, &buttons) in pressed.iter().enumerate() {
+            memory
+                .joypad
+                .set_player_buttons(player, buttons, &mut memory.io);
+        }
+
+        // Directions then buttons, and P15 going back high moves on to the next controller
+        let mut lines = vec![];
+        for _ in 0..5 {
+            bus_write(&mut memory, 0xFF00, 0x20);
+            let directions = bus_read(&memory, 0xFF00).unwrap() & 0xF;
+            bus_write(&mut memory, 0xFF00, 0x10);
+            let buttons = bus_read(&memory, 0xFF00).unwrap() & 0xF;
+            bus_write(&mut memory, 0xFF00, 0x30);
+            lines.push(!(directions << 4 | buttons));
+        }
+        assert_eq!(lines, [0x00, 0x41, 0x08, 0x22, 0x00]);
+
+        // Back to one player, only the first controller counts
+        send(&mut memory, 0x11, &[0]);
+        bus_write(&mut memory, 0xFF00, 0x10);
+        assert_eq!(bus_read(&memory, 0xFF00), Some(0xDF));
+    }
+
+    #[test]
+    fn test_border() {
+        let mut memory = sgb_memory();
+        // Tile 1 is colour 5 everywhere: bitplanes 0 and 2 set
+        let mut tiles = vec![0; 0x1000];
+        for row in 0..8 {
+            tiles[32 + row * 2] = 0xFF;
+            tiles[32 + 16 + row * 2] = 0xFF;
+        }
+        show_transfer(&mut memory, &tiles);
+        send(&mut memory, 0x13, &[0]);
+
+        // The top left tile uses tile 1 with border palette 5, everything else is see-through
+        let mut map = vec![0; 0x1000];
+        map[0..2].copy_from_slice(&(1u16 | 5 << 10).to_le_bytes());
+        let color = 0x0421u16.to_le_bytes();
+        map[0x800 + (16 + 5) * 2..0x800 + (16 + 5) * 2 + 2].copy_from_slice(&color);
+        show_transfer(&mut memory, &map);
+        send(&mut memory, 0x14, &[]);
+        send(&mut memory, 0x00, &[0x1F, 0x00]);
+
+        let sgb = memory.ppu.sgb.as_ref().unwrap();
+        assert_eq!(sgb.border.pixel(3, 3), Some(0x0421));
+        assert_eq!(sgb.border.pixel(8, 0), None);
+
+        let mut output = VideoOutput::new();
+        output.sgb_border = true;
+        assert_eq!(output.size(), (BORDER_WIDTH, BORDER_HEIGHT));
+        let frame = output.rgb(&memory.ppu);
+        assert_eq!(frame.len(), BORDER_WIDTH * BORDER_HEIGHT);
+        assert_eq!(frame[0], 0x080808);
+        // Backdrop from colour 0 outside the game screen, the game itself at (48, 40)
+        assert_eq!(frame[BORDER_WIDTH * 20 + 20], 0xFF0000);
+        assert_eq!(frame[BORDER_WIDTH * 40 + 48], 0xFFFFFF);
+        assert_eq!(
+            output.rgba(&memory.ppu).len(),
+            BORDER_WIDTH * BORDER_HEIGHT * 4
+        );
+    }
+
+    #[test]
+    fn test_screen_colours() {
+        let mut memory = sgb_memory();
+        let colors: Vec<u8> = [0x7FFFu16, 0x001F, 0x0000, 0x0000, 0x7C00, 0x0000, 0x0000]
+            .iter()
+            .flat_map(|color| color.to_le_bytes())
+            .collect();
+        send(&mut memory, 0x00, &colors);
+        // Right half of the screen uses palette 1
+        send(&mut memory, 0x06, &[0x01 | 0x01 << 4, 10]);
+        // Tile 0 is colour 1, BGP maps it to shade 1
+        memory.vram[0][..16].copy_from_slice(&[0xFF, 0x00].repeat(8));
+        bus_write(&mut memory, 0xFF47, 0xE4);
+        bus_write(&mut memory, 0xFF40, 0x91);
+        let frames = memory.ppu.frames;
+        while memory.ppu.frames < frames + 2 {
+            bus_tick(&mut memory, 4);
+        }
+        assert_eq!(memory.ppu.color_framebuffer[0], 0x001F);
+        assert_eq!(memory.ppu.color_framebuffer[159], 0x7C00);
+        assert_eq!(VideoOutput::new().rgb(&memory.ppu)[159], 0x0000FF);
+    }
+}
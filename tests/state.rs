@@ -0,0 +1,111 @@
+mod common;
+
+#[cfg(test)]
+mod state_test {
+
+    use blazeboy::{Buttons, Catridge, GameBoy, Model, StateError};
+
+    use crate::common::{loop_rom, temp_file};
+
+    // Starts a tone, then keeps counting in A into SCX and through 0xC000-0xC0FF
+    const COUNT: [u8; 20] = [
+        0x3E, 0xF0, 0xE0, 0x12, 0x3E, 0x87, 0xE0, 0x14, // NR12 = 0xF0, trigger channel 1
+        0x21, 0x00, 0xC0, // LD HL,0xC000
+        0x3C, 0xE0, 0x43, 0x22, 0x26, 0xC0, // INC A / LDH (SCX),A / LD (HL+),A / LD H,0xC0
+        0xC3, 0x5B, 0x01, // JP back to INC A
+    ];
+
+    fn count_rom() -> Catridge {
+        let mut rom = vec![0u8; 0x8000];
+        rom[0x100..0x103].copy_from_slice(&[0xC3, 0x50, 0x01]);
+        rom[0x134..0x139].copy_from_slice(b"COUNT");
+        rom[0x150..0x150 + COUNT.len()].copy_from_slice(&COUNT);
+        Catridge::from_bytes(rom).unwrap()
+    }
+
+    fn run_frames(gameboy: &mut GameBoy, frames: usize) {
+        for _ in 0..frames {
+            gameboy.run_frame();
+        }
+    }
+
+    #[test]
+    fn test_same_run_after_loading() {
+        let mut gameboy = GameBoy::from_catridge(count_rom());
+        run_frames(&mut gameboy, 10);
+        // Somewhere in the middle of a frame
+        for _ in 0..1234 {
+            gameboy.step();
+        }
+        let state = gameboy.state_data();
+        run_frames(&mut gameboy, 5);
+        let after = gameboy.state_data();
+        assert_ne!(after, state);
+
+        // On the same console and on a fresh one
+        gameboy.load_state_data(&state).unwrap();
+        assert_eq!(gameboy.state_data(), state);
+        run_frames(&mut gameboy, 5);
+        assert_eq!(gameboy.state_data(), after);
+
+        let mut fresh = GameBoy::from_catridge(count_rom());
+        fresh.load_state_data(&state).unwrap();
+        run_frames(&mut fresh, 5);
+        assert_eq!(fresh.state_data(), after);
+        assert_eq!(fresh.cpu.registers.pc, gameboy.cpu.registers.pc);
+        assert_eq!(
+            fresh.memory.wram[0][..0x100],
+            gameboy.memory.wram[0][..0x100]
+        );
+    }
+
+    #[test]
+    fn test_model() {
+        let mut sgb = GameBoy::from_catridge_sgb(count_rom());
+        sgb.set_buttons(Buttons::START);
+        run_frames(&mut sgb, 2);
+        let mut gameboy = GameBoy::from_catridge(count_rom());
+        gameboy.load_state_data(&sgb.state_data()).unwrap();
+        assert_eq!(Model::of(&gameboy), Model::Sgb);
+        assert_eq!(gameboy.memory.joypad.buttons(0), Buttons::START);
+    }
+
+    #[test]
+    fn test_file() {
+        let filename = temp_file("count.state");
+        let mut gameboy = GameBoy::from_catridge(count_rom());
+        run_frames(&mut gameboy, 3);
+        gameboy.save_state(&filename).unwrap();
+        let mut loaded = GameBoy::from_catridge(count_rom());
+        loaded.load_state(&filename).unwrap();
+        assert_eq!(loaded.state_data(), gameboy.state_data());
+        std::fs::remove_file(&filename).unwrap();
+        assert_eq!(loaded.load_state(&filename), Err(StateError::Load));
+    }
+
+    #[test]
+    fn test_bad_states() {
+        let mut gameboy = GameBoy::from_catridge(count_rom());
+        run_frames(&mut gameboy, 3);
+        let state = gameboy.state_data();
+        run_frames(&mut gameboy, 1);
+        let before = gameboy.state_data();
+
+        let mut other = GameBoy::from_catridge(loop_rom());
+        assert_eq!(other.load_state_data(&state), Err(StateError::Rom));
+        assert_eq!(gameboy.load_state_data(b"BBST"), Err(StateError::Header));
+        let mut data = state.clone();
+        data[4] = 0;
+        assert_eq!(gameboy.load_state_data(&data), Err(StateError::Header));
+
+        // Broken states leave the console as it was
+        assert_eq!(
+            gameboy.load_state_data(&state[..state.len() - 1]),
+            Err(StateError::Data)
+        );
+        let mut data = state.clone();
+        data.push(0);
+        assert_eq!(gameboy.load_state_data(&data), Err(StateError::Data));
+        assert_eq!(gameboy.state_data(), before);
+    }
+}
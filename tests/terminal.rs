@@ -0,0 +1,61 @@
+#[cfg(test)]
+mod terminal_test {
+
+    use blazeboy::{ansi256, TerminalColors, TerminalRenderer, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+    fn cells(output: &str) -> usize {
+        output.matches('▀').count()
+    }
+
+    #[test]
+    fn test_ansi256() {
+        assert_eq!(ansi256(0x000000), 16);
+        assert_eq!(ansi256(0xFF0000), 196);
+        assert_eq!(ansi256(0xFFFFFF), 231);
+        assert_eq!(ansi256(0x5F87AF), 67);
+        // Greys between the cube levels come from the ramp
+        assert_eq!(ansi256(0x808080), 244);
+        assert_eq!(ansi256(0x080808), 232);
+    }
+
+    #[test]
+    fn test_full_frame() {
+        let mut renderer = TerminalRenderer::new(TerminalColors::TrueColor);
+        let frame = vec![0xE0F8D0; SCREEN_WIDTH * SCREEN_HEIGHT];
+        let output = renderer.draw(&frame, SCREEN_WIDTH);
+        assert_eq!(cells(&output), SCREEN_WIDTH * SCREEN_HEIGHT / 2);
+        // One cursor move per line and the colours only set once
+        assert_eq!(output.matches('H').count(), SCREEN_HEIGHT / 2);
+        assert_eq!(output.matches("\x1b[38;2;224;248;208m").count(), 1);
+        assert_eq!(output.matches("\x1b[48;2;224;248;208m").count(), 1);
+        assert!(output.starts_with("\x1b[1;1H"));
+        assert!(output.ends_with("\x1b[0m"));
+    }
+
+    #[test]
+    fn test_diff() {
+        let mut renderer = TerminalRenderer::new(TerminalColors::Ansi256);
+        renderer.column = 2;
+        let mut frame = vec![0x000000; 8 * 4];
+        renderer.draw(&frame, 8);
+        assert_eq!(renderer.draw(&frame, 8), "");
+
+        // Bottom half of the cell at column 3, row 1
+        frame[3 * 8 + 3] = 0xFF0000;
+        let output = renderer.draw(&frame, 8);
+        assert_eq!(output, "\x1b[2;6H\x1b[38;5;16m\x1b[48;5;196m▀\x1b[0m");
+
+        renderer.invalidate();
+        assert_eq!(cells(&renderer.draw(&frame, 8)), 16);
+        // A different width redraws everything too
+        assert_eq!(cells(&renderer.draw(&frame, 4)), 16);
+    }
+
+    #[test]
+    fn test_odd_height() {
+        let mut renderer = TerminalRenderer::new(TerminalColors::TrueColor);
+        let output = renderer.draw(&[0xFFFFFF; 3], 1);
+        assert_eq!(cells(&output), 2);
+        assert!(output.contains("\x1b[48;2;0;0;0m"));
+    }
+}
@@ -0,0 +1,111 @@
+#[cfg(test)]
+mod timer_test {
+
+    use blazeboy::{bus_read, bus_tick, bus_write, Memory};
+
+    fn read(memory: &Memory, address: u16) -> u8 {
+        bus_read(memory, address).unwrap()
+    }
+
+    #[test]
+    fn test_div() {
+        let mut memory = Memory::new();
+        bus_tick(&mut memory, 255);
+        assert_eq!(read(&memory, 0xFF04), 0);
+        bus_tick(&mut memory, 4);
+        assert_eq!(read(&memory, 0xFF04), 1);
+        bus_write(&mut memory, 0xFF04, 0x77);
+        assert_eq!(read(&memory, 0xFF04), 0);
+        assert_eq!(read(&memory, 0xFF07), 0xF8);
+    }
+
+    #[test]
+    fn test_tima_frequencies() {
+        for (tac, period) in [(0x04, 1024), (0x05, 16), (0x06, 64), (0x07, 256)] {
+            let mut memory = Memory::new();
+            bus_write(&mut memory, 0xFF07, tac);
+            bus_tick(&mut memory, period * 10 - 4);
+            assert_eq!(read(&memory, 0xFF05), 9, "TAC {:#04x}", tac);
+            bus_tick(&mut memory, 4);
+            assert_eq!(read(&memory, 0xFF05), 10, "TAC {:#04x}", tac);
+        }
+    }
+
+    #[test]
+    fn test_disabled_timer() {
+        let mut memory = Memory::new();
+        bus_write(&mut memory, 0xFF07, 0x01);
+        bus_tick(&mut memory, 1024);
+        assert_eq!(read(&memory, 0xFF05), 0);
+    }
+
+    fn overflow(memory: &mut Memory) {
+        bus_write(memory, 0xFF06, 0xAB);
+        bus_write(memory, 0xFF05, 0xFF);
+        bus_write(memory, 0xFF07, 0x05);
+        bus_tick(memory, 16);
+    }
+
+    #[test]
+    fn test_reload_delay() {
+        let mut memory = Memory::new();
+        overflow(&mut memory);
+        assert_eq!(read(&memory, 0xFF05), 0x00);
+        assert_eq!(memory.io.interrupt_flags(), 0);
+
+        bus_tick(&mut memory, 4);
+        assert_eq!(read(&memory, 0xFF05), 0xAB);
+        assert_eq!(memory.io.interrupt_flags(), 0x04);
+    }
+
+    #[test]
+    fn test_tima_write_cancels_reload() {
+        let mut memory = Memory::new();
+        overflow(&mut memory);
+        bus_write(&mut memory, 0xFF05, 0x42);
+        bus_tick(&mut memory, 4);
+        assert_eq!(read(&memory, 0xFF05), 0x42);
+        assert_eq!(memory.io.interrupt_flags(), 0);
+    }
+
+    #[test]
+    fn test_writes_during_reload() {
+        let mut memory = Memory::new();
+        overflow(&mut memory);
+        bus_tick(&mut memory, 4);
+        bus_write(&mut memory, 0xFF05, 0x42);
+        assert_eq!(read(&memory, 0xFF05), 0xAB);
+        bus_write(&mut memory, 0xFF06, 0x33);
+        assert_eq!(read(&memory, 0xFF05), 0x33);
+
+        bus_tick(&mut memory, 4);
+        bus_write(&mut memory, 0xFF05, 0x42);
+        assert_eq!(read(&memory, 0xFF05), 0x42);
+    }
+
+    #[test]
+    fn test_div_write_falling_edge() {
+        let mut memory = Memory::new();
+        bus_write(&mut memory, 0xFF07, 0x05);
+        bus_tick(&mut memory, 8);
+        bus_write(&mut memory, 0xFF04, 0x00);
+        assert_eq!(read(&memory, 0xFF05), 1);
+
+        bus_tick(&mut memory, 4);
+        bus_write(&mut memory, 0xFF04, 0x00);
+        assert_eq!(read(&memory, 0xFF05), 1);
+    }
+
+    #[test]
+    fn test_tac_write_falling_edge() {
+        let mut memory = Memory::new();
+        bus_write(&mut memory, 0xFF07, 0x05);
+        bus_tick(&mut memory, 8);
+        bus_write(&mut memory, 0xFF07, 0x04);
+        assert_eq!(read(&memory, 0xFF05), 1);
+
+        bus_write(&mut memory, 0xFF07, 0x05);
+        bus_write(&mut memory, 0xFF07, 0x01);
+        assert_eq!(read(&memory, 0xFF05), 2);
+    }
+}
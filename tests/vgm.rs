@@ -0,0 +1,102 @@
+mod common;
+
+#[cfg(test)]
+mod vgm_test {
+
+    use std::io::Cursor;
+
+    use blazeboy::{bus_write, GameBoy, VgmWriter};
+
+    use crate::common::loop_rom;
+
+    fn word(bytes: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn test_header() {
+        let mut vgm = VgmWriter::new(Cursor::new(Vec::new()), 1000).unwrap();
+        vgm.write(1000, 0xFF26, 0x80).unwrap();
+        // A second later
+        vgm.write(1000 + 4194304, 0xFF30, 0x12).unwrap();
+        let bytes = vgm.finish(1000 + 4194304).unwrap().into_inner();
+        assert_eq!(&bytes[0..4], b"Vgm ");
+        assert_eq!(word(&bytes, 0x04) as usize, bytes.len() - 4);
+        assert_eq!(word(&bytes, 0x08), 0x171);
+        assert_eq!(word(&bytes, 0x18), 44100);
+        assert_eq!(word(&bytes, 0x34), 0xCC);
+        assert_eq!(word(&bytes, 0x80), 4194304);
+        assert_eq!(
+            bytes[0x100..],
+            [0xB3, 0x16, 0x80, 0x61, 0x44, 0xAC, 0xB3, 0x20, 0x12, 0x66]
+        );
+    }
+
+    #[test]
+    fn test_waits() {
+        let mut vgm = VgmWriter::new(Cursor::new(Vec::new()), 0).unwrap();
+        let cycle = |samples: u64| samples * 4194304 / 44100 + 1;
+        // Short, 60 Hz, 50 Hz and more than one long wait
+        for samples in [5, 740, 1622, 1622 + 70000] {
+            vgm.wait_until(cycle(samples)).unwrap();
+            assert_eq!(vgm.samples(), samples);
+        }
+        // Time never goes back
+        vgm.wait_until(0).unwrap();
+        assert_eq!(vgm.samples(), 71622);
+        let bytes = vgm.finish(0).unwrap().into_inner();
+        assert_eq!(
+            bytes[0x100..],
+            [0x74, 0x62, 0x63, 0x61, 0xFF, 0xFF, 0x61, 0x71, 0x11, 0x66]
+        );
+    }
+
+    fn log(filename: &str) -> Vec<u8> {
+        let mut gameboy = GameBoy::from_catridge(loop_rom());
+        gameboy.run_frame();
+        gameboy.start_vgm(filename).unwrap();
+        bus_write(&mut gameboy.memory, 0xFF17, 0xF0);
+        gameboy.run_frame();
+        bus_write(&mut gameboy.memory, 0xFF19, 0x87);
+        gameboy.run_frame();
+        gameboy.stop_vgm().unwrap();
+        assert!(gameboy.memory.apu_log.is_none());
+        let bytes = std::fs::read(filename).unwrap();
+        std::fs::remove_file(filename).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn test_gameboy_log() {
+        let filename = std::env::temp_dir()
+            .join(format!("blazeboy-{}-log.vgm", std::process::id()))
+            .to_string_lossy()
+            .into_owned();
+        let bytes = log(&filename);
+        let data = &bytes[0x100..];
+        // The current state comes first: power, wave RAM, then the registers. Channel 1
+        // is still playing the boot sound, so it's triggered again
+        assert_eq!(data[0..3], [0xB3, 0x16, 0x80]);
+        assert_eq!(data[3..5], [0xB3, 0x20]);
+        assert!(data.windows(3).any(|command| command == [0xB3, 0x02, 0xF3]));
+        assert!(data.windows(3).any(|command| command == [0xB3, 0x04, 0x87]));
+        // Then the writes, a frame apart
+        let first = data
+            .windows(3)
+            .position(|command| command == [0xB3, 0x07, 0xF0]);
+        let second = data
+            .windows(3)
+            .position(|command| command == [0xB3, 0x09, 0x87]);
+        let (first, second) = (first.unwrap(), second.unwrap());
+        assert_eq!(data[first + 3], 0x61);
+        let wait = u16::from_le_bytes([data[first + 4], data[first + 5]]);
+        assert!((738..=739).contains(&wait), "{}", wait);
+        assert_eq!(first + 6, second);
+        assert_eq!(*data.last().unwrap(), 0x66);
+        // Two frames, ~1477 samples
+        assert!((1470..1480).contains(&word(&bytes, 0x18)));
+
+        // The same run makes the same file
+        assert_eq!(bytes, log(&filename));
+    }
+}
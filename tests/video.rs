@@ -0,0 +1,161 @@
+#[cfg(test)]
+mod video_test {
+
+    use blazeboy::{
+        bus_tick, bus_write, Catridge, ColorCorrection, CompatPalette, DmgPalette, Ghosting,
+        LcdGrid, Memory, VideoOutput,
+    };
+
+    fn build_rom(title: &[u8], licensee: u8) -> Vec<u8> {
+        let mut data = vec![0u8; 0x8000];
+        data[0x134..0x134 + title.len()].copy_from_slice(title);
+        data[0x14B] = licensee;
+        data
+    }
+
+    // Lines of tiles 0-3, each one a solid colour matching its number
+    fn draw_shades(memory: &mut Memory) {
+        for tile in 0..4 {
+            for row in 0..8 {
+                memory.vram[0][tile * 16 + row * 2] = if tile & 1 != 0 { 0xFF } else { 0 };
+                memory.vram[0][tile * 16 + row * 2 + 1] = if tile & 2 != 0 { 0xFF } else { 0 };
+            }
+            memory.vram[0][0x1800 + tile] = tile as u8;
+        }
+        bus_write(memory, 0xFF47, 0xE4);
+        bus_write(memory, 0xFF40, 0x91);
+        bus_tick(memory, 456 * 154);
+    }
+
+    #[test]
+    fn test_dmg_presets() {
+        let mut memory = Memory::new();
+        draw_shades(&mut memory);
+        let mut output = VideoOutput::new();
+        for palette in DmgPalette::PRESETS {
+            output.dmg_palette = palette;
+            let frame = output.rgb(&memory.ppu);
+            let colors = palette.colors();
+            assert_eq!([frame[0], frame[8], frame[16], frame[24]], colors);
+        }
+
+        output.dmg_palette = DmgPalette::PeaGreen;
+        let rgba = output.rgba(&memory.ppu);
+        assert_eq!(rgba.len(), VideoOutput::FRAME_SIZE);
+        assert_eq!(rgba[24 * 4..24 * 4 + 4], [0x0F, 0x38, 0x0F, 0xFF]);
+    }
+
+    #[test]
+    fn test_custom_palette() {
+        let text = "; light to dark\n#E0F8D0 88C070\n346856 081820\n";
+        let palette = DmgPalette::parse(text).unwrap();
+        assert_eq!(palette.colors(), [0xE0F8D0, 0x88C070, 0x346856, 0x081820]);
+        assert!(DmgPalette::parse("FFFFFF 000000").is_err());
+        assert!(DmgPalette::parse("FFFFFF AAAAAA 555555 00000G").is_err());
+        assert!(DmgPalette::load("/nonexistent/palette.txt").is_err());
+        assert_eq!(DmgPalette::from_name("Pocket"), Some(DmgPalette::Pocket));
+    }
+
+    #[test]
+    fn test_color_correction() {
+        assert_eq!(ColorCorrection::Raw.apply(0x7FFF), 0xFFFFFF);
+        assert_eq!(ColorCorrection::Raw.apply(0x001F), 0xFF0000);
+        for correction in [ColorCorrection::Gambatte, ColorCorrection::Higan] {
+            assert_eq!(correction.apply(0x0000), 0x000000);
+            let red = correction.apply(0x001F);
+            // Pure red bleeds into blue on the real LCD
+            assert!(red & 0xFF > 0, "{:?}", correction);
+            assert!(red >> 16 < 0xFF, "{:?}", correction);
+        }
+        assert_eq!(ColorCorrection::Gambatte.apply(0x7FFF), 0xF8F8F8);
+    }
+
+    #[test]
+    fn test_compat_palettes() {
+        let red = Catridge::from_bytes(build_rom(b"POKEMON RED", 0x01)).unwrap();
+        assert_eq!(red.title_checksum(), 0x14);
+        let palette = CompatPalette::for_catridge(&red);
+        assert_eq!(palette.background[1], 0xFF8484);
+
+        let unknown = Catridge::from_bytes(build_rom(b"SOMETHING", 0x01)).unwrap();
+        assert_eq!(
+            CompatPalette::for_catridge(&unknown),
+            CompatPalette::DEFAULT
+        );
+        let third_party = Catridge::from_bytes(build_rom(b"POKEMON RED", 0x08)).unwrap();
+        assert_eq!(
+            CompatPalette::for_catridge(&third_party),
+            CompatPalette::DEFAULT
+        );
+
+        // POKEMON BLUE and VEGAS STAKES share a checksum, the 4th letter tells them apart
+        let blue = Catridge::from_bytes(build_rom(b"POKEMON BLUE", 0x01)).unwrap();
+        let blue = CompatPalette::for_catridge(&blue);
+        assert_eq!(blue.background, [0xFFFFFF, 0x63A5FF, 0x0000FF, 0x000000]);
+        assert_eq!(blue.object0, [0xFFFFFF, 0xFF8484, 0x943939, 0x000000]);
+        assert_eq!(blue.object1, blue.background);
+        let vegas = Catridge::from_bytes(build_rom(b"VEGAS STAKES", 0x01)).unwrap();
+        assert_eq!(vegas.title_checksum(), 0x61);
+        assert_eq!(CompatPalette::for_catridge(&vegas).background[1], 0x7BFF31);
+        let other = Catridge::from_bytes(build_rom(b"POKMEON BLUE", 0x01)).unwrap();
+        assert_eq!(CompatPalette::for_catridge(&other), CompatPalette::DEFAULT);
+
+        let mut memory = Memory::new();
+        palette.apply(&mut memory.ppu);
+        draw_shades(&mut memory);
+        let mut output = VideoOutput::new();
+        output.correction = ColorCorrection::Raw;
+        let frame = output.rgb(&memory.ppu);
+        assert_eq!(frame[0], 0xFFFFFF);
+        assert_eq!(frame[8], 0xFF8484);
+        assert_eq!(frame[24], 0x000000);
+    }
+
+    #[test]
+    fn test_ghosting() {
+        let mut ghosting = Ghosting::new(&[1.0, 1.0]);
+        assert_eq!(ghosting.apply(0, vec![0x000000]), [0x000000]);
+        assert_eq!(ghosting.apply(1, vec![0xFFFFFF]), [0x808080]);
+        // The same frame again replaces it instead of blending with itself
+        assert_eq!(ghosting.apply(1, vec![0x204060]), [0x102030]);
+        assert_eq!(ghosting.apply(2, vec![0xFFFFFF]), [0x90A0B0]);
+        assert_eq!(Ghosting::persistence(3, 0.5).weights(), [1.0, 0.5, 0.25]);
+
+        // A background flickering between white and black every frame blends to grey
+        let mut memory = Memory::new();
+        draw_shades(&mut memory);
+        let mut output = VideoOutput::new();
+        output.ghosting = Some(Ghosting::new(&[1.0, 1.0]));
+        assert_eq!(output.render(&memory.ppu)[0], 0xFFFFFF);
+        bus_write(&mut memory, 0xFF47, 0x1B);
+        bus_tick(&mut memory, 456 * 154);
+        assert_eq!(output.render(&memory.ppu)[0], 0x808080);
+        assert_eq!(output.rgb(&memory.ppu)[0], 0x000000);
+    }
+
+    #[test]
+    fn test_lcd_grid() {
+        let grid = LcdGrid::new(3);
+        let frame = grid.apply(&[0x000000, 0xFFFFFF], 2, 0xFFFFFF);
+        assert_eq!(frame.len(), 6 * 3);
+        assert_eq!(frame[0..6], [0, 0, 0x808080, 0xFFFFFF, 0xFFFFFF, 0xFFFFFF]);
+        assert_eq!(frame[12..15], [0x808080; 3]);
+
+        let mut memory = Memory::new();
+        draw_shades(&mut memory);
+        let mut output = VideoOutput::new();
+        output.dmg_palette = DmgPalette::PeaGreen;
+        output.grid = Some(LcdGrid {
+            scale: 2,
+            strength: 1.0,
+        });
+        assert_eq!(output.size(), (320, 288));
+        let rgba = output.rgba(&memory.ppu);
+        assert_eq!(rgba.len(), 320 * 288 * 4);
+        // Gaps show the lightest colour of the palette
+        let frame = output.render(&memory.ppu);
+        assert_eq!(frame[48], 0x0F380F);
+        assert_eq!(frame[49], 0x9BBC0F);
+        assert_eq!(frame[320 + 48], 0x9BBC0F);
+    }
+}
@@ -0,0 +1,131 @@
+mod common;
+
+#[cfg(test)]
+mod wav_export_test {
+
+    use blazeboy::{bus_write, stem_filename, Apu, GameBoy, Io};
+
+    use crate::common::{loop_rom, temp_file};
+
+    // Square 2 on the left only, noise on the right only
+    fn start_channels(gameboy: &mut GameBoy) {
+        let memory = &mut gameboy.memory;
+        bus_write(memory, 0xFF24, 0x77);
+        bus_write(memory, 0xFF25, 0x28);
+        bus_write(memory, 0xFF17, 0xF0);
+        bus_write(memory, 0xFF18, 0x00);
+        bus_write(memory, 0xFF19, 0x87);
+        bus_write(memory, 0xFF21, 0xF0);
+        bus_write(memory, 0xFF22, 0x10);
+        bus_write(memory, 0xFF23, 0x80);
+    }
+
+    fn pcm(filename: &str) -> Vec<i16> {
+        let bytes = std::fs::read(filename).unwrap();
+        assert_eq!(&bytes[0..4], b"RIFF");
+        bytes[44..]
+            .chunks_exact(2)
+            .map(|pair| i16::from_le_bytes([pair[0], pair[1]]))
+            .collect()
+    }
+
+    #[test]
+    fn test_mute_solo() {
+        let mut apu = Apu::new();
+        let mut io = Io::new();
+        apu.write(&mut io, 0xFF24, 0x77, false);
+        apu.write(&mut io, 0xFF25, 0xFF, false);
+        apu.write(&mut io, 0xFF17, 0xF0, false);
+        apu.write(&mut io, 0xFF19, 0x80, false);
+        apu.write(&mut io, 0xFF21, 0xF0, false);
+        apu.write(&mut io, 0xFF23, 0x80, false);
+        let both = apu.mix();
+        let square = apu.channel_mix(1);
+        let noise = apu.channel_mix(3);
+        assert_eq!(both[0], square[0] + noise[0]);
+
+        apu.muted[3] = true;
+        assert!(!apu.audible(3));
+        assert_eq!(apu.mix(), square);
+        apu.solo = Some(3);
+        assert!(apu.audible(3) && !apu.audible(1));
+        assert_eq!(apu.mix(), noise);
+        // The channel's own output doesn't care about mutes
+        assert_eq!(apu.channel_mix(1), square);
+        apu.solo = None;
+        apu.muted = [true; 4];
+        assert_eq!(apu.mix(), [0, 0]);
+    }
+
+    #[test]
+    fn test_stem_filename() {
+        assert_eq!(stem_filename("song.wav", 1), "song.ch1.wav");
+        assert_eq!(stem_filename("out/song.wav", 4), "out/song.ch4.wav");
+    }
+
+    #[test]
+    fn test_wav_stems() {
+        let filename = temp_file("stems.wav");
+        let mut gameboy = GameBoy::from_catridge(loop_rom());
+        gameboy.start_wav(&filename, 44100, true).unwrap();
+        start_channels(&mut gameboy);
+        for _ in 0..30 {
+            gameboy.run_frame();
+        }
+        let hash = gameboy.stop_wav().unwrap().unwrap();
+        assert_eq!(gameboy.stop_wav().unwrap(), None);
+        assert!(gameboy.memory.apu.take_samples().is_empty());
+        assert!(gameboy.memory.apu.take_channel_samples()[1].is_empty());
+
+        let mix = pcm(&filename);
+        // 30 frames are a bit over half a second, ~22150 stereo frames at 44.1 kHz
+        assert!((44100..44300).contains(&mix.len()), "{}", mix.len());
+        let stems: Vec<Vec<i16>> = (1..=4)
+            .map(|channel| pcm(&stem_filename(&filename, channel)))
+            .collect();
+        assert!(stems.iter().all(|stem| stem.len() == mix.len()));
+        // Silent channels make silent stems, the others stay on their side
+        assert!(stems[0].iter().chain(&stems[2]).all(|&sample| sample == 0));
+        assert!(stems[1].iter().step_by(2).any(|&sample| sample > 1000));
+        assert!(stems[1]
+            .iter()
+            .skip(1)
+            .step_by(2)
+            .all(|&sample| sample == 0));
+        assert!(stems[3]
+            .iter()
+            .skip(1)
+            .step_by(2)
+            .any(|&sample| sample > 1000));
+        assert!(stems[3].iter().step_by(2).all(|&sample| sample == 0));
+        assert_ne!(hash, 0);
+
+        std::fs::remove_file(&filename).unwrap();
+        for channel in 1..=4 {
+            std::fs::remove_file(stem_filename(&filename, channel)).unwrap();
+        }
+    }
+
+    // Records a second of the two channels, with `muted` left out
+    fn record(name: &str, muted: [bool; 4]) -> u64 {
+        let filename = temp_file(name);
+        let mut gameboy = GameBoy::from_catridge(loop_rom());
+        gameboy.memory.apu.muted = muted;
+        gameboy.start_wav(&filename, 48000, false).unwrap();
+        start_channels(&mut gameboy);
+        for _ in 0..60 {
+            gameboy.run_frame();
+        }
+        let hash = gameboy.stop_wav().unwrap().unwrap();
+        assert!(!std::path::Path::new(&stem_filename(&filename, 1)).exists());
+        std::fs::remove_file(&filename).unwrap();
+        hash
+    }
+
+    #[test]
+    fn test_wav_hash() {
+        let hash = record("hash-a.wav", [false; 4]);
+        assert_eq!(hash, record("hash-b.wav", [false; 4]));
+        assert_ne!(hash, record("hash-c.wav", [false, false, false, true]));
+    }
+}